@@ -16,6 +16,11 @@ pub fn proportional(amount: u64, numerator: u64, denominator: u64) -> Result<u64
         .map_err(|_| error!(MarinadeError::CalculationFailure))
 }
 
+/// checked conversion of a signed stake delta, negate it first to get the unstake amount
+pub fn u64_from_i128(value: i128) -> Result<u64> {
+    u64::try_from(value).map_err(|_| error!(MarinadeError::CalculationFailure))
+}
+
 #[inline] //alias for proportional
 pub fn value_from_shares(shares: u64, total_value: u64, total_shares: u64) -> Result<u64> {
     proportional(shares, total_value, total_shares)
@@ -1,7 +1,10 @@
-use crate::MarinadeError;
+use crate::{state::stake_system::StakeSystem, MarinadeError};
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::stake::state::StakeState;
+use anchor_lang::solana_program::{
+    stake::state::StakeState, stake_history::StakeHistoryEntry, sysvar::stake_history, vote,
+};
 use anchor_spl::token::{Mint, TokenAccount};
+use std::convert::TryInto;
 
 pub fn check_owner_program<'info, A: ToAccountInfo<'info>>(
     account: &A,
@@ -25,6 +28,25 @@ pub fn check_owner_program<'info, A: ToAccountInfo<'info>>(
     }
 }
 
+/// the vote account which is not rent exempt can be reclaimed with the delegated stake in it
+pub fn check_vote_account_rent_exempt(
+    vote: &AccountInfo,
+    rent: &Rent,
+    field_name: &str,
+) -> Result<()> {
+    if rent.is_exempt(vote.lamports(), vote.data_len()) {
+        Ok(())
+    } else {
+        msg!(
+            "Vote account {} balance {} is lower than rent exempt {}",
+            vote.key,
+            vote.lamports(),
+            rent.minimum_balance(vote.data_len())
+        );
+        Err(error!(MarinadeError::ValidatorVoteNotRentExempt).with_account_name(field_name))
+    }
+}
+
 pub fn check_mint_authority(mint: &Mint, mint_authority: &Pubkey, field_name: &str) -> Result<()> {
     if mint.mint_authority.contains(mint_authority) {
         Ok(())
@@ -85,6 +107,139 @@ pub fn check_token_owner(token: &TokenAccount, owner: &Pubkey, field_name: &str)
     }
 }
 
+pub fn check_stake_account_rent_exempt(
+    stake_account: &AccountInfo,
+    rent: &Rent,
+    field_name: &str,
+) -> Result<()> {
+    let expected = StakeSystem::stake_account_rent_exempt(rent, stake_account.data_len());
+    let actual = stake_account.lamports();
+    if actual < expected {
+        msg!(
+            "Stake account {} balance {} is lower than rent exempt {}",
+            stake_account.key,
+            actual,
+            expected
+        );
+        return Err(error!(MarinadeError::StakeAccountNotRentExempt)
+            .with_account_name(field_name)
+            .with_values((actual, expected)));
+    }
+    Ok(())
+}
+
+/// Slot of the latest vote in the vote account tower without parsing the whole vote state:
+/// u32 version, node_pubkey, authorized_withdrawer, commission, then the votes deque
+/// (V0_23_5 has the authorized voter epoch and the prior voters before the withdrawer).
+/// None if there are no votes. InvalidVoteAccount for an unknown version,
+/// a vote state layout newer than this program must not make the validator look delinquent
+pub fn vote_account_last_voted_slot(vote: &AccountInfo) -> Result<Option<u64>> {
+    require_keys_eq!(
+        *vote.owner,
+        vote::program::ID,
+        MarinadeError::InvalidVoteAccount
+    );
+    let data = vote.data.borrow();
+    let read = |offset: usize, len: usize| -> Result<&[u8]> {
+        data.get(offset..offset + len)
+            .ok_or_else(|| error!(MarinadeError::InvalidVoteAccount))
+    };
+    // version, node_pubkey, authorized_withdrawer, commission
+    const VOTES_OFFSET: usize = 4 + 32 + 32 + 1;
+    // V0_23_5 has authorized_voter, authorized_voter_epoch
+    // and prior_voters: 32 * (Pubkey, Epoch, Epoch, Slot) and the usize index in addition
+    const V0_23_5_VOTES_OFFSET: usize = VOTES_OFFSET + 32 + 8 + 32 * 56 + 8;
+    // (offset of the votes deque, vote size, offset of the slot in the vote)
+    let (votes_offset, vote_size, slot_offset) =
+        match u32::from_le_bytes(read(0, 4)?.try_into().unwrap()) {
+            0 => (V0_23_5_VOTES_OFFSET, 12, 0), // V0_23_5: Lockout { slot, confirmation_count }
+            1 => (VOTES_OFFSET, 12, 0),         // V1_14_11: Lockout { slot, confirmation_count }
+            2 => (VOTES_OFFSET, 13, 1),         // Current: LandedVote { latency, lockout }
+            version => {
+                msg!("Unknown vote state version {}", version);
+                return Err(error!(MarinadeError::InvalidVoteAccount));
+            }
+        };
+    let votes_len = u64::from_le_bytes(read(votes_offset, 8)?.try_into().unwrap()) as usize;
+    if votes_len == 0 {
+        return Ok(None);
+    }
+    let last_vote_offset = votes_len
+        .checked_sub(1)
+        .and_then(|last| last.checked_mul(vote_size))
+        .and_then(|offset| offset.checked_add(votes_offset + 8 + slot_offset))
+        .ok_or_else(|| error!(MarinadeError::InvalidVoteAccount))?;
+    Ok(Some(u64::from_le_bytes(
+        read(last_vote_offset, 8)?.try_into().unwrap(),
+    )))
+}
+
+/// The latest entry of the stake history sysvar without parsing the whole history:
+/// u64 entries count followed by (epoch, effective, activating, deactivating) entries, newest first.
+/// Five u64 reads whatever the history length; there is no measured CU ceiling for it
+pub fn latest_stake_history_entry(
+    stake_history: &AccountInfo,
+) -> Result<Option<(u64, StakeHistoryEntry)>> {
+    require_keys_eq!(*stake_history.key, stake_history::ID);
+    let data = stake_history.data.borrow();
+    let read_u64 = |offset: usize| -> Result<u64> {
+        Ok(u64::from_le_bytes(
+            data.get(offset..offset + 8)
+                .ok_or_else(|| error!(ErrorCode::AccountDidNotDeserialize))?
+                .try_into()
+                .unwrap(),
+        ))
+    };
+    if read_u64(0)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some((
+        read_u64(8)?,
+        StakeHistoryEntry {
+            effective: read_u64(16)?,
+            activating: read_u64(24)?,
+            deactivating: read_u64(32)?,
+        },
+    )))
+}
+
+/// the account must not alias any of the other accounts of the instruction
+pub fn check_distinct_account(
+    account: &Pubkey,
+    other_accounts: &[(&Pubkey, &str)],
+    field_name: &str,
+) -> Result<()> {
+    for (other_account, other_field_name) in other_accounts {
+        if account == *other_account {
+            msg!(
+                "{} {} is also passed as {}",
+                field_name,
+                account,
+                other_field_name
+            );
+            return Err(error!(MarinadeError::DuplicateAccount).with_account_name(field_name));
+        }
+    }
+    Ok(())
+}
+
+// rent of the created stake accounts is calculated for this size
+pub fn check_stake_account_size(data_len: usize, field_name: &str) -> Result<()> {
+    let expected = std::mem::size_of::<StakeState>();
+    if data_len != expected {
+        msg!(
+            "Invalid {} size {}. Expected {}",
+            field_name,
+            data_len,
+            expected
+        );
+        return Err(error!(MarinadeError::InvalidStakeAccountSize)
+            .with_account_name(field_name)
+            .with_values((data_len, expected)));
+    }
+    Ok(())
+}
+
 // check that the account is delegated and to the right validator
 // also that the stake amount is updated
 pub fn check_stake_amount_and_validator(
@@ -157,3 +312,124 @@ pub fn check_token_source_account<'info>(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::*;
+    use anchor_lang::solana_program::stake::state::StakeState;
+
+    use super::{check_distinct_account, check_stake_account_size, vote_account_last_voted_slot};
+    use crate::MarinadeError;
+    use anchor_lang::solana_program::vote::{
+        self,
+        state::{Lockout, VoteState, VoteStateVersions},
+    };
+
+    fn last_voted_slot(mut data: Vec<u8>) -> Result<Option<u64>> {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &vote::program::ID,
+            false,
+            0,
+        );
+        vote_account_last_voted_slot(&info)
+    }
+
+    fn serialize_vote_state(versioned: &VoteStateVersions) -> Vec<u8> {
+        let mut data = vec![0; VoteState::size_of()];
+        VoteState::serialize(versioned, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_vote_account_last_voted_slot_v0_23_5() {
+        let mut versioned = VoteStateVersions::V0_23_5(Box::default());
+        assert_eq!(last_voted_slot(serialize_vote_state(&versioned)), Ok(None));
+        if let VoteStateVersions::V0_23_5(vote_state) = &mut versioned {
+            vote_state.votes.push_back(Lockout::new(100));
+            vote_state.votes.push_back(Lockout::new(101));
+        }
+        assert_eq!(
+            last_voted_slot(serialize_vote_state(&versioned)),
+            Ok(Some(101))
+        );
+    }
+
+    #[test]
+    fn test_vote_account_last_voted_slot_v1_14_11() {
+        let mut vote_state = VoteState::default();
+        let versioned = VoteStateVersions::new_current(vote_state.clone());
+        assert_eq!(last_voted_slot(serialize_vote_state(&versioned)), Ok(None));
+        vote_state.votes.push_back(Lockout::new(200));
+        vote_state.votes.push_back(Lockout::new(201));
+        let versioned = VoteStateVersions::new_current(vote_state);
+        assert_eq!(
+            last_voted_slot(serialize_vote_state(&versioned)),
+            Ok(Some(201))
+        );
+    }
+
+    #[test]
+    fn test_vote_account_last_voted_slot_current() {
+        // version, node_pubkey, authorized_withdrawer, commission
+        let mut data = 2u32.to_le_bytes().to_vec();
+        data.extend([0; 32 + 32 + 1]);
+        data.extend(2u64.to_le_bytes());
+        // LandedVote { latency, lockout: Lockout { slot, confirmation_count } }
+        for slot in [300u64, 301] {
+            data.push(1);
+            data.extend(slot.to_le_bytes());
+            data.extend(1u32.to_le_bytes());
+        }
+        assert_eq!(last_voted_slot(data.clone()), Ok(Some(301)));
+        // the last slot is cut
+        data.truncate(data.len() - 4 - 1);
+        assert_eq!(
+            last_voted_slot(data),
+            Err(error!(MarinadeError::InvalidVoteAccount))
+        );
+    }
+
+    #[test]
+    fn test_vote_account_last_voted_slot_unknown_version() {
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend([0; 32 + 32 + 1 + 8]);
+        assert_eq!(
+            last_voted_slot(data),
+            Err(error!(MarinadeError::InvalidVoteAccount))
+        );
+    }
+
+    #[test]
+    fn test_check_stake_account_size() {
+        let size = std::mem::size_of::<StakeState>();
+        assert!(check_stake_account_size(size, "stake_account").is_ok());
+        for wrong_size in [0, size - 1, size + 1] {
+            assert_eq!(
+                check_stake_account_size(wrong_size, "stake_account"),
+                Err(error!(MarinadeError::InvalidStakeAccountSize))
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_distinct_account() {
+        let account = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        assert!(check_distinct_account(&account, &[(&other, "other")], "account").is_ok());
+        assert_eq!(
+            check_distinct_account(
+                &account,
+                &[(&other, "other"), (&account, "same")],
+                "account"
+            ),
+            Err(error!(MarinadeError::DuplicateAccount))
+        );
+    }
+}
@@ -270,4 +270,114 @@ pub enum MarinadeError {
 
     #[msg("Capacity of the list must be not less than it's current size")]
     ShrinkingListWithDeletingContents, // 6086 0x17c6
+
+    #[msg("Validator vote account is not rent exempt")]
+    ValidatorVoteNotRentExempt, // 6087 0x17c7
+
+    #[msg("Invalid stake account size")]
+    InvalidStakeAccountSize, // 6088 0x17c8
+
+    #[msg("Invalid validator pool")]
+    InvalidValidatorPool, // 6089 0x17c9
+
+    #[msg("Validator pool allocations must sum to 100% or be all zero")]
+    InvalidValidatorPoolAllocations, // 6090 0x17ca
+
+    #[msg("Stake delta window must be shorter than the epoch")]
+    StakeDeltaWindowIsTooLong, // 6091 0x17cb
+
+    #[msg("Stake account is not rent exempt")]
+    StakeAccountNotRentExempt, // 6092 0x17cc
+
+    #[msg("Nothing to stake")]
+    NothingToStake, // 6093 0x17cd
+
+    #[msg("Double delta stake command for validator")]
+    DoubleStakeDelta, // 6094 0x17ce
+
+    #[msg("Validator has already reached stake target")]
+    ValidatorAlreadyOnTarget, // 6095 0x17cf
+
+    #[msg("Extra stake delta runs are over the max")]
+    ExtraStakeDeltaRunsTooHigh, // 6096 0x17d0
+
+    #[msg("Staking is paused")]
+    StakingPaused, // 6097 0x17d1
+
+    #[msg("Unstaking is paused")]
+    UnstakingPaused, // 6098 0x17d2
+
+    #[msg("Deposits are paused")]
+    DepositsPaused, // 6099 0x17d3
+
+    #[msg("Invalid pause flags")]
+    InvalidPauseFlags, // 6100 0x17d4
+
+    #[msg("Invalid vote account")]
+    InvalidVoteAccount, // 6101 0x17d5
+
+    #[msg("Too many stake accounts for the validator")]
+    TooManyStakeAccounts, // 6102 0x17d6
+
+    #[msg("Stake history is not updated for the epoch")]
+    StakeHistoryNotUpdated, // 6103 0x17d7
+
+    #[msg("Accounting invariant violation")]
+    InvariantViolation, // 6104 0x17d8
+
+    #[msg("Stake account is already in the stake list")]
+    StakeAccountAlreadyRegistered, // 6105 0x17d9
+
+    #[msg("Stake account authority is not the program authority")]
+    InvalidStakeAccountAuthority, // 6106 0x17da
+
+    #[msg("Validator index is out of the validator list")]
+    InvalidValidatorIndex, // 6107 0x17db
+
+    #[msg("Reconcile page must start where the previous one ended")]
+    ReconcilePageOutOfOrder, // 6108 0x17dc
+
+    #[msg("Not enough stake accounts to split the stake")]
+    NotEnoughStakeAccounts, // 6109 0x17dd
+
+    #[msg("Too many preferred validators")]
+    TooManyPreferredValidators, // 6110 0x17de
+
+    #[msg("Preferred validator list is empty")]
+    NoPreferredValidators, // 6111 0x17df
+
+    #[msg("Reserve would fall below its rent exempt and retention floor")]
+    ReserveWouldNotBeRentExempt, // 6112 0x17e0
+
+    #[msg("Stake record has no validator, call sort_stake_list first")]
+    StakeRecordValidatorUnknown, // 6113 0x17e1
+
+    #[msg("Validator not found in the scanned part of the validator list")]
+    ValidatorNotFound, // 6114 0x17e2
+
+    #[msg("The same account is passed for two different accounts")]
+    DuplicateAccount, // 6115 0x17e3
+
+    #[msg("The transaction is not signed by an allowed crank authority")]
+    UnauthorizedCrank, // 6116 0x17e4
+    #[msg("Too many crank authorities")]
+    TooManyCrankAuthorities, // 6117 0x17e5
+
+    #[msg("Invalid score oracle")]
+    InvalidScoreOracle, // 6118 0x17e6
+
+    #[msg("List header does not match the list account")]
+    InvalidListAccount, // 6119 0x17e7
+
+    #[msg("State account layout is unknown or already migrated")]
+    InvalidStateLayout, // 6120 0x17e8
+
+    #[msg("Stake account is pre-funded over the stake amount")]
+    StakeAccountPrefundedOverStake, // 6121 0x17e9
+    #[msg("Pool scores must be recomputed before enabling the pool allocations")]
+    PoolScoresNotRecomputed, // 6122 0x17ea
+    #[msg("Stake lockup custodian is not the stake withdraw authority")]
+    InvalidStakeLockupCustodian, // 6123 0x17eb
+    #[msg("Stake lockup is not in force")]
+    StakeLockupNotInForce, // 6124 0x17ec
 }
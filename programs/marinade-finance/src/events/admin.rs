@@ -1,9 +1,13 @@
 use anchor_lang::prelude::*;
 
-use crate::instructions::InitializeData;
+use crate::{
+    instructions::InitializeData,
+    state::{validator_system::MAX_VALIDATOR_POOLS, Fee},
+};
 
 use super::{
-    BoolValueChange, FeeCentsValueChange, FeeValueChange, PubkeyValueChange, U64ValueChange,
+    BoolValueChange, FeeCentsValueChange, FeeValueChange, I64ValueChange, PubkeyValueChange,
+    ReserveStakeSourceValueChange, U32ValueChange, U64ValueChange, U8ValueChange,
 };
 
 #[event]
@@ -14,6 +18,7 @@ pub struct ChangeAuthorityEvent {
     pub operational_sol_account_change: Option<PubkeyValueChange>,
     pub treasury_msol_account_change: Option<PubkeyValueChange>,
     pub pause_authority_change: Option<PubkeyValueChange>,
+    pub score_oracle_change: Option<PubkeyValueChange>,
 }
 
 #[event]
@@ -39,6 +44,119 @@ pub struct ConfigMarinadeEvent {
     pub delayed_unstake_fee_change: Option<FeeCentsValueChange>,
     pub withdraw_stake_account_fee_change: Option<FeeCentsValueChange>,
     pub max_stake_moved_per_epoch_change: Option<FeeValueChange>,
+    pub sort_stakes_by_validator_change: Option<BoolValueChange>,
+    pub check_vote_rent_exempt_change: Option<BoolValueChange>,
+    pub max_total_active_balance_change: Option<U64ValueChange>,
+    pub reserve_stake_source_change: Option<ReserveStakeSourceValueChange>,
+    pub stake_window_start_allowance_change: Option<FeeValueChange>,
+    pub stake_over_target_change: Option<BoolValueChange>,
+    pub reserve_retention_lamports_change: Option<U64ValueChange>,
+    pub max_extra_stake_delta_runs_change: Option<U32ValueChange>,
+    pub max_stake_accounts_per_validator_change: Option<U32ValueChange>,
+    pub parse_stake_history_change: Option<BoolValueChange>,
+    pub stake_cooldown_epochs_change: Option<U64ValueChange>,
+    pub invariant_checks_change: Option<BoolValueChange>,
+    pub check_vote_activity_change: Option<BoolValueChange>,
+    pub max_idle_epochs_change: Option<U64ValueChange>,
+    pub max_stake_operations_per_epoch_change: Option<U32ValueChange>,
+    pub stake_lockup_epoch_change: Option<U64ValueChange>,
+    pub stake_lockup_unix_timestamp_change: Option<I64ValueChange>,
+    pub max_stake_per_account_change: Option<U64ValueChange>,
+    pub stake_rounding_lamports_change: Option<U64ValueChange>,
+    pub max_stake_per_call_change: Option<U64ValueChange>,
+    pub min_stake_delta_to_act_change: Option<U64ValueChange>,
+    pub new_validator_ramp_change: Option<FeeValueChange>,
+    pub selection_seed_change: Option<U64ValueChange>,
+}
+
+#[event]
+pub struct ConfigValidatorPoolsEvent {
+    pub state: Pubkey,
+    pub old_allocations: [Fee; MAX_VALIDATOR_POOLS],
+    pub new_allocations: [Fee; MAX_VALIDATOR_POOLS],
+}
+
+#[event]
+pub struct SetStakeDeltaWindowEvent {
+    pub state: Pubkey,
+    pub slots_for_stake_delta_change: U64ValueChange,
+}
+
+#[event]
+pub struct SetValidatorMaxStakeEvent {
+    pub state: Pubkey,
+    pub validator: Pubkey,
+    pub index: u32,
+    pub max_stake_change: U64ValueChange,
+}
+
+#[event]
+pub struct SetValidatorDrainOnlyEvent {
+    pub state: Pubkey,
+    pub validator: Pubkey,
+    pub index: u32,
+    pub drain_only_change: BoolValueChange,
+}
+
+#[event]
+pub struct ResetReserveLowWaterEvent {
+    pub state: Pubkey,
+    pub reserve_low_water_lamports: u64,
+}
+
+#[event]
+pub struct RefillExtraStakeDeltaRunsEvent {
+    pub state: Pubkey,
+    pub epoch: u64,
+    pub extra_stake_delta_runs_change: U32ValueChange,
+}
+
+#[event]
+pub struct SetPauseFlagsEvent {
+    pub state: Pubkey,
+    pub pause_flags_change: U8ValueChange,
+}
+
+#[event]
+pub struct AbsorbStakeAccountEvent {
+    pub state: Pubkey,
+    pub epoch: u64,
+    pub stake_index: u32,
+    pub stake_account: Pubkey,
+    pub validator_index: u32,
+    pub validator_vote: Pubkey,
+    pub delegated: u64,
+    pub validator_active_balance: u64,
+    pub total_active_balance: u64,
+}
+
+#[event]
+pub struct ReconcileTotalActiveBalanceEvent {
+    pub state: Pubkey,
+    pub validator_count: u32,
+    pub total_active_balance_change: U64ValueChange,
+}
+
+#[event]
+pub struct RecomputePoolScoresEvent {
+    pub state: Pubkey,
+    pub validator_count: u32,
+    pub old_pool_scores: [u32; MAX_VALIDATOR_POOLS],
+    pub new_pool_scores: [u32; MAX_VALIDATOR_POOLS],
+}
+
+#[event]
+pub struct SetPreferredValidatorsEvent {
+    pub state: Pubkey,
+    pub old_preferred_validators: Vec<u32>,
+    pub new_preferred_validators: Vec<u32>,
+}
+
+#[event]
+pub struct SetCrankAuthoritiesEvent {
+    pub state: Pubkey,
+    pub old_crank_authorities: Vec<Pubkey>,
+    pub new_crank_authorities: Vec<Pubkey>,
 }
 
 // TODO: ConfigValidatorSystemEvent?
@@ -71,6 +189,7 @@ pub struct ReallocValidatorListEvent {
     pub state: Pubkey,
     pub count: u32,
     pub new_capacity: u32,
+    pub record_size_change: Option<U32ValueChange>, // None if the records were not migrated
 }
 
 #[event]
@@ -78,4 +197,58 @@ pub struct ReallocStakeListEvent {
     pub state: Pubkey,
     pub count: u32,
     pub new_capacity: u32,
+    pub record_size_change: Option<U32ValueChange>, // None if the records were not migrated
+}
+
+#[event]
+pub struct ReallocStateEvent {
+    pub state: Pubkey,
+    pub layout_version_change: U8ValueChange,
+    pub old_len: u32,
+    pub new_len: u32,
+}
+
+#[event]
+pub struct SortStakeListEvent {
+    pub state: Pubkey,
+    pub sorted_count: u32,
+    pub count: u32,
+}
+
+/// Warning: it is time to call realloc_validator_list
+#[event]
+pub struct ValidatorListNearCapacityEvent {
+    pub state: Pubkey,
+    pub count: u32,
+    pub capacity: u32,
+}
+
+/// Warning: it is time to call realloc_stake_list
+#[event]
+pub struct StakeListNearCapacityEvent {
+    pub state: Pubkey,
+    pub count: u32,
+    pub capacity: u32,
+}
+
+#[event]
+pub struct ReconcileStakeEvent {
+    pub state: Pubkey,
+    pub epoch: u64,
+    pub stake_index: u32,
+    pub stake_account: Pubkey,
+    pub validator_index: u32,
+    pub validator_vote: Pubkey,
+    pub delegation_change: U64ValueChange,
+    pub validator_active_balance_change: U64ValueChange,
+    pub total_active_balance_change: U64ValueChange,
+}
+
+#[event]
+pub struct LiftStakeLockupEvent {
+    pub state: Pubkey,
+    pub epoch: u64,
+    pub stake_account: Pubkey,
+    pub lockup_epoch: u64,
+    pub lockup_unix_timestamp: i64,
 }
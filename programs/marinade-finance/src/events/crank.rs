@@ -1,6 +1,9 @@
 use anchor_lang::prelude::*;
 
-use crate::state::Fee;
+use crate::{
+    instructions::ValidatorScoreUpdate,
+    state::{stake_system::StakeReserveStatus, Fee},
+};
 
 use super::U64ValueChange;
 
@@ -86,6 +89,62 @@ pub struct StakeReserveEvent {
     pub validator_active_balance: u64,
     pub total_stake_delta: u64,
     pub amount: u64,
+    pub cranker: Pubkey,
+    pub total_active_balance_after: u64,
+    pub available_reserve_balance_after: u64,
+}
+
+/// lamports left the reserve, emitted by every transfer from the reserve for reconciliation
+#[event]
+pub struct ReserveTransferEvent {
+    pub state: Pubkey,
+    pub epoch: u64,
+    pub amount: u64,
+    pub available_reserve_balance: u64,
+}
+
+/// Warning: stake_reserve used an extra run to stake a validator again in the epoch
+#[event]
+pub struct ExtraStakeDeltaRunConsumedEvent {
+    pub state: Pubkey,
+    pub epoch: u64,
+    pub validator_index: u32,
+    pub validator_vote: Pubkey,
+    pub extra_stake_delta_runs_left: u32,
+}
+
+/// Warning: confirm_delegation found the stake account not delegated to the recorded validator
+/// or deactivating
+#[event]
+pub struct DelegationAnomalyEvent {
+    pub state: Pubkey,
+    pub epoch: u64,
+    pub stake_index: u32,
+    pub stake_account: Pubkey,
+    pub expected_validator: Pubkey,
+    /// None if the stake account is not delegated
+    pub delegated_validator: Option<Pubkey>,
+    /// u64::MAX if not deactivating
+    pub deactivation_epoch: u64,
+}
+
+/// oracle scores applied by stake_reserve_with_scores
+#[event]
+pub struct ApplyOracleScoresEvent {
+    pub state: Pubkey,
+    pub epoch: u64,
+    pub scores: Vec<ValidatorScoreUpdate>,
+    pub persisted: bool,
+}
+
+/// stake_reserve finished without staking
+#[event]
+pub struct StakeReserveSkippedEvent {
+    pub state: Pubkey,
+    pub epoch: u64,
+    pub validator_index: u32,
+    pub validator_vote: Pubkey,
+    pub reason: StakeReserveStatus,
 }
 
 #[event]
@@ -1,6 +1,6 @@
 use anchor_lang::prelude::*;
 
-use super::U32ValueChange;
+use super::{U32ValueChange, U64ValueChange, U8ValueChange};
 
 #[event]
 pub struct AddValidatorEvent {
@@ -28,3 +28,23 @@ pub struct SetValidatorScoreEvent {
     pub index: u32,
     pub score_change: U32ValueChange,
 }
+
+/// stake_reserve_sweep: the stake delta staked over the validator target
+#[event]
+pub struct SweepStakeEvent {
+    pub state: Pubkey,
+    pub epoch: u64,
+    pub validator_index: u32,
+    pub validator_vote: Pubkey,
+    pub amount: u64,
+    pub remaining_stake_delta: u64,
+}
+
+#[event]
+pub struct ConfigValidatorEvent {
+    pub state: Pubkey,
+    pub validator: Pubkey,
+    pub index: u32,
+    pub min_delegation_change: Option<U64ValueChange>,
+    pub pool_change: Option<U8ValueChange>,
+}
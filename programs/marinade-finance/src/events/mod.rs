@@ -1,6 +1,6 @@
 use anchor_lang::prelude::*;
 
-use crate::state::{fee::FeeCents, Fee};
+use crate::state::{fee::FeeCents, Fee, ReserveStakeSource};
 
 pub mod admin;
 pub mod crank;
@@ -15,12 +15,24 @@ pub struct U64ValueChange {
     pub new: u64,
 }
 
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct I64ValueChange {
+    pub old: i64,
+    pub new: i64,
+}
+
 #[derive(Clone, AnchorSerialize, AnchorDeserialize)]
 pub struct U32ValueChange {
     pub old: u32,
     pub new: u32,
 }
 
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct U8ValueChange {
+    pub old: u8,
+    pub new: u8,
+}
+
 #[derive(Clone, AnchorSerialize, AnchorDeserialize)]
 pub struct FeeValueChange {
     pub old: Fee,
@@ -44,3 +56,9 @@ pub struct BoolValueChange {
     pub old: bool,
     pub new: bool,
 }
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct ReserveStakeSourceValueChange {
+    pub old: ReserveStakeSource,
+    pub new: ReserveStakeSource,
+}
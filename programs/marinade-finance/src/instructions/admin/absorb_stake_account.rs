@@ -0,0 +1,306 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake::state::{Delegation, StakeState};
+use anchor_spl::stake::StakeAccount;
+
+use crate::{
+    error::MarinadeError,
+    events::admin::{AbsorbStakeAccountEvent, StakeListNearCapacityEvent},
+    state::{
+        stake_system::{StakeList, StakeSystem},
+        validator_system::ValidatorList,
+    },
+    State,
+};
+
+#[derive(Accounts)]
+pub struct AbsorbStakeAccount<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Box<Account<'info, State>>,
+    pub admin_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        address = state.validator_system.validator_list.account,
+    )]
+    pub validator_list: Account<'info, ValidatorList>,
+    #[account(
+        mut,
+        address = state.stake_system.stake_list.account,
+    )]
+    pub stake_list: Account<'info, StakeList>,
+
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> AbsorbStakeAccount<'info> {
+    /// Registers a stake account already delegated under the program stake authorities
+    /// (e.g. one migrated from another deployment) without any stake program CPI.
+    /// No mSOL is minted: the delegated lamports are added to the pool
+    pub fn process(&mut self, validator_index: u32) -> Result<()> {
+        require!(!self.state.paused, MarinadeError::ProgramIsPaused);
+
+        let delegation = check_stake_to_absorb(
+            &self.state,
+            &self.state.key(),
+            &self.stake_account,
+            &self.clock,
+        )?;
+
+        // the same stake account must not be counted twice
+        require!(
+            !self.state.stake_system.contains_stake_account(
+                &self.stake_list.to_account_info().data.as_ref().borrow(),
+                self.stake_account.to_account_info().key,
+            )?,
+            MarinadeError::StakeAccountAlreadyRegistered
+        );
+
+        let mut validator = self.state.validator_system.get_checked(
+            &self.validator_list.to_account_info().data.as_ref().borrow(),
+            validator_index,
+            &delegation.voter_pubkey,
+        )?;
+        // record for event
+        let validator_active_balance = validator.active_balance;
+        let total_active_balance = self.state.validator_system.total_active_balance;
+
+        let stake_index = self.state.add_stake(
+            &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
+            self.stake_account.to_account_info().key,
+            &delegation.voter_pubkey,
+            delegation.stake,
+            &self.clock,
+            0, // is_emergency_unstaking? no
+        )?;
+        if let Some(capacity) = self
+            .state
+            .stake_system
+            .stake_list_crossed_near_capacity(self.stake_list.to_account_info().data_len(), 1)?
+        {
+            emit!(StakeListNearCapacityEvent {
+                state: self.state.key(),
+                count: self.state.stake_system.stake_count(),
+                capacity,
+            });
+        }
+
+        validator.active_balance = validator
+            .active_balance
+            .checked_add(delegation.stake)
+            .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+        validator.on_stake_account_added();
+        self.state.validator_system.set(
+            &mut self
+                .validator_list
+                .to_account_info()
+                .data
+                .as_ref()
+                .borrow_mut(),
+            validator_index,
+            validator,
+        )?;
+        self.state.validator_system.total_active_balance = total_active_balance
+            .checked_add(delegation.stake)
+            .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+
+        emit!(AbsorbStakeAccountEvent {
+            state: self.state.key(),
+            epoch: self.clock.epoch,
+            stake_index,
+            stake_account: self.stake_account.key(),
+            validator_index,
+            validator_vote: delegation.voter_pubkey,
+            delegated: delegation.stake,
+            validator_active_balance,
+            total_active_balance,
+        });
+        Ok(())
+    }
+}
+
+/// the stake account is actively delegated under the program stake authorities and not locked up.
+/// Returns its delegation
+pub(crate) fn check_stake_to_absorb(
+    state: &State,
+    state_address: &Pubkey,
+    stake_account: &StakeState,
+    clock: &Clock,
+) -> Result<Delegation> {
+    let delegation = stake_account.delegation().ok_or_else(|| {
+        error!(MarinadeError::RequiredDelegatedStake).with_account_name("stake_account")
+    })?;
+    require_eq!(
+        delegation.deactivation_epoch,
+        std::u64::MAX,
+        MarinadeError::RequiredActiveStake
+    );
+
+    let meta = stake_account.meta().unwrap();
+    let staker = StakeSystem::stake_deposit_authority(
+        state_address,
+        state.stake_system.stake_deposit_bump_seed,
+    );
+    require_keys_eq!(
+        meta.authorized.staker,
+        staker,
+        MarinadeError::InvalidStakeAccountAuthority
+    );
+    let withdrawer = StakeSystem::stake_withdraw_authority(
+        state_address,
+        state.stake_system.stake_withdraw_bump_seed,
+    );
+    require_keys_eq!(
+        meta.authorized.withdrawer,
+        withdrawer,
+        MarinadeError::InvalidStakeAccountAuthority
+    );
+    if meta.lockup.is_in_force(clock, None) {
+        msg!("Can not absorb stake account with lockup");
+        return err!(MarinadeError::StakeAccountWithLockup)
+            .map_err(|e| e.with_account_name("stake_account"));
+    }
+    Ok(delegation)
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::*;
+    use anchor_lang::solana_program::{
+        native_token::LAMPORTS_PER_SOL,
+        stake::state::{Authorized, Delegation, Lockup, Meta, Stake, StakeState},
+    };
+
+    use super::check_stake_to_absorb;
+    use crate::{
+        error::MarinadeError,
+        state::{stake_system::StakeSystem, validator_system::ValidatorSystem},
+        State,
+    };
+
+    #[test]
+    fn test_check_stake_to_absorb() {
+        let state_address = Pubkey::new_unique();
+        let mut stake_list_data = [0; 8];
+        let mut validator_list_data = [0; 8];
+        let mut state = State::for_tests(
+            StakeSystem::new(
+                &state_address,
+                Pubkey::new_unique(),
+                &mut stake_list_data,
+                StakeSystem::MIN_UPDATE_WINDOW,
+                LAMPORTS_PER_SOL,
+                0,
+                0,
+            )
+            .unwrap(),
+            ValidatorSystem::new(
+                Pubkey::new_unique(),
+                &mut validator_list_data,
+                Pubkey::new_unique(),
+                0,
+            )
+            .unwrap(),
+        );
+        let (staker, deposit_bump) = StakeSystem::find_stake_deposit_authority(&state_address);
+        let (withdrawer, withdraw_bump) =
+            StakeSystem::find_stake_withdraw_authority(&state_address);
+        state.stake_system.stake_deposit_bump_seed = deposit_bump;
+        state.stake_system.stake_withdraw_bump_seed = withdraw_bump;
+        let clock = Clock {
+            epoch: 10,
+            ..Clock::default()
+        };
+        let meta = Meta {
+            authorized: Authorized { staker, withdrawer },
+            ..Meta::default()
+        };
+        let delegation = Delegation {
+            voter_pubkey: Pubkey::new_unique(),
+            stake: 10 * LAMPORTS_PER_SOL,
+            activation_epoch: 5,
+            ..Delegation::default()
+        };
+        let stake = |meta: Meta, delegation: Delegation| {
+            StakeState::Stake(
+                meta,
+                Stake {
+                    delegation,
+                    credits_observed: 0,
+                },
+            )
+        };
+        assert_eq!(
+            check_stake_to_absorb(&state, &state_address, &stake(meta, delegation), &clock),
+            Ok(delegation)
+        );
+
+        assert_eq!(
+            check_stake_to_absorb(
+                &state,
+                &state_address,
+                &StakeState::Initialized(meta),
+                &clock
+            )
+            .err(),
+            Some(error!(MarinadeError::RequiredDelegatedStake))
+        );
+        let deactivating = Delegation {
+            deactivation_epoch: 10,
+            ..delegation
+        };
+        assert_eq!(
+            check_stake_to_absorb(&state, &state_address, &stake(meta, deactivating), &clock).err(),
+            Some(error!(MarinadeError::RequiredActiveStake))
+        );
+        let foreign_staker = Meta {
+            authorized: Authorized {
+                staker: Pubkey::new_unique(),
+                withdrawer,
+            },
+            ..meta
+        };
+        assert_eq!(
+            check_stake_to_absorb(
+                &state,
+                &state_address,
+                &stake(foreign_staker, delegation),
+                &clock
+            )
+            .err(),
+            Some(error!(MarinadeError::InvalidStakeAccountAuthority))
+        );
+        let foreign_withdrawer = Meta {
+            authorized: Authorized {
+                staker,
+                withdrawer: Pubkey::new_unique(),
+            },
+            ..meta
+        };
+        assert_eq!(
+            check_stake_to_absorb(
+                &state,
+                &state_address,
+                &stake(foreign_withdrawer, delegation),
+                &clock
+            )
+            .err(),
+            Some(error!(MarinadeError::InvalidStakeAccountAuthority))
+        );
+        let locked = Meta {
+            lockup: Lockup {
+                epoch: 11,
+                ..Lockup::default()
+            },
+            ..meta
+        };
+        assert_eq!(
+            check_stake_to_absorb(&state, &state_address, &stake(locked, delegation), &clock).err(),
+            Some(error!(MarinadeError::StakeAccountWithLockup))
+        );
+    }
+}
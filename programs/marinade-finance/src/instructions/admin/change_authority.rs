@@ -23,6 +23,7 @@ pub struct ChangeAuthorityData {
     pub operational_sol_account: Option<Pubkey>,
     pub treasury_msol_account: Option<Pubkey>,
     pub pause_authority: Option<Pubkey>,
+    pub score_oracle: Option<Pubkey>,
 }
 
 impl<'info> ChangeAuthority<'info> {
@@ -81,13 +82,25 @@ impl<'info> ChangeAuthority<'info> {
             None
         };
 
+        let score_oracle_change = if let Some(score_oracle) = data.score_oracle {
+            let old = self.state.score_oracle;
+            self.state.score_oracle = score_oracle;
+            Some(PubkeyValueChange {
+                old,
+                new: score_oracle,
+            })
+        } else {
+            None
+        };
+
         emit!(ChangeAuthorityEvent {
             state: self.state.key(),
             admin_change,
             validator_manager_change,
             operational_sol_account_change,
             treasury_msol_account_change,
-            pause_authority_change
+            pause_authority_change,
+            score_oracle_change,
         });
 
         Ok(())
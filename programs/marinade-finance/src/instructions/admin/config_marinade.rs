@@ -1,10 +1,10 @@
 use crate::events::{
     admin::ConfigMarinadeEvent, BoolValueChange, FeeCentsValueChange, FeeValueChange,
-    U64ValueChange,
+    I64ValueChange, ReserveStakeSourceValueChange, U32ValueChange, U64ValueChange,
 };
 use crate::{
     require_lte,
-    state::{stake_system::StakeSystem, Fee, FeeCents},
+    state::{stake_system::StakeSystem, Fee, FeeCents, ReserveStakeSource},
     MarinadeError, State,
 };
 use anchor_lang::prelude::*;
@@ -22,6 +22,29 @@ pub struct ConfigMarinadeParams {
     pub delayed_unstake_fee: Option<FeeCents>,
     pub withdraw_stake_account_fee: Option<FeeCents>,
     pub max_stake_moved_per_epoch: Option<Fee>,
+    pub sort_stakes_by_validator: Option<bool>,
+    pub check_vote_rent_exempt: Option<bool>,
+    pub max_total_active_balance: Option<u64>,
+    pub reserve_stake_source: Option<ReserveStakeSource>,
+    pub stake_window_start_allowance: Option<Fee>,
+    pub stake_over_target: Option<bool>,
+    pub reserve_retention_lamports: Option<u64>,
+    pub max_extra_stake_delta_runs: Option<u32>,
+    pub max_stake_accounts_per_validator: Option<u32>,
+    pub parse_stake_history: Option<bool>,
+    pub stake_cooldown_epochs: Option<u64>,
+    pub invariant_checks: Option<bool>,
+    pub check_vote_activity: Option<bool>,
+    pub max_idle_epochs: Option<u64>,
+    pub max_stake_operations_per_epoch: Option<u32>,
+    pub stake_lockup_epoch: Option<u64>,
+    pub stake_lockup_unix_timestamp: Option<i64>,
+    pub max_stake_per_account: Option<u64>,
+    pub stake_rounding_lamports: Option<u64>,
+    pub max_stake_per_call: Option<u64>,
+    pub min_stake_delta_to_act: Option<u64>,
+    pub new_validator_ramp: Option<Fee>,
+    pub selection_seed: Option<u64>,
 }
 
 #[derive(Accounts)]
@@ -50,6 +73,29 @@ impl<'info> ConfigMarinade<'info> {
             delayed_unstake_fee,
             withdraw_stake_account_fee,
             max_stake_moved_per_epoch,
+            sort_stakes_by_validator,
+            check_vote_rent_exempt,
+            max_total_active_balance,
+            reserve_stake_source,
+            stake_window_start_allowance,
+            stake_over_target,
+            reserve_retention_lamports,
+            max_extra_stake_delta_runs,
+            max_stake_accounts_per_validator,
+            parse_stake_history,
+            stake_cooldown_epochs,
+            invariant_checks,
+            check_vote_activity,
+            max_idle_epochs,
+            max_stake_operations_per_epoch,
+            stake_lockup_epoch,
+            stake_lockup_unix_timestamp,
+            max_stake_per_account,
+            stake_rounding_lamports,
+            max_stake_per_call,
+            min_stake_delta_to_act,
+            new_validator_ramp,
+            selection_seed,
         }: ConfigMarinadeParams,
     ) -> Result<()> {
         let rewards_fee_change = if let Some(rewards_fee) = rewards_fee {
@@ -214,6 +260,276 @@ impl<'info> ConfigMarinade<'info> {
                 None
             };
 
+        let sort_stakes_by_validator_change =
+            if let Some(sort_stakes_by_validator) = sort_stakes_by_validator {
+                // sorted insertion starts when the existing records are sorted by sort_stake_list
+                let old = self.state.sort_stakes_by_validator;
+                self.state.sort_stakes_by_validator = sort_stakes_by_validator;
+                Some(BoolValueChange {
+                    old,
+                    new: sort_stakes_by_validator,
+                })
+            } else {
+                None
+            };
+
+        let check_vote_rent_exempt_change =
+            if let Some(check_vote_rent_exempt) = check_vote_rent_exempt {
+                let old = self.state.check_vote_rent_exempt;
+                self.state.check_vote_rent_exempt = check_vote_rent_exempt;
+                Some(BoolValueChange {
+                    old,
+                    new: check_vote_rent_exempt,
+                })
+            } else {
+                None
+            };
+
+        let max_total_active_balance_change =
+            if let Some(max_total_active_balance) = max_total_active_balance {
+                // u64::MAX for no cap
+                let old = self.state.max_total_active_balance;
+                self.state.max_total_active_balance = max_total_active_balance;
+                Some(U64ValueChange {
+                    old,
+                    new: max_total_active_balance,
+                })
+            } else {
+                None
+            };
+
+        let reserve_stake_source_change = if let Some(reserve_stake_source) = reserve_stake_source {
+            let old = self.state.reserve_stake_source;
+            self.state.reserve_stake_source = reserve_stake_source;
+            Some(ReserveStakeSourceValueChange {
+                old,
+                new: reserve_stake_source,
+            })
+        } else {
+            None
+        };
+
+        let stake_window_start_allowance_change =
+            if let Some(stake_window_start_allowance) = stake_window_start_allowance {
+                stake_window_start_allowance.check()?;
+                let old = self.state.stake_window_start_allowance;
+                self.state.stake_window_start_allowance = stake_window_start_allowance;
+                Some(FeeValueChange {
+                    old,
+                    new: stake_window_start_allowance,
+                })
+            } else {
+                None
+            };
+
+        let stake_over_target_change = if let Some(stake_over_target) = stake_over_target {
+            let old = self.state.stake_over_target;
+            self.state.stake_over_target = stake_over_target;
+            Some(BoolValueChange {
+                old,
+                new: stake_over_target,
+            })
+        } else {
+            None
+        };
+
+        let reserve_retention_lamports_change =
+            if let Some(reserve_retention_lamports) = reserve_retention_lamports {
+                let old = self.state.reserve_retention_lamports;
+                self.state.reserve_retention_lamports = reserve_retention_lamports;
+                Some(U64ValueChange {
+                    old,
+                    new: reserve_retention_lamports,
+                })
+            } else {
+                None
+            };
+
+        let max_extra_stake_delta_runs_change =
+            if let Some(max_extra_stake_delta_runs) = max_extra_stake_delta_runs {
+                let old = self.state.max_extra_stake_delta_runs;
+                self.state.max_extra_stake_delta_runs = max_extra_stake_delta_runs;
+                Some(U32ValueChange {
+                    old,
+                    new: max_extra_stake_delta_runs,
+                })
+            } else {
+                None
+            };
+
+        let max_stake_accounts_per_validator_change =
+            if let Some(max_stake_accounts_per_validator) = max_stake_accounts_per_validator {
+                let old = self.state.max_stake_accounts_per_validator;
+                self.state.max_stake_accounts_per_validator = max_stake_accounts_per_validator;
+                Some(U32ValueChange {
+                    old,
+                    new: max_stake_accounts_per_validator,
+                })
+            } else {
+                None
+            };
+
+        let parse_stake_history_change = if let Some(parse_stake_history) = parse_stake_history {
+            let old = self.state.parse_stake_history;
+            self.state.parse_stake_history = parse_stake_history;
+            Some(BoolValueChange {
+                old,
+                new: parse_stake_history,
+            })
+        } else {
+            None
+        };
+
+        let stake_cooldown_epochs_change =
+            if let Some(stake_cooldown_epochs) = stake_cooldown_epochs {
+                let old = self.state.stake_cooldown_epochs;
+                self.state.stake_cooldown_epochs = stake_cooldown_epochs;
+                Some(U64ValueChange {
+                    old,
+                    new: stake_cooldown_epochs,
+                })
+            } else {
+                None
+            };
+
+        let invariant_checks_change = if let Some(invariant_checks) = invariant_checks {
+            let old = self.state.invariant_checks;
+            self.state.invariant_checks = invariant_checks;
+            Some(BoolValueChange {
+                old,
+                new: invariant_checks,
+            })
+        } else {
+            None
+        };
+
+        let check_vote_activity_change = if let Some(check_vote_activity) = check_vote_activity {
+            let old = self.state.check_vote_activity;
+            self.state.check_vote_activity = check_vote_activity;
+            Some(BoolValueChange {
+                old,
+                new: check_vote_activity,
+            })
+        } else {
+            None
+        };
+
+        let max_idle_epochs_change = if let Some(max_idle_epochs) = max_idle_epochs {
+            let old = self.state.max_idle_epochs;
+            self.state.max_idle_epochs = max_idle_epochs;
+            Some(U64ValueChange {
+                old,
+                new: max_idle_epochs,
+            })
+        } else {
+            None
+        };
+
+        let max_stake_operations_per_epoch_change =
+            if let Some(max_stake_operations_per_epoch) = max_stake_operations_per_epoch {
+                let old = self.state.max_stake_operations_per_epoch;
+                self.state.max_stake_operations_per_epoch = max_stake_operations_per_epoch;
+                Some(U32ValueChange {
+                    old,
+                    new: max_stake_operations_per_epoch,
+                })
+            } else {
+                None
+            };
+
+        let stake_lockup_epoch_change = if let Some(stake_lockup_epoch) = stake_lockup_epoch {
+            let old = self.state.stake_lockup_epoch;
+            self.state.stake_lockup_epoch = stake_lockup_epoch;
+            Some(U64ValueChange {
+                old,
+                new: stake_lockup_epoch,
+            })
+        } else {
+            None
+        };
+
+        let stake_lockup_unix_timestamp_change =
+            if let Some(stake_lockup_unix_timestamp) = stake_lockup_unix_timestamp {
+                let old = self.state.stake_lockup_unix_timestamp;
+                self.state.stake_lockup_unix_timestamp = stake_lockup_unix_timestamp;
+                Some(I64ValueChange {
+                    old,
+                    new: stake_lockup_unix_timestamp,
+                })
+            } else {
+                None
+            };
+
+        let max_stake_per_account_change =
+            if let Some(max_stake_per_account) = max_stake_per_account {
+                let old = self.state.max_stake_per_account;
+                self.state.max_stake_per_account = max_stake_per_account;
+                Some(U64ValueChange {
+                    old,
+                    new: max_stake_per_account,
+                })
+            } else {
+                None
+            };
+
+        let stake_rounding_lamports_change =
+            if let Some(stake_rounding_lamports) = stake_rounding_lamports {
+                let old = self.state.stake_rounding_lamports;
+                self.state.stake_rounding_lamports = stake_rounding_lamports;
+                Some(U64ValueChange {
+                    old,
+                    new: stake_rounding_lamports,
+                })
+            } else {
+                None
+            };
+
+        let max_stake_per_call_change = if let Some(max_stake_per_call) = max_stake_per_call {
+            let old = self.state.max_stake_per_call;
+            self.state.max_stake_per_call = max_stake_per_call;
+            Some(U64ValueChange {
+                old,
+                new: max_stake_per_call,
+            })
+        } else {
+            None
+        };
+
+        let min_stake_delta_to_act_change =
+            if let Some(min_stake_delta_to_act) = min_stake_delta_to_act {
+                let old = self.state.min_stake_delta_to_act;
+                self.state.min_stake_delta_to_act = min_stake_delta_to_act;
+                Some(U64ValueChange {
+                    old,
+                    new: min_stake_delta_to_act,
+                })
+            } else {
+                None
+            };
+
+        let new_validator_ramp_change = if let Some(new_validator_ramp) = new_validator_ramp {
+            new_validator_ramp.check()?;
+            let old = self.state.new_validator_ramp;
+            self.state.new_validator_ramp = new_validator_ramp;
+            Some(FeeValueChange {
+                old,
+                new: new_validator_ramp,
+            })
+        } else {
+            None
+        };
+
+        let selection_seed_change = if let Some(selection_seed) = selection_seed {
+            let old = self.state.selection_seed;
+            self.state.selection_seed = selection_seed;
+            Some(U64ValueChange {
+                old,
+                new: selection_seed,
+            })
+        } else {
+            None
+        };
+
         emit!(ConfigMarinadeEvent {
             state: self.state.key(),
             rewards_fee_change,
@@ -227,6 +543,29 @@ impl<'info> ConfigMarinade<'info> {
             delayed_unstake_fee_change,
             withdraw_stake_account_fee_change,
             max_stake_moved_per_epoch_change,
+            sort_stakes_by_validator_change,
+            check_vote_rent_exempt_change,
+            max_total_active_balance_change,
+            reserve_stake_source_change,
+            stake_window_start_allowance_change,
+            stake_over_target_change,
+            reserve_retention_lamports_change,
+            max_extra_stake_delta_runs_change,
+            max_stake_accounts_per_validator_change,
+            parse_stake_history_change,
+            stake_cooldown_epochs_change,
+            invariant_checks_change,
+            check_vote_activity_change,
+            max_idle_epochs_change,
+            max_stake_operations_per_epoch_change,
+            stake_lockup_epoch_change,
+            stake_lockup_unix_timestamp_change,
+            max_stake_per_account_change,
+            stake_rounding_lamports_change,
+            max_stake_per_call_change,
+            min_stake_delta_to_act_change,
+            new_validator_ramp_change,
+            selection_seed_change,
         });
 
         Ok(())
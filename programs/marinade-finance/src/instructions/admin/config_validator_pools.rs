@@ -0,0 +1,51 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError,
+    events::admin::ConfigValidatorPoolsEvent,
+    state::{validator_system::MAX_VALIDATOR_POOLS, Fee},
+    State,
+};
+
+#[derive(Accounts)]
+pub struct ConfigValidatorPools<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Account<'info, State>,
+    pub admin_authority: Signer<'info>,
+}
+
+impl<'info> ConfigValidatorPools<'info> {
+    /// Sets the share of the total stake target of every validator pool.
+    /// All zeroes disables pools: all the validators share the target by score.
+    /// Enabling needs pool_scores_valid (recompute_pool_scores after the state migration)
+    pub fn process(&mut self, allocations: [Fee; MAX_VALIDATOR_POOLS]) -> Result<()> {
+        let mut total_basis_points: u32 = 0;
+        for allocation in allocations.iter() {
+            allocation.check()?;
+            total_basis_points += allocation.basis_points;
+        }
+        if total_basis_points != 0 && total_basis_points != Fee::MAX_BASIS_POINTS {
+            return Err(error!(MarinadeError::InvalidValidatorPoolAllocations)
+                .with_values((total_basis_points, Fee::MAX_BASIS_POINTS)));
+        }
+        if total_basis_points != 0 {
+            require!(
+                self.state.pool_scores_valid,
+                MarinadeError::PoolScoresNotRecomputed
+            );
+        }
+
+        let old_allocations = self.state.pool_allocations;
+        self.state.pool_allocations = allocations;
+
+        emit!(ConfigValidatorPoolsEvent {
+            state: self.state.key(),
+            old_allocations,
+            new_allocations: allocations,
+        });
+        Ok(())
+    }
+}
@@ -2,11 +2,14 @@ use anchor_lang::prelude::*;
 
 use crate::{
     error::MarinadeError,
-    events::admin::{EmergencyPauseEvent, ResumeEvent},
+    events::{
+        admin::{EmergencyPauseEvent, ResumeEvent, SetPauseFlagsEvent},
+        U8ValueChange,
+    },
     State,
 };
 
-// this account struct is used for pause() and resume() instructions (see lib.rs)
+// this account struct is used for pause(), resume() and set_pause() instructions (see lib.rs)
 #[derive(Accounts)]
 pub struct EmergencyPause<'info> {
     #[account(
@@ -36,4 +39,23 @@ impl<'info> EmergencyPause<'info> {
         });
         Ok(())
     }
+
+    /// Pauses the parts of the program selected by State::PAUSE_* bits, 0 to unpause all of them
+    pub fn set_pause(&mut self, pause_flags: u8) -> Result<()> {
+        require_eq!(
+            pause_flags & !State::PAUSE_FLAGS_MASK,
+            0,
+            MarinadeError::InvalidPauseFlags
+        );
+        let old = self.state.pause_flags;
+        self.state.pause_flags = pause_flags;
+        emit!(SetPauseFlagsEvent {
+            state: self.state.key(),
+            pause_flags_change: U8ValueChange {
+                old,
+                new: pause_flags,
+            },
+        });
+        Ok(())
+    }
 }
@@ -7,8 +7,11 @@ use crate::{
     events::admin::InitializeEvent,
     require_lte,
     state::{
-        fee::FeeCents, liq_pool::LiqPool, stake_system::StakeSystem,
-        validator_system::ValidatorSystem, Fee,
+        fee::FeeCents,
+        liq_pool::LiqPool,
+        stake_system::{StakeReserveLastStatus, StakeReserveReport, StakeSystem},
+        validator_system::{ValidatorSystem, MAX_PREFERRED_VALIDATORS, MAX_VALIDATOR_POOLS},
+        Fee, ReserveStakeSource, MAX_CRANK_AUTHORITIES,
     },
     State, ID,
 };
@@ -188,6 +191,51 @@ impl<'info> Initialize<'info> {
             last_stake_move_epoch: 0,
             stake_moved: 0,
             max_stake_moved_per_epoch: Fee::from_basis_points(10000), // 100% of total_lamports_under_control
+            layout_version: State::LAYOUT_VERSION,
+            max_total_active_balance: std::u64::MAX, // Unlimited
+            reserve_rewards_balance: 0,
+            reserve_stake_source: ReserveStakeSource::Combined,
+            reserve_retention_lamports: 0,
+            reserve_low_water_lamports: std::u64::MAX, // not measured yet
+            pause_flags: 0,
+            invariant_checks: false,
+            reconcile_next_validator_index: 0,
+            reconcile_active_balance_sum: 0,
+            preferred_validators: [0; MAX_PREFERRED_VALIDATORS],
+            preferred_validator_count: 0,
+            crank_authorities: [Pubkey::default(); MAX_CRANK_AUTHORITIES],
+            crank_authority_count: 0,
+            score_oracle: Pubkey::default(),
+            sort_stakes_by_validator: false,
+            sorted_stake_count: 0,
+            stake_reserve_report: StakeReserveReport::default(),
+            stake_reserve_last_status: StakeReserveLastStatus::default(),
+            check_vote_rent_exempt: false,
+            stake_window_start_allowance: Fee::from_basis_points(Fee::MAX_BASIS_POINTS),
+            stake_over_target: false,
+            max_extra_stake_delta_runs: std::u32::MAX,
+            max_stake_accounts_per_validator: 0,
+            parse_stake_history: false,
+            stake_cooldown_epochs: 0,
+            check_vote_activity: false,
+            max_idle_epochs: 0,
+            max_stake_operations_per_epoch: 0,
+            stake_lockup_epoch: 0,
+            stake_lockup_unix_timestamp: 0,
+            max_stake_per_account: 0,
+            stake_rounding_lamports: 0,
+            max_stake_per_call: 0,
+            min_stake_delta_to_act: 0,
+            new_validator_ramp: Fee::from_basis_points(Fee::MAX_BASIS_POINTS),
+            pool_allocations: [Fee::from_basis_points(0); MAX_VALIDATOR_POOLS],
+            pool_scores: [0; MAX_VALIDATOR_POOLS],
+            stake_operations_this_epoch: 0,
+            stake_operations_epoch: 0,
+            // no validators yet
+            pool_scores_valid: true,
+            recompute_pool_scores_next_index: 0,
+            recompute_pool_scores_sums: [0; MAX_VALIDATOR_POOLS],
+            selection_seed: 0,
         });
 
         emit!(InitializeEvent {
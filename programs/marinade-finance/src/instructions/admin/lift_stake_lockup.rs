@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    program::invoke_signed,
+    stake::{self, instruction::LockupArgs, state::Lockup},
+};
+use anchor_spl::stake::{Stake, StakeAccount};
+
+use crate::{
+    error::MarinadeError, events::admin::LiftStakeLockupEvent, state::stake_system::StakeSystem,
+    State,
+};
+
+#[derive(Accounts)]
+pub struct LiftStakeLockup<'info> {
+    #[account(
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Box<Account<'info, State>>,
+    pub admin_authority: Signer<'info>,
+    #[account(mut)]
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+    /// CHECK: PDA
+    #[account(
+        seeds = [
+            &state.key().to_bytes(),
+            StakeSystem::STAKE_WITHDRAW_SEED
+        ],
+        bump = state.stake_system.stake_withdraw_bump_seed
+    )]
+    pub stake_withdraw_authority: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub stake_program: Program<'info, Stake>,
+}
+
+impl<'info> LiftStakeLockup<'info> {
+    /// Clears the lockup stake_reserve set (stake_lockup_epoch, stake_lockup_unix_timestamp)
+    /// on a stake account, in the stake list or already withdrawn by a user:
+    /// the stake withdraw authority PDA is the custodian
+    pub fn process(&mut self) -> Result<()> {
+        let lockup = self.stake_account.lockup().ok_or_else(|| {
+            error!(MarinadeError::RequiredDelegatedStake).with_account_name("stake_account")
+        })?;
+        require_keys_eq!(
+            lockup.custodian,
+            self.stake_withdraw_authority.key(),
+            MarinadeError::InvalidStakeLockupCustodian
+        );
+        require!(
+            lockup.is_in_force(&self.clock, None),
+            MarinadeError::StakeLockupNotInForce
+        );
+
+        set_stake_lockup(
+            &self.state,
+            self.stake_account.to_account_info(),
+            self.stake_withdraw_authority.to_account_info(),
+            self.stake_program.to_account_info(),
+            &Lockup::default(),
+        )?;
+
+        emit!(LiftStakeLockupEvent {
+            state: self.state.key(),
+            epoch: self.clock.epoch,
+            stake_account: self.stake_account.key(),
+            lockup_epoch: lockup.epoch,
+            lockup_unix_timestamp: lockup.unix_timestamp,
+        });
+        Ok(())
+    }
+}
+
+/// stake set_lockup signed by the stake withdraw authority PDA: the custodian of the stake_reserve
+/// lockup while it is in force, the withdrawer of the stake accounts of the list otherwise
+pub(crate) fn set_stake_lockup<'info>(
+    state: &Account<'info, State>,
+    stake_account: AccountInfo<'info>,
+    stake_withdraw_authority: AccountInfo<'info>,
+    stake_program: AccountInfo<'info>,
+    lockup: &Lockup,
+) -> Result<()> {
+    invoke_signed(
+        &stake::instruction::set_lockup(
+            stake_account.key,
+            &LockupArgs {
+                unix_timestamp: Some(lockup.unix_timestamp),
+                epoch: Some(lockup.epoch),
+                custodian: Some(lockup.custodian),
+            },
+            stake_withdraw_authority.key,
+        ),
+        &[stake_program, stake_account, stake_withdraw_authority],
+        &[&[
+            &state.key().to_bytes(),
+            StakeSystem::STAKE_WITHDRAW_SEED,
+            &[state.stake_system.stake_withdraw_bump_seed],
+        ]],
+    )?;
+    Ok(())
+}
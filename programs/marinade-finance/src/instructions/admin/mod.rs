@@ -1,17 +1,47 @@
+pub mod absorb_stake_account;
 pub mod change_authority;
 pub mod config_lp;
 pub mod config_marinade;
+pub mod config_validator_pools;
 pub mod config_validator_system;
 pub mod emergency_pause;
 pub mod initialize;
+pub mod lift_stake_lockup;
 pub mod realloc_stake_list;
+pub mod realloc_state;
 pub mod realloc_validator_list;
+pub mod recompute_pool_scores;
+pub mod reconcile_stake;
+pub mod reconcile_total_active_balance;
+pub mod refill_extra_stake_delta_runs;
+pub mod reset_reserve_low_water;
+pub mod set_crank_authorities;
+pub mod set_preferred_validators;
+pub mod set_stake_delta_window;
+pub mod set_validator_drain_only;
+pub mod set_validator_max_stake;
+pub mod sort_stake_list;
 
+pub use absorb_stake_account::*;
 pub use change_authority::*;
 pub use config_lp::*;
 pub use config_marinade::*;
+pub use config_validator_pools::*;
 pub use config_validator_system::*;
 pub use emergency_pause::*;
 pub use initialize::*;
+pub use lift_stake_lockup::*;
 pub use realloc_stake_list::*;
+pub use realloc_state::*;
 pub use realloc_validator_list::*;
+pub use recompute_pool_scores::*;
+pub use reconcile_stake::*;
+pub use reconcile_total_active_balance::*;
+pub use refill_extra_stake_delta_runs::*;
+pub use reset_reserve_low_water::*;
+pub use set_crank_authorities::*;
+pub use set_preferred_validators::*;
+pub use set_stake_delta_window::*;
+pub use set_validator_drain_only::*;
+pub use set_validator_max_stake::*;
+pub use sort_stake_list::*;
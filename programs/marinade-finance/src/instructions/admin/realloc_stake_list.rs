@@ -1,7 +1,9 @@
 use anchor_lang::{prelude::*, system_program, Discriminator};
 
 use crate::{
-    error::MarinadeError, events::admin::ReallocStakeListEvent, state::stake_system::StakeList,
+    error::MarinadeError,
+    events::{admin::ReallocStakeListEvent, U32ValueChange},
+    state::{list::List, stake_system::StakeList},
     State,
 };
 
@@ -17,8 +19,11 @@ pub struct ReallocStakeList<'info> {
     #[account(
         mut,
         address = state.stake_system.stake_list.account,
-        realloc = StakeList::DISCRIMINATOR.len()
-            + (state.stake_system.stake_record_size() * capacity) as usize,
+        // grown by at most 10 KiB, repeated until the list reaches the capacity
+        realloc = List::realloc_len(
+            stake_list.to_account_info().data_len(),
+            StakeList::DISCRIMINATOR.len() + (state.stake_system.upgraded_stake_record_size() * capacity) as usize,
+        ),
         realloc::payer = rent_funds,
         realloc::zero = false,
     )]
@@ -34,16 +39,43 @@ pub struct ReallocStakeList<'info> {
 }
 
 impl<'info> ReallocStakeList<'info> {
+    /// Grows the stake list to capacity records of the current layout, by 10 KiB per call:
+    /// the same call is repeated until the account fits the capacity,
+    /// the last one migrates the records (the list is not usable until then)
     pub fn process(&mut self, capacity: u32) -> Result<()> {
         require_gte!(
             capacity,
             self.state.stake_system.stake_count(),
             MarinadeError::ShrinkingListWithDeletingContents
         );
+        let len = self.stake_list.to_account_info().data_len();
+        let target_len = StakeList::DISCRIMINATOR.len()
+            + (self.state.stake_system.upgraded_stake_record_size() * capacity) as usize;
+        if len < target_len {
+            // the records are migrated once the account fits them
+            msg!(
+                "stake_list grown to {} of {} bytes, repeat to continue",
+                len,
+                target_len
+            );
+            return Ok(());
+        }
+        // migrate records to the current layout if the list was created for an older one
+        let record_size_change = self
+            .state
+            .stake_system
+            .upgrade_stake_record_size(
+                &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
+            )?
+            .map(|old| U32ValueChange {
+                old,
+                new: self.state.stake_system.stake_record_size(),
+            });
         emit!(ReallocStakeListEvent {
             state: self.state.key(),
             count: self.state.stake_system.stake_count(),
-            new_capacity: capacity
+            new_capacity: capacity,
+            record_size_change,
         });
         Ok(())
     }
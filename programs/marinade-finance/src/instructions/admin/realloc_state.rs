@@ -0,0 +1,84 @@
+use anchor_lang::{prelude::*, system_program, Discriminator};
+
+use crate::{
+    error::MarinadeError,
+    events::{admin::ReallocStateEvent, U8ValueChange},
+    State, ID,
+};
+
+#[derive(Accounts)]
+pub struct ReallocState<'info> {
+    /// CHECK: the older layouts don't deserialize as State. Discriminator, layout and admin_authority are checked in process
+    #[account(mut, owner = ID)]
+    pub state: UncheckedAccount<'info>,
+    pub admin_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        owner = system_program::ID,
+    )]
+    pub rent_funds: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ReallocState<'info> {
+    /// admin_authority follows the discriminator and msol_mint in every layout
+    const ADMIN_AUTHORITY_OFFSET: usize = 8 + 32;
+
+    /// Migrates the State account of an older layout to State::LAYOUT_VERSION:
+    /// reallocs it to State::serialized_len() and sets the defaults of the appended fields
+    pub fn process(&mut self) -> Result<()> {
+        let state_info = self.state.to_account_info();
+        let old_len = state_info.data_len();
+        {
+            let data = state_info.data.borrow();
+            let layout_version = State::layout_version(&data)?;
+            require!(
+                data[..8] == State::DISCRIMINATOR,
+                MarinadeError::InvalidStateLayout
+            );
+            require_gt!(
+                State::LAYOUT_VERSION,
+                layout_version,
+                MarinadeError::InvalidStateLayout
+            );
+            let admin_authority = Pubkey::try_from_slice(
+                &data[Self::ADMIN_AUTHORITY_OFFSET..Self::ADMIN_AUTHORITY_OFFSET + 32],
+            )?;
+            require_keys_eq!(
+                admin_authority,
+                self.admin_authority.key(),
+                MarinadeError::InvalidAdminAuthority
+            );
+        }
+
+        let new_len = old_len.max(State::serialized_len());
+        let rent_exempt = Rent::get()?.minimum_balance(new_len);
+        if state_info.lamports() < rent_exempt {
+            system_program::transfer(
+                CpiContext::new(
+                    self.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: self.rent_funds.to_account_info(),
+                        to: state_info.clone(),
+                    },
+                ),
+                rent_exempt - state_info.lamports(),
+            )?;
+        }
+        state_info.realloc(new_len, true)?;
+        let old_version = State::migrate_layout(&mut state_info.data.borrow_mut())?;
+
+        emit!(ReallocStateEvent {
+            state: self.state.key(),
+            layout_version_change: U8ValueChange {
+                old: old_version,
+                new: State::LAYOUT_VERSION,
+            },
+            old_len: old_len as u32,
+            new_len: new_len as u32,
+        });
+        Ok(())
+    }
+}
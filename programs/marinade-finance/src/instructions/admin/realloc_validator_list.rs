@@ -1,8 +1,10 @@
 use anchor_lang::{prelude::*, system_program, Discriminator};
 
 use crate::{
-    error::MarinadeError, events::admin::ReallocValidatorListEvent,
-    state::validator_system::ValidatorList, State,
+    error::MarinadeError,
+    events::{admin::ReallocValidatorListEvent, U32ValueChange},
+    state::{list::List, validator_system::ValidatorList},
+    State,
 };
 
 #[derive(Accounts)]
@@ -17,8 +19,11 @@ pub struct ReallocValidatorList<'info> {
     #[account(
         mut,
         address = state.validator_system.validator_list.account,
-        realloc = ValidatorList::DISCRIMINATOR.len()
-            + (state.validator_system.validator_record_size() * capacity) as usize,
+        // grown by at most 10 KiB, repeated until the list reaches the capacity
+        realloc = List::realloc_len(
+            validator_list.to_account_info().data_len(),
+            ValidatorList::DISCRIMINATOR.len() + (state.validator_system.upgraded_validator_record_size() * capacity) as usize,
+        ),
         realloc::payer = rent_funds,
         realloc::zero = false,
     )]
@@ -34,16 +39,48 @@ pub struct ReallocValidatorList<'info> {
 }
 
 impl<'info> ReallocValidatorList<'info> {
+    /// Grows the validator list to capacity records of the current layout, by 10 KiB per call:
+    /// the same call is repeated until the account fits the capacity,
+    /// the last one migrates the records (the list is not usable until then)
     pub fn process(&mut self, capacity: u32) -> Result<()> {
         require_gte!(
             capacity,
             self.state.validator_system.validator_count(),
             MarinadeError::ShrinkingListWithDeletingContents
         );
+        let len = self.validator_list.to_account_info().data_len();
+        let target_len = ValidatorList::DISCRIMINATOR.len()
+            + (self.state.validator_system.upgraded_validator_record_size() * capacity) as usize;
+        if len < target_len {
+            // the records are migrated once the account fits them
+            msg!(
+                "validator_list grown to {} of {} bytes, repeat to continue",
+                len,
+                target_len
+            );
+            return Ok(());
+        }
+        // migrate records to the current layout if the list was created for an older one
+        let record_size_change = self
+            .state
+            .validator_system
+            .upgrade_validator_record_size(
+                &mut self
+                    .validator_list
+                    .to_account_info()
+                    .data
+                    .as_ref()
+                    .borrow_mut(),
+            )?
+            .map(|old| U32ValueChange {
+                old,
+                new: self.state.validator_system.validator_record_size(),
+            });
         emit!(ReallocValidatorListEvent {
             state: self.state.key(),
             count: self.state.validator_system.validator_count(),
-            new_capacity: capacity
+            new_capacity: capacity,
+            record_size_change,
         });
         Ok(())
     }
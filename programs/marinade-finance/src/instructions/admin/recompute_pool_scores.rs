@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError, events::admin::RecomputePoolScoresEvent,
+    state::validator_system::ValidatorList, State,
+};
+
+#[derive(Accounts)]
+pub struct RecomputePoolScores<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Box<Account<'info, State>>,
+    pub admin_authority: Signer<'info>,
+    #[account(address = state.validator_system.validator_list.account)]
+    pub validator_list: Account<'info, ValidatorList>,
+}
+
+impl<'info> RecomputePoolScores<'info> {
+    /// Repair tool: recomputes pool_scores as the sums of the validator scores in every pool.
+    /// The validators are summed in pages of count starting at start_index (0 restarts the run),
+    /// the last page sets pool_scores and allows config_validator_pools to enable the allocations.
+    /// Only while the program is paused so the scores and pools don't move between the pages
+    pub fn process(&mut self, start_index: u32, count: u32) -> Result<()> {
+        require!(self.state.paused, MarinadeError::NotPaused);
+        let old_pool_scores = self.state.pool_scores;
+        let done = self.state.recompute_pool_scores(
+            &self.validator_list.to_account_info().data.borrow(),
+            start_index,
+            count,
+        )?;
+        if !done {
+            msg!(
+                "Summed validators up to {} of {}",
+                self.state.recompute_pool_scores_next_index,
+                self.state.validator_system.validator_count()
+            );
+            return Ok(());
+        }
+
+        msg!(
+            "Pool scores {:?} recomputed to {:?}",
+            old_pool_scores,
+            self.state.pool_scores
+        );
+        emit!(RecomputePoolScoresEvent {
+            state: self.state.key(),
+            validator_count: self.state.validator_system.validator_count(),
+            old_pool_scores,
+            new_pool_scores: self.state.pool_scores,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+use anchor_lang::prelude::*;
+use anchor_spl::stake::StakeAccount;
+
+use crate::{
+    error::MarinadeError,
+    events::{admin::ReconcileStakeEvent, U64ValueChange},
+    state::{
+        stake_system::{StakeList, StakeRecord},
+        validator_system::{ValidatorList, ValidatorRecord},
+    },
+    State,
+};
+
+#[derive(Accounts)]
+pub struct ReconcileStake<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Box<Account<'info, State>>,
+    pub admin_authority: Signer<'info>,
+    #[account(
+        mut,
+        address = state.validator_system.validator_list.account,
+    )]
+    pub validator_list: Account<'info, ValidatorList>,
+    #[account(
+        mut,
+        address = state.stake_system.stake_list.account,
+    )]
+    pub stake_list: Account<'info, StakeList>,
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> ReconcileStake<'info> {
+    /// Repair tool: aligns the recorded delegation of an active stake account
+    /// (and the validator and total active balances) with the on-chain delegated lamports
+    /// when the stake lost lamports (slashed). A positive drift is rewards:
+    /// it is left to update_active which takes the protocol fee from it
+    pub fn process(&mut self, stake_index: u32, validator_index: u32) -> Result<()> {
+        require!(!self.state.paused, MarinadeError::ProgramIsPaused);
+
+        let mut stake = self.state.stake_system.get_checked(
+            &self.stake_list.to_account_info().data.as_ref().borrow(),
+            stake_index,
+            self.stake_account.to_account_info().key,
+        )?;
+
+        let delegation = self.stake_account.delegation().ok_or_else(|| {
+            error!(MarinadeError::RequiredDelegatedStake).with_account_name("stake_account")
+        })?;
+        // deactivating stakes are already removed from the active balance
+        require_eq!(
+            delegation.deactivation_epoch,
+            std::u64::MAX,
+            MarinadeError::RequiredActiveStake
+        );
+
+        let mut validator = self.state.validator_system.get_checked(
+            &self.validator_list.to_account_info().data.as_ref().borrow(),
+            validator_index,
+            &delegation.voter_pubkey,
+        )?;
+
+        let recorded = stake.last_update_delegated_lamports;
+        let actual = delegation.stake;
+        let validator_active_balance = validator.active_balance;
+        let total_active_balance = self.state.validator_system.total_active_balance;
+        msg!(
+            "Stake {} recorded delegation {} actual {}. Validator {} active balance {}, total active balance {}",
+            stake.stake_account,
+            recorded,
+            actual,
+            validator.validator_account,
+            validator_active_balance,
+            total_active_balance
+        );
+        if !reconcile_negative_drift(&mut self.state, &mut validator, &mut stake, actual) {
+            return Ok(());
+        }
+
+        self.state.validator_system.set(
+            &mut self
+                .validator_list
+                .to_account_info()
+                .data
+                .as_ref()
+                .borrow_mut(),
+            validator_index,
+            validator,
+        )?;
+        self.state.stake_system.set(
+            &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
+            stake_index,
+            stake,
+        )?;
+        // total_active_balance is a part of the mSOL price, lowered as by the update_active of a slashed stake
+        self.state.msol_price = self.state.msol_to_sol(State::PRICE_DENOMINATOR)?;
+        msg!(
+            "Validator active balance {}, total active balance {}",
+            validator.active_balance,
+            self.state.validator_system.total_active_balance
+        );
+
+        emit!(ReconcileStakeEvent {
+            state: self.state.key(),
+            epoch: self.clock.epoch,
+            stake_index,
+            stake_account: stake.stake_account,
+            validator_index,
+            validator_vote: validator.validator_account,
+            delegation_change: U64ValueChange {
+                old: recorded,
+                new: actual,
+            },
+            validator_active_balance_change: U64ValueChange {
+                old: validator_active_balance,
+                new: validator.active_balance,
+            },
+            total_active_balance_change: U64ValueChange {
+                old: total_active_balance,
+                new: self.state.validator_system.total_active_balance,
+            },
+        });
+
+        Ok(())
+    }
+}
+
+/// Moves the recorded delegation (and the validator and total active balances) down to actual.
+/// Returns false when there is no negative drift to reconcile
+pub(crate) fn reconcile_negative_drift(
+    state: &mut State,
+    validator: &mut ValidatorRecord,
+    stake: &mut StakeRecord,
+    actual: u64,
+) -> bool {
+    let drift = match stake.last_update_delegated_lamports.checked_sub(actual) {
+        None => {
+            msg!(
+                "Drift +{} (rewards not accounted yet), left to update_active",
+                actual - stake.last_update_delegated_lamports
+            );
+            return false;
+        }
+        Some(0) => {
+            msg!("No drift");
+            return false;
+        }
+        Some(drift) => drift,
+    };
+    msg!("Drift -{} (slashed)", drift);
+    // saturating as update_active: the balances can be lower after an earlier drift
+    validator.active_balance = validator.active_balance.saturating_sub(drift);
+    state.validator_system.total_active_balance = state
+        .validator_system
+        .total_active_balance
+        .saturating_sub(drift);
+    stake.last_update_delegated_lamports = actual;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::reconcile_negative_drift;
+    use crate::{
+        state::{
+            stake_system::{StakeRecord, StakeSystem},
+            validator_system::{ValidatorRecord, ValidatorSystem},
+        },
+        State,
+    };
+    use anchor_lang::prelude::*;
+    use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+
+    fn state_with_total_active_balance(total_active_balance: u64) -> State {
+        let mut stake_list_data = [0; 8];
+        let mut validator_list_data = [0; 8];
+        let mut validator_system = ValidatorSystem::new(
+            Pubkey::new_unique(),
+            &mut validator_list_data,
+            Pubkey::new_unique(),
+            0,
+        )
+        .unwrap();
+        validator_system.total_active_balance = total_active_balance;
+        State::for_tests(
+            StakeSystem::new(
+                &Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                &mut stake_list_data,
+                StakeSystem::MIN_UPDATE_WINDOW,
+                LAMPORTS_PER_SOL,
+                0,
+                0,
+            )
+            .unwrap(),
+            validator_system,
+        )
+    }
+
+    #[test]
+    fn test_reconcile_negative_drift() {
+        let mut state = state_with_total_active_balance(10 * LAMPORTS_PER_SOL);
+        let mut validator = ValidatorRecord {
+            active_balance: 4 * LAMPORTS_PER_SOL,
+            ..ValidatorRecord::default()
+        };
+        let mut stake = StakeRecord {
+            last_update_delegated_lamports: 3 * LAMPORTS_PER_SOL,
+            ..StakeRecord::default()
+        };
+
+        // rewards are left to update_active and its fee
+        assert!(!reconcile_negative_drift(
+            &mut state,
+            &mut validator,
+            &mut stake,
+            4 * LAMPORTS_PER_SOL
+        ));
+        assert!(!reconcile_negative_drift(
+            &mut state,
+            &mut validator,
+            &mut stake,
+            3 * LAMPORTS_PER_SOL
+        ));
+        assert_eq!(stake.last_update_delegated_lamports, 3 * LAMPORTS_PER_SOL);
+        assert_eq!(validator.active_balance, 4 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            state.validator_system.total_active_balance,
+            10 * LAMPORTS_PER_SOL
+        );
+
+        assert!(reconcile_negative_drift(
+            &mut state,
+            &mut validator,
+            &mut stake,
+            LAMPORTS_PER_SOL
+        ));
+        assert_eq!(stake.last_update_delegated_lamports, LAMPORTS_PER_SOL);
+        assert_eq!(validator.active_balance, 2 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            state.validator_system.total_active_balance,
+            8 * LAMPORTS_PER_SOL
+        );
+
+        // the validator balance lower than the drift
+        validator.active_balance = 0;
+        assert!(reconcile_negative_drift(
+            &mut state,
+            &mut validator,
+            &mut stake,
+            0
+        ));
+        assert_eq!(validator.active_balance, 0);
+        assert_eq!(
+            state.validator_system.total_active_balance,
+            7 * LAMPORTS_PER_SOL
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError,
+    events::{admin::ReconcileTotalActiveBalanceEvent, U64ValueChange},
+    state::validator_system::ValidatorList,
+    State,
+};
+
+#[derive(Accounts)]
+pub struct ReconcileTotalActiveBalance<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Box<Account<'info, State>>,
+    pub admin_authority: Signer<'info>,
+    #[account(address = state.validator_system.validator_list.account)]
+    pub validator_list: Account<'info, ValidatorList>,
+}
+
+impl<'info> ReconcileTotalActiveBalance<'info> {
+    /// Repair tool: recomputes total_active_balance as the sum of the validator active balances.
+    /// The validators are summed in pages of count starting at start_index (0 restarts the run),
+    /// the last page sets the total. Only while the program is paused so the balances don't move between the pages
+    pub fn process(&mut self, start_index: u32, count: u32) -> Result<()> {
+        require!(self.state.paused, MarinadeError::NotPaused);
+        let old = self.state.validator_system.total_active_balance;
+        let done = self.state.reconcile_total_active_balance(
+            &self.validator_list.to_account_info().data.borrow(),
+            start_index,
+            count,
+        )?;
+        let validator_count = self.state.validator_system.validator_count();
+        if !done {
+            msg!(
+                "Summed validators up to {} of {}",
+                self.state.reconcile_next_validator_index,
+                validator_count
+            );
+            return Ok(());
+        }
+
+        let new = self.state.validator_system.total_active_balance;
+        msg!("Total active balance {} reconciled to {}", old, new);
+        emit!(ReconcileTotalActiveBalanceEvent {
+            state: self.state.key(),
+            validator_count,
+            total_active_balance_change: U64ValueChange { old, new },
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError,
+    events::{admin::RefillExtraStakeDeltaRunsEvent, U32ValueChange},
+    State,
+};
+
+#[derive(Accounts)]
+pub struct RefillExtraStakeDeltaRuns<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Account<'info, State>,
+    pub admin_authority: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> RefillExtraStakeDeltaRuns<'info> {
+    /// Sets the extra stake_reserve runs allowed for the validators already staked in the epoch
+    pub fn process(&mut self, count: u32) -> Result<()> {
+        require_gte!(
+            self.state.max_extra_stake_delta_runs,
+            count,
+            MarinadeError::ExtraStakeDeltaRunsTooHigh
+        );
+        let old = self.state.stake_system.extra_stake_delta_runs;
+        self.state.stake_system.extra_stake_delta_runs = count;
+
+        emit!(RefillExtraStakeDeltaRunsEvent {
+            state: self.state.key(),
+            epoch: self.clock.epoch,
+            extra_stake_delta_runs_change: U32ValueChange { old, new: count },
+        });
+        Ok(())
+    }
+}
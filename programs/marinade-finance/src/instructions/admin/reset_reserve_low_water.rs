@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::MarinadeError, events::admin::ResetReserveLowWaterEvent, State};
+
+#[derive(Accounts)]
+pub struct ResetReserveLowWater<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Account<'info, State>,
+    pub admin_authority: Signer<'info>,
+}
+
+impl<'info> ResetReserveLowWater<'info> {
+    /// Starts a new reserve low-water measurement window
+    pub fn process(&mut self) -> Result<()> {
+        // record for event
+        let reserve_low_water_lamports = self.state.reserve_low_water_lamports;
+        self.state.reserve_low_water_lamports = std::u64::MAX;
+
+        emit!(ResetReserveLowWaterEvent {
+            state: self.state.key(),
+            reserve_low_water_lamports,
+        });
+        Ok(())
+    }
+}
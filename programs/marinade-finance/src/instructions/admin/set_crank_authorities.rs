@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError, events::admin::SetCrankAuthoritiesEvent, state::MAX_CRANK_AUTHORITIES,
+    State,
+};
+
+#[derive(Accounts)]
+pub struct SetCrankAuthorities<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Account<'info, State>,
+    pub admin_authority: Signer<'info>,
+}
+
+impl<'info> SetCrankAuthorities<'info> {
+    /// Replaces the keys allowed to sign stake_reserve.
+    /// An empty list makes stake_reserve permissionless again
+    pub fn process(&mut self, crank_authorities: Vec<Pubkey>) -> Result<()> {
+        require_gte!(
+            MAX_CRANK_AUTHORITIES,
+            crank_authorities.len(),
+            MarinadeError::TooManyCrankAuthorities
+        );
+
+        let old_crank_authorities = self.state.crank_authorities().to_vec();
+        self.state.crank_authorities = [Pubkey::default(); MAX_CRANK_AUTHORITIES];
+        self.state.crank_authorities[..crank_authorities.len()].copy_from_slice(&crank_authorities);
+        self.state.crank_authority_count = crank_authorities.len() as u8;
+
+        emit!(SetCrankAuthoritiesEvent {
+            state: self.state.key(),
+            old_crank_authorities,
+            new_crank_authorities: crank_authorities,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError, events::admin::SetPreferredValidatorsEvent,
+    state::validator_system::MAX_PREFERRED_VALIDATORS, State,
+};
+
+#[derive(Accounts)]
+pub struct SetPreferredValidators<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Account<'info, State>,
+    pub admin_authority: Signer<'info>,
+}
+
+impl<'info> SetPreferredValidators<'info> {
+    /// Replaces the validator indexes stake_reserve_preferred tries in order.
+    /// The list is not updated by remove_validator: indexes out of the list are skipped
+    pub fn process(&mut self, validator_indexes: Vec<u32>) -> Result<()> {
+        require_gte!(
+            MAX_PREFERRED_VALIDATORS,
+            validator_indexes.len(),
+            MarinadeError::TooManyPreferredValidators
+        );
+        for index in validator_indexes.iter() {
+            self.state.validator_system.check_validator_index(*index)?;
+        }
+
+        let old_preferred_validators = self.state.preferred_validators().to_vec();
+        self.state.preferred_validators = [0; MAX_PREFERRED_VALIDATORS];
+        self.state.preferred_validators[..validator_indexes.len()]
+            .copy_from_slice(&validator_indexes);
+        self.state.preferred_validator_count = validator_indexes.len() as u8;
+
+        emit!(SetPreferredValidatorsEvent {
+            state: self.state.key(),
+            old_preferred_validators,
+            new_preferred_validators: validator_indexes,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError,
+    events::{admin::SetStakeDeltaWindowEvent, U64ValueChange},
+    state::stake_system::StakeSystem,
+    State,
+};
+
+#[derive(Accounts)]
+pub struct SetStakeDeltaWindow<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Account<'info, State>,
+    pub admin_authority: Signer<'info>,
+
+    pub epoch_schedule: Sysvar<'info, EpochSchedule>,
+}
+
+impl<'info> SetStakeDeltaWindow<'info> {
+    /// Sets the number of the last slots of the epoch where the stake-delta operations are allowed.
+    /// The window must be shorter than the epoch
+    pub fn process(&mut self, slots_for_stake_delta: u64) -> Result<()> {
+        require_gte!(
+            slots_for_stake_delta,
+            StakeSystem::MIN_UPDATE_WINDOW,
+            MarinadeError::UpdateWindowIsTooLow
+        );
+        let slots_in_epoch = self.epoch_schedule.slots_per_epoch;
+        require_gt!(
+            slots_in_epoch,
+            slots_for_stake_delta,
+            MarinadeError::StakeDeltaWindowIsTooLong
+        );
+
+        let old = self.state.stake_system.slots_for_stake_delta;
+        self.state.stake_system.slots_for_stake_delta = slots_for_stake_delta;
+
+        emit!(SetStakeDeltaWindowEvent {
+            state: self.state.key(),
+            slots_for_stake_delta_change: U64ValueChange {
+                old,
+                new: slots_for_stake_delta,
+            },
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError,
+    events::{admin::SetValidatorDrainOnlyEvent, BoolValueChange},
+    state::validator_system::ValidatorList,
+    State,
+};
+
+#[derive(Accounts)]
+pub struct SetValidatorDrainOnly<'info> {
+    #[account(
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Account<'info, State>,
+    pub admin_authority: Signer<'info>,
+    #[account(
+        mut,
+        address = state.validator_system.validator_list.account,
+    )]
+    pub validator_list: Account<'info, ValidatorList>,
+}
+
+impl<'info> SetValidatorDrainOnly<'info> {
+    /// Stops (or resumes) stake_reserve into the validator without unstaking it,
+    /// for winding down before remove_validator
+    pub fn process(&mut self, index: u32, validator_vote: Pubkey, drain_only: bool) -> Result<()> {
+        let mut validator = self.state.validator_system.get_checked(
+            &self.validator_list.to_account_info().data.borrow(),
+            index,
+            &validator_vote,
+        )?;
+        let old = validator.drain_only;
+        validator.drain_only = drain_only;
+        self.state.validator_system.set(
+            &mut self.validator_list.to_account_info().data.borrow_mut(),
+            index,
+            validator,
+        )?;
+
+        emit!(SetValidatorDrainOnlyEvent {
+            state: self.state.key(),
+            validator: validator_vote,
+            index,
+            drain_only_change: BoolValueChange {
+                old,
+                new: drain_only,
+            },
+        });
+        Ok(())
+    }
+}
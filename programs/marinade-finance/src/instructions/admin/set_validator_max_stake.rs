@@ -0,0 +1,52 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError,
+    events::{admin::SetValidatorMaxStakeEvent, U64ValueChange},
+    state::validator_system::ValidatorList,
+    State,
+};
+
+#[derive(Accounts)]
+pub struct SetValidatorMaxStake<'info> {
+    #[account(
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Account<'info, State>,
+    pub admin_authority: Signer<'info>,
+    #[account(
+        mut,
+        address = state.validator_system.validator_list.account,
+    )]
+    pub validator_list: Account<'info, ValidatorList>,
+}
+
+impl<'info> SetValidatorMaxStake<'info> {
+    /// Sets the hard cap of the validator active balance for stake_reserve, 0 for no cap.
+    /// The stake over the cap is not unstaked
+    pub fn process(&mut self, index: u32, validator_vote: Pubkey, max_stake: u64) -> Result<()> {
+        let mut validator = self.state.validator_system.get_checked(
+            &self.validator_list.to_account_info().data.borrow(),
+            index,
+            &validator_vote,
+        )?;
+        let old = validator.max_stake;
+        validator.max_stake = max_stake;
+        self.state.validator_system.set(
+            &mut self.validator_list.to_account_info().data.borrow_mut(),
+            index,
+            validator,
+        )?;
+
+        emit!(SetValidatorMaxStakeEvent {
+            state: self.state.key(),
+            validator: validator_vote,
+            index,
+            max_stake_change: U64ValueChange {
+                old,
+                new: max_stake,
+            },
+        });
+        Ok(())
+    }
+}
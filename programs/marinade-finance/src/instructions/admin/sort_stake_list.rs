@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake;
+use anchor_spl::stake::StakeAccount;
+
+use crate::{
+    error::MarinadeError, events::admin::SortStakeListEvent, state::stake_system::StakeList, State,
+};
+
+#[derive(Accounts)]
+pub struct SortStakeList<'info> {
+    #[account(
+        mut,
+        has_one = admin_authority @ MarinadeError::InvalidAdminAuthority
+    )]
+    pub state: Box<Account<'info, State>>,
+    pub admin_authority: Signer<'info>,
+    #[account(
+        mut,
+        address = state.stake_system.stake_list.account,
+    )]
+    pub stake_list: Account<'info, StakeList>,
+}
+
+impl<'info> SortStakeList<'info> {
+    /// Migration of an existing stake_list to the sorted by validator layout.
+    /// `stake_accounts` are the accounts of the records starting from sorted_stake_count in the list order.
+    /// Can be called multiple times until all the records are sorted
+    pub fn process(&mut self, stake_accounts: &[AccountInfo]) -> Result<()> {
+        require!(!self.state.paused, MarinadeError::ProgramIsPaused);
+
+        for stake_account in stake_accounts {
+            let mut stake = self.state.stake_system.get_checked(
+                &self.stake_list.to_account_info().data.as_ref().borrow(),
+                self.state.sorted_stake_count,
+                stake_account.key,
+            )?;
+            require_keys_eq!(
+                *stake_account.owner,
+                stake::program::ID,
+                MarinadeError::WrongStakeAccountOrIndex
+            );
+            let stake_account_data =
+                StakeAccount::try_deserialize(&mut stake_account.data.borrow().as_ref())?;
+            // records created before the validator field was introduced have it zeroed
+            if let Some(delegation) = stake_account_data.delegation() {
+                stake.validator = delegation.voter_pubkey;
+            }
+            self.state.sort_next_stake(
+                &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
+                stake,
+            )?;
+        }
+
+        emit!(SortStakeListEvent {
+            state: self.state.key(),
+            sorted_count: self.state.sorted_stake_count,
+            count: self.state.stake_system.stake_count(),
+        });
+        Ok(())
+    }
+}
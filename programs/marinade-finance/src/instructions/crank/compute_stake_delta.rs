@@ -0,0 +1,90 @@
+use crate::{calc::u64_from_i128, state::validator_system::ValidatorList, State};
+use anchor_lang::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct StakeDeltaSimulation {
+    pub stake_delta: i128,
+    pub total_stake_target: u64,
+    /// None if no validator_index was requested
+    pub validator_stake_target: Option<u64>,
+    /// amount stake_reserve would delegate to the validator, 0 if it would not stake
+    pub stake_target: Option<u64>,
+}
+
+#[derive(Accounts)]
+pub struct ComputeStakeDelta<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(address = state.validator_system.validator_list.account)]
+    pub validator_list: Account<'info, ValidatorList>,
+    #[account(
+        seeds = [
+            &state.key().to_bytes(),
+            State::RESERVE_SEED
+        ],
+        bump = state.reserve_bump_seed
+    )]
+    pub reserve_pda: SystemAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub epoch_schedule: Sysvar<'info, EpochSchedule>,
+}
+
+impl<'info> ComputeStakeDelta<'info> {
+    /// View for the bots: dry-run of the stake_reserve sizing.
+    /// Does not check the stake-delta window and the double stake-delta of the validator
+    pub fn process(&self, validator_index: Option<u32>) -> Result<StakeDeltaSimulation> {
+        let stake_delta = self.state.stake_reserve_delta(self.reserve_pda.lamports());
+        let total_stake_delta = u64_from_i128(stake_delta.max(0))?;
+        let total_stake_target = self
+            .state
+            .validator_system
+            .total_active_balance
+            .saturating_add(total_stake_delta);
+        let mut simulation = StakeDeltaSimulation {
+            stake_delta,
+            total_stake_target,
+            validator_stake_target: None,
+            stake_target: None,
+        };
+        if let Some(validator_index) = validator_index {
+            let validator = self.state.validator_system.get(
+                &self.validator_list.to_account_info().data.as_ref().borrow(),
+                validator_index,
+            )?;
+            let validator_stake_target = self.state.ramped_stake_target(
+                &validator,
+                self.state
+                    .validator_stake_target(&validator, total_stake_target)?,
+                self.clock.epoch,
+            );
+            let stake_target = if self
+                .state
+                .stake_delta_below_action_threshold(total_stake_delta)
+                || validator.drain_only
+                || validator.active_balance >= validator_stake_target
+            {
+                0
+            } else {
+                self.state
+                    .stake_reserve_target(
+                        validator_stake_target,
+                        validator.active_balance,
+                        total_stake_delta,
+                        &self.clock,
+                        self.epoch_schedule.get_last_slot_in_epoch(self.clock.epoch),
+                    )
+                    .min(validator.max_stake_room())
+                    .min(self.state.stake_per_call_cap())
+            };
+            simulation.validator_stake_target = Some(validator_stake_target);
+            simulation.stake_target = Some(
+                if stake_target < self.state.stake_system.validator_min_stake(&validator) {
+                    0
+                } else {
+                    stake_target
+                },
+            );
+        }
+        Ok(simulation)
+    }
+}
@@ -0,0 +1,114 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake::state::Delegation;
+use anchor_spl::stake::StakeAccount;
+
+use crate::{
+    error::MarinadeError, events::crank::DelegationAnomalyEvent, state::stake_system::StakeList,
+    State,
+};
+
+#[derive(Accounts)]
+pub struct ConfirmDelegation<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        address = state.stake_system.stake_list.account,
+    )]
+    pub stake_list: Account<'info, StakeList>,
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> ConfirmDelegation<'info> {
+    /// Reconciliation crank: sets delegation_confirmed of the stake record
+    /// when the stake account is delegated to the recorded validator and not deactivating.
+    /// Otherwise clears it and emits DelegationAnomalyEvent
+    /// (stakes deactivated by deactivate_stake or emergency_unstake are reported too).
+    /// Returns the new delegation_confirmed
+    pub fn process(&mut self, stake_index: u32) -> Result<bool> {
+        require!(!self.state.paused, MarinadeError::ProgramIsPaused);
+
+        let mut stake = self.state.stake_system.get_checked(
+            &self.stake_list.to_account_info().data.as_ref().borrow(),
+            stake_index,
+            self.stake_account.to_account_info().key,
+        )?;
+        // records migrated from the older layout have no validator until sort_stake_list
+        require_keys_neq!(
+            stake.validator,
+            Pubkey::default(),
+            MarinadeError::StakeRecordValidatorUnknown
+        );
+
+        let delegation = self.stake_account.delegation();
+        let confirmed = is_delegation_confirmed(delegation.as_ref(), &stake.validator);
+        if !confirmed {
+            msg!(
+                "Stake {} is not delegated to validator {} as recorded",
+                stake.stake_account,
+                stake.validator
+            );
+            emit!(DelegationAnomalyEvent {
+                state: self.state.key(),
+                epoch: self.clock.epoch,
+                stake_index,
+                stake_account: stake.stake_account,
+                expected_validator: stake.validator,
+                delegated_validator: delegation.map(|delegation| delegation.voter_pubkey),
+                deactivation_epoch: delegation
+                    .map_or(std::u64::MAX, |delegation| delegation.deactivation_epoch),
+            });
+        }
+
+        stake.delegation_confirmed = confirmed as u8;
+        self.state.stake_system.set(
+            &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
+            stake_index,
+            stake,
+        )?;
+        Ok(confirmed)
+    }
+}
+
+/// delegated to the validator and not deactivating
+pub(crate) fn is_delegation_confirmed(delegation: Option<&Delegation>, validator: &Pubkey) -> bool {
+    matches!(
+        delegation,
+        Some(delegation) if delegation.voter_pubkey == *validator
+            && delegation.deactivation_epoch == std::u64::MAX
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::*;
+    use anchor_lang::solana_program::stake::state::Delegation;
+
+    use super::is_delegation_confirmed;
+
+    #[test]
+    fn test_is_delegation_confirmed() {
+        let validator = Pubkey::new_unique();
+        let delegation = Delegation {
+            voter_pubkey: validator,
+            stake: 1_000_000_000,
+            activation_epoch: 10,
+            ..Delegation::default()
+        };
+        assert!(is_delegation_confirmed(Some(&delegation), &validator));
+        // not delegated
+        assert!(!is_delegation_confirmed(None, &validator));
+        // redelegated outside of the program
+        assert!(!is_delegation_confirmed(
+            Some(&delegation),
+            &Pubkey::new_unique()
+        ));
+        // deactivating
+        let deactivating = Delegation {
+            deactivation_epoch: 12,
+            ..delegation
+        };
+        assert!(!is_delegation_confirmed(Some(&deactivating), &validator));
+    }
+}
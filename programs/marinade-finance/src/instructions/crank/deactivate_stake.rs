@@ -23,7 +23,11 @@ use crate::checks::check_stake_amount_and_validator;
 
 #[derive(Accounts)]
 pub struct DeactivateStake<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        // the first constraint: Anchor checks it after deserializing the accounts, before the address and PDA checks
+        constraint = !state.is_paused_for(State::PAUSE_UNSTAKING) @ MarinadeError::UnstakingPaused,
+    )]
     pub state: Box<Account<'info, State>>,
     // Readonly. For stake delta calculation
     #[account(
@@ -139,7 +143,6 @@ impl<'info> DeactivateStake<'info> {
         // compute target for this particular validator (total_stake_target * score/total_score)
         let validator_stake_target = self
             .state
-            .validator_system
             .validator_stake_target(&validator, total_stake_target)?;
 
         // compute how much we should unstake from this validator
@@ -170,7 +173,7 @@ impl<'info> DeactivateStake<'info> {
             },
         );
 
-        let (unstaked_amount, deactivate_whole_stake) =
+        let (unstaked_amount, split_stake_index) =
             if stake_account_target < 2 * self.state.stake_system.min_stake {
                 // unstake all if what will remain in the account is < twice min_stake
                 msg!("Deactivate whole stake {}", stake.stake_account);
@@ -194,7 +197,7 @@ impl<'info> DeactivateStake<'info> {
                 // Return back the rent reserve of unused split stake account
                 self.return_unused_split_stake_account_rent()?;
 
-                (stake.last_update_delegated_lamports, true)
+                (stake.last_update_delegated_lamports, None)
             } else {
                 // we must perform partial unstake
                 // Update validator.last_stake_delta_epoch for split-stakes only because probably we need to unstake multiple whole stakes for the same validator
@@ -229,9 +232,10 @@ impl<'info> DeactivateStake<'info> {
                     stake.stake_account
                 );
 
-                self.state.stake_system.add(
+                let split_stake_index = self.state.add_stake(
                     &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
                     &self.split_stake_account.key(),
+                    &validator.validator_account,
                     split_amount,
                     &self.clock,
                     0, // is_emergency_unstaking? no
@@ -276,10 +280,14 @@ impl<'info> DeactivateStake<'info> {
                 ))?;
 
                 stake.last_update_delegated_lamports -= split_amount;
-                (split_amount, false)
+                (split_amount, Some(split_stake_index))
             };
         // we now consider amount no longer "active" for this specific validator
         validator.active_balance -= unstaked_amount;
+        if split_stake_index.is_none() {
+            // the whole account is deactivating
+            validator.on_stake_account_removed();
+        }
         // Any stake-delta activity must activate stake delta mode
         self.state.stake_system.last_stake_delta_epoch = self.clock.epoch;
         // and in state totals,
@@ -313,14 +321,10 @@ impl<'info> DeactivateStake<'info> {
             stake_index,
             stake_account: self.stake_account.key(),
             last_update_stake_delegation,
-            split_stake_account: if deactivate_whole_stake {
-                None
-            } else {
-                Some(SplitStakeAccountInfo {
-                    account: self.split_stake_account.key(),
-                    index: self.state.stake_system.stake_count() - 1,
-                })
-            },
+            split_stake_account: split_stake_index.map(|index| SplitStakeAccountInfo {
+                account: self.split_stake_account.key(),
+                index,
+            }),
             validator_index,
             validator_vote: validator.validator_account,
             total_stake_target,
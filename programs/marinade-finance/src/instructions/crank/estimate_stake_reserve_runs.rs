@@ -0,0 +1,42 @@
+use crate::{state::validator_system::ValidatorList, State};
+use anchor_lang::prelude::*;
+
+use super::StakePlanCursor;
+
+#[derive(Accounts)]
+pub struct EstimateStakeReserveRuns<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(address = state.validator_system.validator_list.account)]
+    pub validator_list: Account<'info, ValidatorList>,
+    #[account(
+        seeds = [
+            &state.key().to_bytes(),
+            State::RESERVE_SEED
+        ],
+        bump = state.reserve_bump_seed
+    )]
+    pub reserve_pda: SystemAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> EstimateStakeReserveRuns<'info> {
+    /// View for the bot: how many stake_reserve calls are needed to deploy the current stake delta.
+    /// See StakePlanCursor::advance for the simulation details
+    pub fn process(&self) -> Result<u32> {
+        let mut runs: u32 = 0;
+        if let Some(mut cursor) = StakePlanCursor::start(&self.state, self.reserve_pda.lamports())?
+        {
+            let validator_list_info = self.validator_list.to_account_info();
+            cursor.advance(
+                &self.state,
+                &validator_list_info.data.borrow(),
+                self.clock.epoch,
+                u32::MAX,
+                |_, _| runs += 1,
+            )?;
+            msg!("Undeployed stake delta {}", cursor.remaining_stake_delta);
+        }
+        Ok(runs)
+    }
+}
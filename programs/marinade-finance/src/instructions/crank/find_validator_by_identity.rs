@@ -0,0 +1,42 @@
+use crate::{state::validator_system::ValidatorList, State};
+use anchor_lang::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct ValidatorByIdentity {
+    pub validator_index: u32,
+    /// pass it as validator_vote to the stake instructions
+    pub validator_vote: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct FindValidatorByIdentity<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(address = state.validator_system.validator_list.account)]
+    pub validator_list: Account<'info, ValidatorList>,
+}
+
+impl<'info> FindValidatorByIdentity<'info> {
+    /// View for the tooling tracking validators by identity:
+    /// the validator list entry of the vote account of the identity among `vote_accounts`
+    /// in validator_list[start_index..start_index + max_scan].
+    /// ValidatorNotFound if the identity is not in the scanned range
+    pub fn process(
+        &self,
+        identity: Pubkey,
+        start_index: u32,
+        max_scan: u32,
+        vote_accounts: &[AccountInfo<'info>],
+    ) -> Result<ValidatorByIdentity> {
+        let (validator_index, validator) = self.state.validator_system.find_by_identity(
+            &self.validator_list.to_account_info().data.as_ref().borrow(),
+            &identity,
+            vote_accounts,
+            start_index,
+            max_scan,
+        )?;
+        Ok(ValidatorByIdentity {
+            validator_index,
+            validator_vote: validator.validator_account,
+        })
+    }
+}
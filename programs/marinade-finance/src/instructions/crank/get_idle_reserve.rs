@@ -0,0 +1,32 @@
+use crate::State;
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct GetIdleReserve<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        seeds = [
+            &state.key().to_bytes(),
+            State::RESERVE_SEED
+        ],
+        bump = state.reserve_bump_seed
+    )]
+    pub reserve_pda: SystemAccount<'info>,
+}
+
+impl<'info> GetIdleReserve<'info> {
+    /// View for yield-drag reporting: reserve lamports which can be staked.
+    /// Differently from state.available_reserve_balance (reserve minus rent exempt)
+    /// it excludes the obligations: the delayed-unstake tickets not covered by cooling down stakes
+    pub fn process(&self) -> Result<u64> {
+        let obligations = self
+            .state
+            .circulating_ticket_balance
+            .saturating_sub(self.state.stake_system.delayed_unstake_cooling_down);
+        Ok(self
+            .reserve_pda
+            .lamports()
+            .saturating_sub(self.state.rent_exempt_for_token_acc)
+            .saturating_sub(obligations))
+    }
+}
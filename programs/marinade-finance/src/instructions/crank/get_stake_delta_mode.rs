@@ -0,0 +1,28 @@
+use crate::State;
+use anchor_lang::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct StakeDeltaMode {
+    /// epoch of the last stake-delta operation (u64::MAX if never)
+    pub last_stake_delta_epoch: u64,
+    /// stake-delta operations already happened in the current epoch
+    pub is_active: bool,
+}
+
+#[derive(Accounts)]
+pub struct GetStakeDeltaMode<'info> {
+    pub state: Box<Account<'info, State>>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> GetStakeDeltaMode<'info> {
+    /// View for the bots: stake and unstake cranks share the stake-delta mode of the epoch
+    pub fn process(&self) -> Result<StakeDeltaMode> {
+        let last_stake_delta_epoch = self.state.stake_system.last_stake_delta_epoch;
+        Ok(StakeDeltaMode {
+            last_stake_delta_epoch,
+            is_active: last_stake_delta_epoch == self.clock.epoch,
+        })
+    }
+}
@@ -0,0 +1,14 @@
+use crate::{state::stake_system::StakeReserveLastStatus, State};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct GetStakeReserveLastStatus<'info> {
+    pub state: Box<Account<'info, State>>,
+}
+
+impl<'info> GetStakeReserveLastStatus<'info> {
+    /// View for monitoring: outcome of the last not reverted stake_reserve call
+    pub fn process(&self) -> Result<StakeReserveLastStatus> {
+        Ok(self.state.stake_reserve_last_status)
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{state::stake_system::StakeReserveReport, State};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct GetStakeReserveReport<'info> {
+    pub state: Box<Account<'info, State>>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> GetStakeReserveReport<'info> {
+    /// View for the bot: stake_reserve summary of the current epoch
+    pub fn process(&self) -> Result<StakeReserveReport> {
+        let mut report = self.state.stake_reserve_report;
+        // counters stored for a previous epoch are reported as zeroes
+        Ok(*report.for_epoch(self.clock.epoch))
+    }
+}
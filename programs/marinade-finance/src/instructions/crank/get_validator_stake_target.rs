@@ -0,0 +1,45 @@
+use crate::{state::validator_system::ValidatorList, State};
+use anchor_lang::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct ValidatorStakeTarget {
+    pub validator_stake_target: u64,
+    pub active_balance: u64,
+}
+
+#[derive(Accounts)]
+pub struct GetValidatorStakeTarget<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(address = state.validator_system.validator_list.account)]
+    pub validator_list: Account<'info, ValidatorList>,
+    #[account(
+        seeds = [
+            &state.key().to_bytes(),
+            State::RESERVE_SEED
+        ],
+        bump = state.reserve_bump_seed
+    )]
+    pub reserve_pda: SystemAccount<'info>,
+}
+
+impl<'info> GetValidatorStakeTarget<'info> {
+    /// View for the operators: the target balance of the validator for the current stake delta.
+    /// A validator with zero score (e.g. being removed) has zero target
+    pub fn process(&self, validator_index: u32) -> Result<ValidatorStakeTarget> {
+        let validator = self.state.validator_system.get(
+            &self.validator_list.to_account_info().data.as_ref().borrow(),
+            validator_index,
+        )?;
+        let total_stake_target = self.state.total_stake_target(self.reserve_pda.lamports())?;
+        let validator_stake_target = if validator.score == 0 {
+            0
+        } else {
+            self.state
+                .validator_stake_target(&validator, total_stake_target)?
+        };
+        Ok(ValidatorStakeTarget {
+            validator_stake_target,
+            active_balance: validator.active_balance,
+        })
+    }
+}
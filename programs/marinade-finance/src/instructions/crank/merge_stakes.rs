@@ -142,6 +142,8 @@ impl<'info> MergeStakes<'info> {
             validator.validator_account,
             MarinadeError::InvalidSourceStakeDelegation
         );
+        // the stake program rejects stakes of different lockups when one is in force
+        // (e.g. a stake_reserve lockup and an older unlocked stake), lift_stake_lockup clears it first
         invoke_signed(
             &stake::instruction::merge(
                 self.destination_stake.to_account_info().key,
@@ -178,6 +180,8 @@ impl<'info> MergeStakes<'info> {
             self.source_stake.meta().unwrap().rent_exempt_reserve - extra_delegated;
         // update validator.active_balance
         validator.active_balance += extra_delegated;
+        // the source account is merged into the destination
+        validator.on_stake_account_removed();
         // store in list
         self.state.validator_system.set(
             &mut self
@@ -200,7 +204,7 @@ impl<'info> MergeStakes<'info> {
             destination_stake_info,
         )?;
         // Call this last because of index invalidation
-        self.state.stake_system.remove(
+        self.state.remove_stake(
             &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
             source_stake_index,
         )?;
@@ -223,7 +227,8 @@ impl<'info> MergeStakes<'info> {
                     ]],
                 ),
                 returned_stake_rent,
-                None,
+                // the custodian of the stake_reserve lockup
+                Some(self.stake_withdraw_authority.to_account_info()),
             )?;
         }
         emit!(MergeStakesEvent {
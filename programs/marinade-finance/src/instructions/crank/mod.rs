@@ -1,11 +1,43 @@
+pub mod compute_stake_delta;
+pub mod confirm_delegation;
 pub mod deactivate_stake;
+pub mod estimate_stake_reserve_runs;
+pub mod find_validator_by_identity;
+pub mod get_idle_reserve;
+pub mod get_stake_delta_mode;
+pub mod get_stake_reserve_last_status;
+pub mod get_stake_reserve_report;
+pub mod get_validator_stake_target;
 pub mod merge_stakes;
+pub mod preview_all_validator_targets;
 pub mod redelegate;
+pub mod simulate_stake_plan;
 pub mod stake_reserve;
+pub mod stake_reserve_multi;
+pub mod stake_reserve_with_scores;
+pub mod stake_window_status;
+pub mod total_active_balance_gap;
 pub mod update;
+pub mod would_stake_validator;
 
+pub use compute_stake_delta::*;
+pub use confirm_delegation::*;
 pub use deactivate_stake::*;
+pub use estimate_stake_reserve_runs::*;
+pub use find_validator_by_identity::*;
+pub use get_idle_reserve::*;
+pub use get_stake_delta_mode::*;
+pub use get_stake_reserve_last_status::*;
+pub use get_stake_reserve_report::*;
+pub use get_validator_stake_target::*;
 pub use merge_stakes::*;
+pub use preview_all_validator_targets::*;
 pub use redelegate::*;
+pub use simulate_stake_plan::*;
 pub use stake_reserve::*;
+pub use stake_reserve_multi::*;
+pub use stake_reserve_with_scores::*;
+pub use stake_window_status::*;
+pub use total_active_balance_gap::*;
 pub use update::*;
+pub use would_stake_validator::*;
@@ -0,0 +1,57 @@
+use crate::{state::validator_system::ValidatorList, State};
+use anchor_lang::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct ValidatorTargetPreview {
+    pub validator_index: u32,
+    pub active_balance: u64,
+    pub validator_stake_target: u64,
+}
+
+#[derive(Accounts)]
+pub struct PreviewAllValidatorTargets<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(address = state.validator_system.validator_list.account)]
+    pub validator_list: Account<'info, ValidatorList>,
+    #[account(
+        seeds = [
+            &state.key().to_bytes(),
+            State::RESERVE_SEED
+        ],
+        bump = state.reserve_bump_seed
+    )]
+    pub reserve_pda: SystemAccount<'info>,
+}
+
+impl<'info> PreviewAllValidatorTargets<'info> {
+    // fits into the return data limit
+    pub const MAX_ITEMS: u32 = 50;
+
+    /// View for the dashboards: get_validator_stake_target for the validators
+    /// from start_index, at most MAX_ITEMS. Call again from the next index to get the rest
+    pub fn process(&self, start_index: u32) -> Result<Vec<ValidatorTargetPreview>> {
+        let total_stake_target = self.state.total_stake_target(self.reserve_pda.lamports())?;
+        let validator_list_info = self.validator_list.to_account_info();
+        let validator_list_data = validator_list_info.data.borrow();
+        let end_index = self
+            .state
+            .validator_system
+            .validator_count()
+            .min(start_index.saturating_add(Self::MAX_ITEMS));
+        let mut previews = vec![];
+        for validator_index in start_index..end_index {
+            let validator = self
+                .state
+                .validator_system
+                .get(&validator_list_data, validator_index)?;
+            previews.push(ValidatorTargetPreview {
+                validator_index,
+                active_balance: validator.active_balance,
+                validator_stake_target: self
+                    .state
+                    .validator_stake_target(&validator, total_stake_target)?,
+            });
+        }
+        Ok(previews)
+    }
+}
@@ -1,7 +1,10 @@
 use crate::{
-    checks::check_stake_amount_and_validator,
+    checks::{check_stake_amount_and_validator, check_vote_account_rent_exempt},
     error::MarinadeError,
-    events::crank::{RedelegateEvent, SplitStakeAccountInfo},
+    events::{
+        admin::StakeListNearCapacityEvent,
+        crank::{RedelegateEvent, SplitStakeAccountInfo},
+    },
     state::{
         stake_system::{StakeList, StakeRecord, StakeSystem},
         validator_system::ValidatorList,
@@ -165,7 +168,6 @@ impl<'info> ReDelegate<'info> {
         // compute target for this particular validator (total_stake_target * score/total_score)
         let source_validator_stake_target = self
             .state
-            .validator_system
             .validator_stake_target(&source_validator, total_stake_target)?;
         // if validator is already on-target (or the split will be lower than min_stake), exit now
         if source_validator.active_balance
@@ -200,12 +202,18 @@ impl<'info> ReDelegate<'info> {
                 &self.dest_validator_account.key(),
             )
             .map_err(|e| e.with_account_name("dest_validator_account"))?;
+        if self.state.check_vote_rent_exempt {
+            check_vote_account_rent_exempt(
+                &self.dest_validator_account,
+                &Rent::get()?,
+                "dest_validator_account",
+            )?;
+        }
         let dest_validator_balance = dest_validator.active_balance;
 
         // compute dest validator target
         let dest_validator_stake_target = self
             .state
-            .validator_system
             .validator_stake_target(&dest_validator, total_stake_target)?;
         // verify: dest validator must be under target
         if dest_validator.active_balance + self.state.stake_system.min_stake
@@ -235,7 +243,7 @@ impl<'info> ReDelegate<'info> {
             stake.last_update_delegated_lamports - redelegate_amount_theoretical;
         // select if we redelegate all or if we split first
         // (do not leave less than min_stake in the account)
-        let (source_account, redelegate_amount_effective) =
+        let (source_account, redelegate_amount_effective, split_stake_index) =
             if stake_account_after < self.state.stake_system.min_stake {
                 // redelegate all if what will remain in the account is < min_stake
                 msg!("ReDelegate whole stake {}", stake.stake_account);
@@ -256,17 +264,23 @@ impl<'info> ReDelegate<'info> {
                 (
                     self.stake_account.to_account_info(),
                     amount_to_redelegate_whole_account,
+                    None,
                 )
                 //
                 //
             } else {
                 // not whole account,
                 // we need to split first
-                self.split_stake_for_redelegation(&mut stake, redelegate_amount_theoretical)?;
+                let split_stake_index = self.split_stake_for_redelegation(
+                    &mut stake,
+                    &source_validator.validator_account,
+                    redelegate_amount_theoretical,
+                )?;
                 // account to redelegate is the splitted account
                 (
                     self.split_stake_account.to_account_info(),
                     redelegate_amount_theoretical,
+                    Some(split_stake_index),
                 )
             };
         self.state
@@ -301,18 +315,45 @@ impl<'info> ReDelegate<'info> {
 
         // add new warming-up re-delegated account to Marinade stake-accounts list
         // warn - the lamports are accounted here, and no longer in the source account
-        self.state.stake_system.add(
+        let redelegate_stake_index = self.state.add_stake(
             &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
             &self.redelegate_stake_account.key(),
+            &dest_validator.validator_account,
             redelegate_amount_effective,
             &self.clock,
             0, // is_emergency_unstaking
         )?;
+        // the new record belongs to another validator
+        // so in sorted stake list mode it can be inserted before the source records
+        let shift_index = |index: u32| {
+            if redelegate_stake_index <= index {
+                index + 1
+            } else {
+                index
+            }
+        };
+        let stake_index = shift_index(stake_index);
+        let split_stake_index = split_stake_index.map(shift_index);
+        if let Some(capacity) = self.state.stake_system.stake_list_crossed_near_capacity(
+            self.stake_list.to_account_info().data_len(),
+            if split_stake_index.is_some() { 2 } else { 1 },
+        )? {
+            emit!(StakeListNearCapacityEvent {
+                state: self.state.key(),
+                count: self.state.stake_system.stake_count(),
+                capacity,
+            });
+        }
 
         // we now consider amount no longer "active" for this specific validator
         source_validator.active_balance -= redelegate_amount_effective;
         // it moved to dest-validator
         dest_validator.active_balance += redelegate_amount_effective;
+        if split_stake_index.is_none() {
+            // the whole source account is deactivating
+            source_validator.on_stake_account_removed();
+        }
+        dest_validator.on_stake_account_added();
 
         // update stake-list & validator-list
         self.state.stake_system.set(
@@ -358,15 +399,11 @@ impl<'info> ReDelegate<'info> {
             dest_validator_balance,
             dest_validator_stake_target,
             redelegate_amount: redelegate_amount_effective,
-            split_stake_account: if source_account.key() == self.split_stake_account.key() {
-                Some(SplitStakeAccountInfo {
-                    account: self.split_stake_account.key(),
-                    index: self.state.stake_system.stake_count() - 2,
-                })
-            } else {
-                None
-            },
-            redelegate_stake_index: self.state.stake_system.stake_count() - 1,
+            split_stake_account: split_stake_index.map(|index| SplitStakeAccountInfo {
+                account: self.split_stake_account.key(),
+                index,
+            }),
+            redelegate_stake_index,
             redelegate_stake_account: self.redelegate_stake_account.key(),
         });
 
@@ -398,8 +435,9 @@ impl<'info> ReDelegate<'info> {
     pub fn split_stake_for_redelegation(
         &mut self,
         stake: &mut StakeRecord,
+        validator: &Pubkey,
         amount: u64,
-    ) -> Result<()> {
+    ) -> Result<u32> {
         msg!(
             "Split {} lamports from stake {} to {}",
             amount,
@@ -408,9 +446,10 @@ impl<'info> ReDelegate<'info> {
         );
 
         // add the split account as new account to Marinade stake-accounts list
-        self.state.stake_system.add(
+        let split_stake_index = self.state.add_stake(
             &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
             &self.split_stake_account.key(),
+            validator,
             0, // this account will be deactivating,
             // all lamports will be moved to the re-delegated account,
             // but even with no lamports, we expect the redelegate-deactivating account to provide rewards at the end of the epoch.
@@ -448,6 +487,6 @@ impl<'info> ReDelegate<'info> {
         // update amount accounted for source stake account
         stake.last_update_delegated_lamports -= amount;
 
-        Ok(())
+        Ok(split_stake_index)
     }
 }
@@ -0,0 +1,163 @@
+use crate::{calc::u64_from_i128, state::validator_system::ValidatorList, State};
+use anchor_lang::prelude::*;
+
+/// Position of the stake_reserve simulation, allows to continue it in the next call
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct StakePlanCursor {
+    pub validator_index: u32,
+    pub total_stake_target: u64,
+    pub remaining_stake_delta: u64,
+    pub total_active_balance_room: u64,
+    pub extra_stake_delta_runs: u32,
+}
+
+impl StakePlanCursor {
+    /// start of the plan for the reserve balance. None if there is nothing to stake
+    pub fn start(state: &State, reserve_balance: u64) -> Result<Option<Self>> {
+        let stake_delta = state.stake_reserve_delta(reserve_balance);
+        if stake_delta <= 0 {
+            return Ok(None);
+        }
+        let remaining_stake_delta = u64_from_i128(stake_delta)?;
+        Ok(Some(Self {
+            validator_index: 0,
+            // every stake_reserve moves lamports from the delta to the active balance,
+            // so the total target doesn't change between the calls
+            total_stake_target: state
+                .validator_system
+                .total_active_balance
+                .saturating_add(remaining_stake_delta),
+            remaining_stake_delta,
+            total_active_balance_room: state.total_active_balance_room(),
+            extra_stake_delta_runs: state.stake_system.extra_stake_delta_runs,
+        }))
+    }
+
+    /// Simulates stake_reserve calls in validator list order with the same sizing as stake_reserve
+    /// calling on_stake(validator_index, amount) for every stake account it would create.
+    /// Validators that can not receive stake this epoch (already staked without extra runs left,
+    /// or the amount lower than min stake) are skipped, so the delta may remain partially undeployed.
+    /// Stops after max_stakes stakes. Returns true when the end of the plan is reached
+    pub fn advance<F: FnMut(u32, u64)>(
+        &mut self,
+        state: &State,
+        validator_list_data: &[u8],
+        epoch: u64,
+        max_stakes: u32,
+        mut on_stake: F,
+    ) -> Result<bool> {
+        let mut stakes: u32 = 0;
+        while self.validator_index < state.validator_system.validator_count() {
+            if state.stake_delta_below_action_threshold(self.remaining_stake_delta)
+                || self.total_active_balance_room == 0
+            {
+                break;
+            }
+            if stakes == max_stakes {
+                return Ok(false);
+            }
+            let index = self.validator_index;
+            self.validator_index += 1;
+            let validator = state.validator_system.get(validator_list_data, index)?;
+            if validator.drain_only || state.in_stake_cooldown(&validator, epoch) {
+                continue;
+            }
+            if validator.last_stake_delta_epoch == epoch {
+                if self.extra_stake_delta_runs == 0 {
+                    continue;
+                }
+                self.extra_stake_delta_runs -= 1;
+            }
+            let validator_stake_target = state.ramped_stake_target(
+                &validator,
+                state.validator_stake_target(&validator, self.total_stake_target)?,
+                epoch,
+            );
+            if validator.active_balance >= validator_stake_target {
+                continue;
+            }
+            let stake_target = state
+                .stake_reserve_amount(
+                    validator_stake_target,
+                    validator.active_balance,
+                    self.remaining_stake_delta,
+                )
+                .min(self.total_active_balance_room)
+                .min(validator.max_stake_room())
+                .min(state.stake_per_call_cap());
+            if stake_target < state.stake_system.validator_min_stake(&validator)
+                || state.stake_accounts_limit_reached(&validator)
+            {
+                continue;
+            }
+            self.remaining_stake_delta -= stake_target;
+            self.total_active_balance_room -= stake_target;
+            stakes += 1;
+            on_stake(index, stake_target);
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct StakePlanItem {
+    pub validator_index: u32,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct StakePlan {
+    pub items: Vec<StakePlanItem>,
+    /// pass it to the next call to continue the plan. None when the plan is complete
+    pub next: Option<StakePlanCursor>,
+}
+
+#[derive(Accounts)]
+pub struct SimulateStakePlan<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(address = state.validator_system.validator_list.account)]
+    pub validator_list: Account<'info, ValidatorList>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> SimulateStakePlan<'info> {
+    // fits into the return data limit
+    pub const MAX_ITEMS: u32 = 64;
+
+    /// View for planning and tests: the sequence of stakes stake_reserve would create in this epoch
+    /// for the hypothetical reserve balance (ignoring the stake-delta window ramp and staking over the target)
+    pub fn process(
+        &self,
+        reserve_balance: u64,
+        cursor: Option<StakePlanCursor>,
+        max_items: u32,
+    ) -> Result<StakePlan> {
+        let cursor = match cursor {
+            Some(cursor) => Some(cursor),
+            None => StakePlanCursor::start(&self.state, reserve_balance)?,
+        };
+        let mut cursor = match cursor {
+            Some(cursor) => cursor,
+            None => return Ok(StakePlan::default()),
+        };
+        let validator_list_info = self.validator_list.to_account_info();
+        let mut items = vec![];
+        let done = cursor.advance(
+            &self.state,
+            &validator_list_info.data.borrow(),
+            self.clock.epoch,
+            max_items.min(Self::MAX_ITEMS),
+            |validator_index, amount| {
+                items.push(StakePlanItem {
+                    validator_index,
+                    amount,
+                })
+            },
+        )?;
+        Ok(StakePlan {
+            items,
+            next: if done { None } else { Some(cursor) },
+        })
+    }
+}
@@ -1,34 +1,45 @@
 use crate::{
+    calc::u64_from_i128,
+    checks::{
+        check_distinct_account, check_stake_account_rent_exempt, check_stake_account_size,
+        check_vote_account_rent_exempt, latest_stake_history_entry, vote_account_last_voted_slot,
+    },
     error::MarinadeError,
-    events::crank::StakeReserveEvent,
+    events::{
+        admin::StakeListNearCapacityEvent,
+        crank::{ExtraStakeDeltaRunConsumedEvent, StakeReserveEvent, StakeReserveSkippedEvent},
+    },
+    log_compute_units,
     state::{
-        stake_system::{StakeList, StakeSystem},
-        validator_system::ValidatorList,
+        stake_system::{StakeList, StakeRecord, StakeReserveStatus, StakeSystem},
+        validator_system::{ValidatorList, ValidatorRecord},
     },
-    State, ID,
+    State,
 };
 use anchor_lang::{
     prelude::*,
-    system_program::{transfer, Transfer},
+    system_program::{create_account, transfer, CreateAccount, Transfer},
 };
 use anchor_lang::{
     solana_program::{
-        log::sol_log_compute_units,
         program::{invoke, invoke_signed},
         stake::{
             self,
-            state::{Authorized, Lockup, StakeState},
+            state::{Authorized, StakeState},
         },
         sysvar::stake_history,
     },
     system_program,
 };
 use anchor_spl::stake::{withdraw, Stake, StakeAccount, Withdraw};
-use std::convert::TryFrom;
 
 #[derive(Accounts)]
 pub struct StakeReserve<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        // the first constraint: Anchor checks it after deserializing the accounts, before the address and PDA checks
+        constraint = !state.is_paused_for(State::PAUSE_STAKING) @ MarinadeError::StakingPaused,
+    )]
     pub state: Box<Account<'info, State>>,
     #[account(
         mut,
@@ -73,7 +84,6 @@ pub struct StakeReserve<'info> {
         owner = system_program::ID
     )]
     pub rent_payer: Signer<'info>,
-
     pub clock: Sysvar<'info, Clock>,
     pub epoch_schedule: Sysvar<'info, EpochSchedule>,
     pub rent: Sysvar<'info, Rent>,
@@ -86,253 +96,2036 @@ pub struct StakeReserve<'info> {
 
     pub system_program: Program<'info, System>,
     pub stake_program: Program<'info, Stake>,
+
+    // the optional accounts are the last ones: the clients of the older versions can omit them
+    // (allow-missing-optionals). The program id is passed for a skipped optional account
+    // when extra stake accounts follow in the remaining accounts
+    /// CHECK: CPI. optional. Vote of fallback_validator_index
+    #[account(mut)]
+    pub fallback_validator_vote: Option<UncheckedAccount<'info>>,
+    /// optional. Recorded in the stake record as the bot created the stake.
+    /// With state.crank_authorities set, it or rent_payer must be one of them
+    pub cranker: Option<Signer<'info>>,
+}
+
+/// Arguments of stake_reserve_with_params.
+/// Borsh has no field defaults, so the struct is deserialized field by field:
+/// the fields missing at the end of the data keep their defaults and the data of
+/// an older client (down to the bare validator_index of stake_reserve) is accepted.
+/// New fields are appended with the old behavior as the default.
+/// The missing fields can only be detected when the params are the last argument
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize)]
+pub struct StakeReserveParams {
+    pub validator_index: u32,
+    pub fallback_validator_index: Option<u32>,
+    pub dry_run: bool,
+    pub strict: bool,
+    /// stake this amount instead of the computed one,
+    /// raised to the validator min stake and capped by the computed amount
+    pub requested_amount: Option<u64>,
+}
+
+impl AnchorDeserialize for StakeReserveParams {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        fn field_or_default<T: AnchorDeserialize + Default>(buf: &mut &[u8]) -> std::io::Result<T> {
+            if buf.is_empty() {
+                Ok(T::default())
+            } else {
+                T::deserialize(buf)
+            }
+        }
+        Ok(Self {
+            validator_index: u32::deserialize(buf)?,
+            fallback_validator_index: field_or_default(buf)?,
+            dry_run: field_or_default(buf)?,
+            strict: field_or_default(buf)?,
+            requested_amount: field_or_default(buf)?,
+        })
+    }
+}
+
+impl From<u32> for StakeReserveParams {
+    fn from(validator_index: u32) -> Self {
+        Self {
+            validator_index,
+            ..Default::default()
+        }
+    }
 }
 
 impl<'info> StakeReserve<'info> {
     /// called by the bot
-    /// Receives self.stake_account where to stake, normally an empty account (new keypair)
+    /// Receives self.stake_account where to stake, normally an empty account (new keypair).
+    /// A pre-funded account reduces the transfer from the reserve, the extra lamports become program stake
     /// stakes from available delta-stake in data.validator_index
+    /// or in fallback_validator_index when validator_index is already on target.
+    /// dry_run runs all the checks without staking and without changing the state.
+    /// strict fails the nothing to stake, double stake delta and on target no-ops
+    /// Returns the staked amount (0 on no-op) and the stake delta left for the next calls
+    ///
+    /// Failures (MarinadeError code):
+    /// - paused: ProgramIsPaused 6077, StakingPaused 6097
+    /// - crank allowlist: UnauthorizedCrank 6116
+    /// - list headers: InvalidListAccount 6119
+    /// - validator index or vote: InvalidValidatorIndex 6107, WrongValidatorAccountOrIndex 6049
+    /// - slot window: TooEarlyForStakeDelta 6042
+    /// - strict no-ops: NothingToStake 6093, DoubleStakeDelta 6094, ValidatorAlreadyOnTarget 6095
+    /// - vote balance: ValidatorVoteNotRentExempt 6087
+    /// - stake account not a new empty account: InvalidStakeAccountSize 6088, StakeAccountNotRentExempt 6092,
+    ///   pre-funded over the stake amount: StakeAccountPrefundedOverStake 6121
+    /// - stake history sysvar: StakeHistoryNotUpdated 6103
+    /// - stake accounts: TooManyStakeAccounts 6102, NotEnoughStakeAccounts 6109
+    /// - reserve balance: ReserveWouldNotBeRentExempt 6112
+    /// - calculation: CalculationFailure 6013, InvariantViolation 6104
+    ///
     /// pub fn stake_reserve()
-    pub fn process(&mut self, validator_index: u32) -> Result<()> {
+    pub fn process(
+        &mut self,
+        params: StakeReserveParams,
+        extra_stake_accounts: &[AccountInfo<'info>],
+    ) -> Result<StakeReserveResult> {
+        let clock = Clock::clone(&self.clock);
+        self.process_with_clock(&clock, params, false, extra_stake_accounts)
+    }
+
+    /// stake_reserve of the whole stake delta into the validator, over its stake target.
+    /// Called by stake_reserve_sweep of the validator manager
+    pub fn process_sweep(
+        &mut self,
+        validator_index: u32,
+        extra_stake_accounts: &[AccountInfo<'info>],
+    ) -> Result<StakeReserveResult> {
+        let clock = Clock::clone(&self.clock);
+        self.process_with_clock(&clock, validator_index.into(), true, extra_stake_accounts)
+    }
+
+    fn process_with_clock(
+        &mut self,
+        clock: &Clock,
+        StakeReserveParams {
+            validator_index,
+            fallback_validator_index,
+            dry_run,
+            strict,
+            requested_amount,
+        }: StakeReserveParams,
+        sweep: bool,
+        extra_stake_accounts: &[AccountInfo<'info>],
+    ) -> Result<StakeReserveResult> {
         require!(!self.state.paused, MarinadeError::ProgramIsPaused);
+        self.check_crank()?;
+        self.state
+            .validator_system
+            .check_validator_index(validator_index)?;
+        if let Some(fallback_validator_index) = fallback_validator_index {
+            self.state
+                .validator_system
+                .check_validator_index(fallback_validator_index)?;
+        }
+        let stake_reserve_accounts = self.stake_reserve_accounts();
+        stake_reserve_accounts.check_distinct_stake_account(
+            self.stake_account.to_account_info().key,
+            "stake_account",
+        )?;
+        for extra_stake_account in extra_stake_accounts {
+            stake_reserve_accounts
+                .check_distinct_stake_account(extra_stake_account.key, "extra_stake_account")?;
+        }
 
-        sol_log_compute_units();
+        log_compute_units();
 
-        // record for event
-        let total_active_balance = self.state.validator_system.total_active_balance;
+        let reserve_balance = self.reserve_pda.lamports();
+        let last_slot = self.epoch_schedule.get_last_slot_in_epoch(clock.epoch);
+        let validator_list_info = self.validator_list.to_account_info();
+        let mut validator_index = validator_index;
+        let mut validator_vote = self.validator_vote.to_account_info();
+        let mut plan = plan_stake_reserve(
+            &self.state,
+            &validator_list_info.data.as_ref().borrow(),
+            validator_index,
+            validator_vote.key,
+            reserve_balance,
+            clock,
+            last_slot,
+            sweep,
+            requested_amount,
+        )?;
+        if let (
+            Err(StakeReserveStatus::ValidatorOnTarget | StakeReserveStatus::ValidatorDrainOnly),
+            Some(fallback_validator_index),
+        ) = (&plan, fallback_validator_index)
+        {
+            msg!(
+                "Trying fallback validator index {}",
+                fallback_validator_index
+            );
+            validator_index = fallback_validator_index;
+            validator_vote = self
+                .fallback_validator_vote
+                .as_ref()
+                .ok_or_else(|| {
+                    error!(ErrorCode::AccountNotEnoughKeys)
+                        .with_account_name("fallback_validator_vote")
+                })?
+                .to_account_info();
+            plan = plan_stake_reserve(
+                &self.state,
+                &validator_list_info.data.as_ref().borrow(),
+                validator_index,
+                validator_vote.key,
+                reserve_balance,
+                clock,
+                last_slot,
+                sweep,
+                requested_amount,
+            )
+            .map_err(|e| e.with_account_name("fallback_validator_vote"))?;
+        }
+        if plan.is_ok() {
+            if let Some(status) =
+                check_vote_activity(&self.state, &validator_vote, clock, &self.epoch_schedule)?
+            {
+                plan = Err(status);
+            }
+        }
+        let plan = match plan {
+            Ok(plan) => plan,
+            Err(reason) => {
+                if let (true, Some(error)) = (strict, reason.strict_error()) {
+                    return Err(error!(error));
+                }
+                let no_op = StakeReserveResult::no_op(&self.state, reserve_balance, reason)?;
+                if dry_run {
+                    msg!("Dry run: no-op {:?}", reason);
+                    self.return_unused_stake_account_rent()?;
+                    return Ok(no_op);
+                }
+                self.skip(clock, validator_index, validator_vote.key(), reason)?;
+                return Ok(no_op); // Not an error. Don't fail other instructions in tx
+            }
+        };
+        // A pre-funded stake account (holding more than the rent exempt reserve) is accepted:
+        // the extra lamports are delegated together with the reserve lamports
+        // and become the program custodied stake without minting mSOL (a donation to the pool).
+        // Only self.stake_account can be pre-funded, the extra stake accounts are created empty.
+        // It is a system account with lamports only: `init` allocates it uninitialized,
+        // so the lockup and the authorities are the ones set by the initialize below
+        let prefunded = self
+            .stake_account
+            .to_account_info()
+            .lamports()
+            .saturating_sub(StakeSystem::stake_account_rent_exempt(
+                &self.rent,
+                self.stake_account.to_account_info().data_len(),
+            ));
 
-        let staker = Pubkey::create_program_address(
-            &[
-                &self.state.key().to_bytes(),
-                StakeSystem::STAKE_DEPOSIT_SEED,
-                &[self.state.stake_system.stake_deposit_bump_seed],
-            ],
-            &ID,
-        )
-        .unwrap();
+        check_stake_account_size(
+            self.stake_account.to_account_info().data_len(),
+            "stake_account",
+        )?;
+        check_stake_account_rent_exempt(
+            &self.stake_account.to_account_info(),
+            &self.rent,
+            "stake_account",
+        )?;
 
-        let withdrawer = Pubkey::create_program_address(
-            &[
-                &self.state.key().to_bytes(),
-                StakeSystem::STAKE_WITHDRAW_SEED,
-                &[self.state.stake_system.stake_withdraw_bump_seed],
-            ],
-            &ID,
+        let result = stake_reserve_accounts.stake_planned(
+            &mut self.state,
+            clock,
+            validator_index,
+            &validator_vote,
+            plan,
+            Some(&self.stake_account.to_account_info()),
+            extra_stake_accounts,
+            prefunded,
+            dry_run,
+        )?;
+        if dry_run {
+            self.return_unused_stake_account_rent()?;
+        }
+        Ok(result)
+    }
+
+    /// the accounts of the delegation shared with stake_reserve_multi
+    fn stake_reserve_accounts(&self) -> StakeReserveAccounts<'info> {
+        StakeReserveAccounts::new(
+            &self.state,
+            &self.validator_list,
+            &self.stake_list,
+            &self.reserve_pda,
+            &self.stake_deposit_authority,
+            &self.rent_payer,
+            self.cranker.as_ref(),
+            &self.clock,
+            &self.rent,
+            &self.stake_history,
+            &self.stake_config,
+            &self.system_program,
+            &self.stake_program,
         )
-        .unwrap();
+    }
 
-        let reserve_balance = self.reserve_pda.lamports();
-        let stake_delta = self.state.stake_delta(reserve_balance);
-        if stake_delta <= 0 {
-            if stake_delta < 0 {
-                msg!(
-                    "Must unstake {} instead of staking",
-                    u64::try_from(-stake_delta).expect("Stake delta overflow")
-                );
-            } else {
-                msg!("Noting to do");
+    /// stake_reserve_greedy(). Stakes into the most under-staked validator
+    /// of validator_list[start_index..start_index + max_scan].
+    /// self.validator_vote must be the vote of the selected validator
+    pub fn process_greedy(
+        &mut self,
+        start_index: u32,
+        max_scan: u32,
+        extra_stake_accounts: &[AccountInfo<'info>],
+    ) -> Result<StakeReserveResult> {
+        require!(!self.state.paused, MarinadeError::ProgramIsPaused);
+        // before reading the list: the no-op below records the status
+        self.check_crank()?;
+        let clock = Clock::clone(&self.clock);
+        match self.select_greedy_validator(&clock, start_index, max_scan)? {
+            Some(validator_index) => {
+                msg!("Greedy selected validator index {}", validator_index);
+                self.process_with_clock(&clock, validator_index.into(), false, extra_stake_accounts)
+            }
+            None => {
+                msg!("No under-staked validator from index {}", start_index);
+                self.skip(
+                    &clock,
+                    start_index,
+                    self.validator_vote.key(),
+                    StakeReserveStatus::AllValidatorsAtTarget,
+                )?;
+                StakeReserveResult::no_op(
+                    &self.state,
+                    self.reserve_pda.lamports(),
+                    StakeReserveStatus::AllValidatorsAtTarget,
+                )
             }
-            self.return_unused_stake_account_rent()?;
-            return Ok(()); // Not an error. Don't fail other instructions in tx
         }
-        let total_stake_delta = u64::try_from(stake_delta).expect("Stake delta overflow");
-        let total_stake_target = total_active_balance.saturating_add(total_stake_delta);
+    }
 
-        let mut validator = self
+    /// index of the validator with the largest gap to its stake target
+    /// skipping the validators which can not be staked in this epoch
+    fn select_greedy_validator(
+        &self,
+        clock: &Clock,
+        start_index: u32,
+        max_scan: u32,
+    ) -> Result<Option<u32>> {
+        select_validator_for_stake(
+            &self.state,
+            &self.validator_list.to_account_info().data.borrow(),
+            self.state.total_stake_target(self.reserve_pda.lamports())?,
+            clock.epoch,
+            start_index,
+            max_scan,
+        )
+    }
+
+    /// stake_reserve_by_vote(). Stakes into self.validator_vote
+    /// found in validator_list[start_index..start_index + max_scan]
+    pub fn process_by_vote(
+        &mut self,
+        start_index: u32,
+        max_scan: u32,
+        extra_stake_accounts: &[AccountInfo<'info>],
+    ) -> Result<StakeReserveResult> {
+        self.check_crank()?;
+        let validator_index = self
             .state
             .validator_system
-            .get_checked(
+            .find_by_vote(
                 &self.validator_list.to_account_info().data.as_ref().borrow(),
-                validator_index,
                 self.validator_vote.key,
-            )
-            .map_err(|e| e.with_account_name("validator_vote"))?;
+                start_index,
+                max_scan,
+            )?
+            .ok_or_else(|| {
+                error!(MarinadeError::ValidatorNotFound).with_account_name("validator_vote")
+            })?;
+        msg!(
+            "Validator {} found at index {}",
+            self.validator_vote.key,
+            validator_index
+        );
+        self.process(validator_index.into(), extra_stake_accounts)
+    }
+
+    /// stake_reserve_preferred(). Stakes into the first under-staked validator
+    /// of state.preferred_validators. self.validator_vote must be the vote of the selected validator
+    pub fn process_preferred(
+        &mut self,
+        extra_stake_accounts: &[AccountInfo<'info>],
+    ) -> Result<StakeReservePreferredResult> {
+        require!(!self.state.paused, MarinadeError::ProgramIsPaused);
+        require!(
+            self.state.preferred_validator_count > 0,
+            MarinadeError::NoPreferredValidators
+        );
+        // before reading the list: the no-op below records the status
+        self.check_crank()?;
+        let clock = Clock::clone(&self.clock);
+        match self.select_preferred_validator(&clock)? {
+            Some(validator_index) => {
+                msg!("Preferred validator index {}", validator_index);
+                Ok(StakeReservePreferredResult {
+                    validator_index: Some(validator_index),
+                    result: self.process_with_clock(
+                        &clock,
+                        validator_index.into(),
+                        false,
+                        extra_stake_accounts,
+                    )?,
+                })
+            }
+            None => {
+                msg!("No under-staked preferred validator");
+                self.skip(
+                    &clock,
+                    self.state.preferred_validators[0],
+                    self.validator_vote.key(),
+                    StakeReserveStatus::AllValidatorsAtTarget,
+                )?;
+                Ok(StakeReservePreferredResult {
+                    validator_index: None,
+                    result: StakeReserveResult::no_op(
+                        &self.state,
+                        self.reserve_pda.lamports(),
+                        StakeReserveStatus::AllValidatorsAtTarget,
+                    )?,
+                })
+            }
+        }
+    }
+
+    /// the first preferred validator under its stake target
+    /// skipping the validators which can not be staked in this epoch
+    /// and the indexes out of the list (the list is not updated by remove_validator)
+    fn select_preferred_validator(&self, clock: &Clock) -> Result<Option<u32>> {
+        let total_stake_target = self.state.total_stake_target(self.reserve_pda.lamports())?;
+        let validator_list_info = self.validator_list.to_account_info();
+        let validator_list_data = validator_list_info.data.borrow();
+        for &index in self.state.preferred_validators() {
+            if index >= self.state.validator_system.validator_count() {
+                continue;
+            }
+            let validator = self
+                .state
+                .validator_system
+                .get(&validator_list_data, index)?;
+            if !can_stake_in_epoch(&self.state, &validator, clock.epoch) {
+                continue;
+            }
+            let validator_stake_target = self
+                .state
+                .validator_stake_target(&validator, total_stake_target)?;
+            if validator.active_balance
+                < self
+                    .state
+                    .ramped_stake_target(&validator, validator_stake_target, clock.epoch)
+            {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    /// no-op exit
+    fn skip(
+        &mut self,
+        clock: &Clock,
+        validator_index: u32,
+        validator_vote: Pubkey,
+        reason: StakeReserveStatus,
+    ) -> Result<()> {
+        self.on_status(clock, reason);
+        emit!(StakeReserveSkippedEvent {
+            state: self.state.key(),
+            epoch: clock.epoch,
+            validator_index,
+            validator_vote,
+            reason,
+        });
+        self.return_unused_stake_account_rent()
+    }
+
+    fn check_crank(&self) -> Result<()> {
+        self.stake_reserve_accounts().check_crank(&self.state)
+    }
+
+    fn on_status(&mut self, clock: &Clock, status: StakeReserveStatus) {
+        self.state.on_stake_reserve_status(status, clock)
+    }
+
+    pub fn return_unused_stake_account_rent(&self) -> Result<()> {
+        // Return back the rent reserve of unused stake account in case of early return
+        withdraw(
+            CpiContext::new(
+                self.stake_program.to_account_info(),
+                Withdraw {
+                    stake: self.stake_account.to_account_info(),
+                    withdrawer: self.stake_account.to_account_info(),
+                    to: self.rent_payer.to_account_info(),
+                    clock: self.clock.to_account_info(),
+                    stake_history: self.stake_history.to_account_info(),
+                },
+            ),
+            self.stake_account.to_account_info().lamports(),
+            None,
+        )
+    }
+}
+
+/// Checked first by every stake_reserve entry point, before the lists are read
+/// and before a no-op is recorded: the crank allowlist and the list headers
+pub(crate) fn check_stake_reserve_crank(
+    state: &State,
+    crank_signers: &[&Pubkey],
+    validator_list_len: usize,
+    stake_list_len: usize,
+) -> Result<()> {
+    state.check_crank_authority(crank_signers)?;
+    state
+        .validator_system
+        .check_validator_list(validator_list_len)?;
+    state.stake_system.check_stake_list(stake_list_len)
+}
+
+/// Version of the stake_reserve logic returned in StakeReserveResult.version.
+/// Incremented whenever the amount staked or the no-op status can differ for the same state and accounts
+/// (sizing, clamps, no-op rules, new modes). Adding an instruction or a field read by the clients
+/// without changing the math keeps the version. Results before versioning have no version field
+pub const STAKE_RESERVE_VERSION: u8 = 2;
+
+/// Return data of stake_reserve: lets the bot chain the calls without recomputing the delta.
+/// status (the first byte) tells a stake from every kind of no-op
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct StakeReserveResult {
+    pub status: StakeReserveStatus,
+    pub staked: u64,
+    pub remaining_stake_delta: u64,
+    /// negative stake delta, needs deactivate_stake. Only with NothingToStake
+    pub unstake_delta: u64,
+    pub version: u8,
+}
+
+impl StakeReserveResult {
+    pub fn no_op(state: &State, reserve_balance: u64, status: StakeReserveStatus) -> Result<Self> {
+        let stake_delta = state.stake_reserve_delta(reserve_balance);
+        Ok(Self {
+            status,
+            staked: 0,
+            remaining_stake_delta: u64_from_i128(stake_delta.max(0))?,
+            unstake_delta: u64_from_i128((-stake_delta).max(0))?,
+            version: STAKE_RESERVE_VERSION,
+        })
+    }
+}
+
+/// Return data of stake_reserve_preferred
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct StakeReservePreferredResult {
+    /// the selected validator, None if all the preferred validators were skipped
+    pub validator_index: Option<u32>,
+    pub result: StakeReserveResult,
+}
+
+/// Amounts of a stake_reserve into a validator
+pub(crate) struct StakeReservePlan {
+    pub validator: ValidatorRecord,
+    pub total_stake_delta: u64,
+    pub total_stake_target: u64,
+    pub validator_stake_target: u64,
+    /// amount to delegate
+    pub stake_target: u64,
+    /// the validator was already staked in the epoch, the stake uses an extra stake delta run
+    pub extra_stake_delta_run: bool,
+}
+
+/// Accounts of the stake_reserve instructions used by the delegation of a planned stake
+pub(crate) struct StakeReserveAccounts<'info> {
+    pub state_address: Pubkey,
+    pub validator_list: AccountInfo<'info>,
+    pub stake_list: AccountInfo<'info>,
+    pub reserve_pda: AccountInfo<'info>,
+    pub stake_deposit_authority: AccountInfo<'info>,
+    pub rent_payer: AccountInfo<'info>,
+    /// recorded in the stake records, default when no cranker is passed
+    pub cranker: Option<Pubkey>,
+    pub clock: AccountInfo<'info>,
+    pub rent: Rent,
+    pub rent_sysvar: AccountInfo<'info>,
+    pub stake_history: AccountInfo<'info>,
+    pub stake_config: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub stake_program: AccountInfo<'info>,
+}
+
+impl<'info> StakeReserveAccounts<'info> {
+    /// the one builder of stake_reserve and stake_reserve_multi, from their account fields of the same names
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        state: &Account<'info, State>,
+        validator_list: &Account<'info, ValidatorList>,
+        stake_list: &Account<'info, StakeList>,
+        reserve_pda: &SystemAccount<'info>,
+        stake_deposit_authority: &UncheckedAccount<'info>,
+        rent_payer: &Signer<'info>,
+        cranker: Option<&Signer<'info>>,
+        clock: &Sysvar<'info, Clock>,
+        rent: &Sysvar<'info, Rent>,
+        stake_history: &UncheckedAccount<'info>,
+        stake_config: &UncheckedAccount<'info>,
+        system_program: &Program<'info, System>,
+        stake_program: &Program<'info, Stake>,
+    ) -> Self {
+        Self {
+            state_address: state.key(),
+            validator_list: validator_list.to_account_info(),
+            stake_list: stake_list.to_account_info(),
+            reserve_pda: reserve_pda.to_account_info(),
+            stake_deposit_authority: stake_deposit_authority.to_account_info(),
+            rent_payer: rent_payer.to_account_info(),
+            cranker: cranker.map(|cranker| cranker.key()),
+            clock: clock.to_account_info(),
+            rent: Rent::clone(rent),
+            rent_sysvar: rent.to_account_info(),
+            stake_history: stake_history.to_account_info(),
+            stake_config: stake_config.to_account_info(),
+            system_program: system_program.to_account_info(),
+            stake_program: stake_program.to_account_info(),
+        }
+    }
+
+    /// rent_payer or the optional cranker must be allowed when state.crank_authorities is set.
+    /// Then the list headers, see check_stake_reserve_crank
+    pub fn check_crank(&self, state: &State) -> Result<()> {
+        let mut crank_signers = vec![self.rent_payer.key];
+        if let Some(cranker) = &self.cranker {
+            crank_signers.push(cranker);
+        }
+        check_stake_reserve_crank(
+            state,
+            &crank_signers,
+            self.validator_list.data_len(),
+            self.stake_list.data_len(),
+        )
+    }
+
+    /// the transfer, initialize and delegate sequence assumes distinct accounts
+    pub fn check_distinct_stake_account(
+        &self,
+        stake_account: &Pubkey,
+        field_name: &str,
+    ) -> Result<()> {
+        check_distinct_account(
+            stake_account,
+            &[
+                (&self.state_address, "state"),
+                (self.reserve_pda.key, "reserve_pda"),
+                (self.validator_list.key, "validator_list"),
+                (self.stake_list.key, "stake_list"),
+            ],
+            field_name,
+        )
+    }
+
+    /// Stakes plan.stake_target into validator_vote, shared by stake_reserve and stake_reserve_multi.
+    /// The stake is split into chunks of at most max_stake_per_account: the first one into
+    /// created_stake_account (created by the caller, can be pre-funded with `prefunded` lamports),
+    /// the others into new_stake_accounts created here.
+    /// All the checks run before the dry run returns, then every chunk is transferred
+    /// from the reserve, initialized and delegated
+    #[allow(clippy::too_many_arguments)]
+    pub fn stake_planned(
+        &self,
+        state: &mut State,
+        clock: &Clock,
+        validator_index: u32,
+        validator_vote: &AccountInfo<'info>,
+        StakeReservePlan {
+            mut validator,
+            total_stake_delta,
+            total_stake_target,
+            validator_stake_target,
+            stake_target,
+            extra_stake_delta_run,
+        }: StakeReservePlan,
+        created_stake_account: Option<&AccountInfo<'info>>,
+        new_stake_accounts: &[AccountInfo<'info>],
+        prefunded: u64,
+        dry_run: bool,
+    ) -> Result<StakeReserveResult> {
         // record for event
+        let total_active_balance = state.validator_system.total_active_balance;
         let validator_active_balance = validator.active_balance;
+        let reserve_balance = self.reserve_pda.lamports();
+        // the stake delta decreases by the reserve part only. Zero when the stake takes all the delta
+        let remaining_stake_delta = total_stake_delta - stake_target.saturating_sub(prefunded);
 
-        if validator.last_stake_delta_epoch == self.clock.epoch {
-            // check if we have some extra stake runs allowed
-            if self.state.stake_system.extra_stake_delta_runs == 0 {
-                msg!(
-                    "Double delta stake command for validator {} in epoch {}",
-                    validator.validator_account,
-                    self.clock.epoch
-                );
-                self.return_unused_stake_account_rent()?;
-                return Ok(()); // Not an error. Don't fail other instructions in tx
-            } else {
-                // some extra runs allowed. Use one
-                self.state.stake_system.extra_stake_delta_runs -= 1;
-            }
+        if state.check_vote_rent_exempt {
+            check_vote_account_rent_exempt(validator_vote, &self.rent, "validator_vote")?;
         }
 
-        let last_slot = self.epoch_schedule.get_last_slot_in_epoch(self.clock.epoch);
+        if state.parse_stake_history {
+            // the latest entry is of the previous epoch when the history is updated at the epoch start
+            match latest_stake_history_entry(&self.stake_history)? {
+                Some((epoch, entry)) if epoch + 1 == clock.epoch => msg!(
+                    "Stake history epoch {} effective {} activating {}",
+                    epoch,
+                    entry.effective,
+                    entry.activating
+                ),
+                _ => return err!(MarinadeError::StakeHistoryNotUpdated),
+            }
+        }
 
+        let created_stake_accounts = created_stake_account.is_some() as u64;
+        let chunks = state.stake_chunks(
+            stake_target,
+            state.stake_system.validator_min_stake(&validator),
+        );
         require_gte!(
-            self.clock.slot,
-            last_slot.saturating_sub(self.state.stake_system.slots_for_stake_delta),
-            MarinadeError::TooEarlyForStakeDelta
+            created_stake_accounts + new_stake_accounts.len() as u64,
+            chunks,
+            MarinadeError::NotEnoughStakeAccounts
         );
+        state.check_stake_accounts_limit(&validator, chunks as u32)?;
+        let chunk_amount = stake_target / chunks;
+        // the pre-funded lamports count in the stake amount and its caps
+        require_gte!(
+            chunk_amount,
+            prefunded,
+            MarinadeError::StakeAccountPrefundedOverStake
+        );
+        // the transfers keep the reserve above its floor
+        state.check_reserve_transfer(
+            reserve_balance,
+            stake_target.saturating_sub(prefunded),
+            &self.rent,
+        )?;
 
-        let validator_stake_target = self
-            .state
-            .validator_system
-            .validator_stake_target(&validator, total_stake_target)?;
-
-        //verify the validator is under-staked
-        if validator_active_balance >= validator_stake_target {
+        if dry_run {
             msg!(
-                    "Validator {} has already reached stake target {}. Please stake into another validator",
-                    validator.validator_account,
-                    validator_stake_target
-                );
-            self.return_unused_stake_account_rent()?;
-            return Ok(()); // Not an error. Don't fail other instructions in tx
+                "Dry run: stake {} into validator {}",
+                stake_target,
+                validator_vote.key
+            );
+            return Ok(StakeReserveResult {
+                status: StakeReserveStatus::Staked,
+                staked: stake_target,
+                remaining_stake_delta,
+                unstake_delta: 0,
+                version: STAKE_RESERVE_VERSION,
+            });
+        }
+        if extra_stake_delta_run {
+            // the plan checked a run is left
+            state.stake_system.extra_stake_delta_runs -= 1;
+            emit!(ExtraStakeDeltaRunConsumedEvent {
+                state: self.state_address,
+                epoch: clock.epoch,
+                validator_index,
+                validator_vote: validator_vote.key(),
+                extra_stake_delta_runs_left: state.stake_system.extra_stake_delta_runs,
+            });
         }
 
-        // compute stake_target
-        // stake_target = validator_target - validator_actual_balance, at most total_stake_delta
-        let stake_target = validator_stake_target
-            .saturating_sub(validator_active_balance)
-            .min(total_stake_delta);
+        let staker = StakeSystem::stake_deposit_authority(
+            &self.state_address,
+            state.stake_system.stake_deposit_bump_seed,
+        );
 
-        // if what's left in stake_delta after this operation is < state.min_stake, take all the remainder from stake_delta
-        let stake_target = if total_stake_delta - stake_target < self.state.stake_system.min_stake {
-            total_stake_delta
-        } else {
-            stake_target
-        };
+        let withdrawer = StakeSystem::stake_withdraw_authority(
+            &self.state_address,
+            state.stake_system.stake_withdraw_bump_seed,
+        );
 
-        // if the amount to stake is < stake_system.min_stake (e.g. less than 1 SOL)
-        // we don't stake to avoid creating a stake account with less than 1 SOL
-        if stake_target < self.state.stake_system.min_stake {
-            msg!(
-                "Resulting stake {} is lower than min stake allowed {}",
-                stake_target,
-                self.state.stake_system.min_stake
+        let mut reserve_amount: u64 = 0;
+        let mut stake_amount: u64 = 0;
+        for chunk in 0..chunks {
+            let stake_account = match (chunk, created_stake_account) {
+                (0, Some(stake_account)) => stake_account.clone(),
+                _ => {
+                    let stake_account =
+                        &new_stake_accounts[(chunk - created_stake_accounts) as usize];
+                    self.create_stake_account(stake_account)?;
+                    stake_account.clone()
+                }
+            };
+            // the last chunk takes the rounding remainder
+            let chunk_stake = if chunk == chunks - 1 {
+                stake_target - chunk_amount * (chunks - 1)
+            } else {
+                chunk_amount
+            };
+            let chunk_prefunded = if chunk == 0 { prefunded } else { 0 };
+            if chunk_prefunded > 0 {
+                msg!("Stake account is pre-funded with {}", chunk_prefunded);
+            }
+            let chunk_reserve_amount = chunk_stake - chunk_prefunded;
+            let chunk_stake_amount = chunk_stake;
+
+            let available_reserve_balance_after = self.delegate_from_reserve(
+                state,
+                clock,
+                &stake_account,
+                validator_vote,
+                chunk_reserve_amount,
+                &staker,
+                &withdrawer,
+            )?;
+
+            let mut stake_record = StakeRecord::new(
+                stake_account.key,
+                validator_vote.key,
+                chunk_stake_amount,
+                clock,
+                0, // is_emergency_unstaking? no
             );
-            self.return_unused_stake_account_rent()?;
-            return Ok(()); // Not an error. Don't fail other instructions in tx
+            stake_record.cranker = self.cranker.unwrap_or_default();
+            let stake_index = state.add_stake_record(
+                &mut self.stake_list.data.as_ref().borrow_mut(),
+                stake_record,
+            )?;
+            if let Some(capacity) = state
+                .stake_system
+                .stake_list_crossed_near_capacity(self.stake_list.data_len(), 1)?
+            {
+                emit!(StakeListNearCapacityEvent {
+                    state: self.state_address,
+                    count: state.stake_system.stake_count(),
+                    capacity,
+                });
+            }
+
+            let first_stake_delta_in_epoch = validator.last_stake_delta_epoch != clock.epoch;
+            state
+                .stake_reserve_report
+                .for_epoch(clock.epoch)
+                .on_staked(chunk_reserve_amount, first_stake_delta_in_epoch);
+
+            // update validator record
+            validator.active_balance = validator
+                .active_balance
+                .checked_add(chunk_stake_amount)
+                .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+            validator.last_stake_delta_epoch = clock.epoch;
+            validator.on_stake_account_added();
+            // update also total_active_balance
+            state.validator_system.total_active_balance = state
+                .validator_system
+                .total_active_balance
+                .checked_add(chunk_stake_amount)
+                .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+            reserve_amount += chunk_reserve_amount;
+            stake_amount += chunk_stake_amount;
+
+            emit!(StakeReserveEvent {
+                state: self.state_address,
+                epoch: clock.epoch,
+                stake_index,
+                stake_account: stake_account.key(),
+                validator_index,
+                validator_vote: validator_vote.key(),
+                amount: chunk_stake_amount,
+                total_stake_target,
+                validator_stake_target,
+                reserve_balance,
+                total_active_balance,
+                validator_active_balance,
+                total_stake_delta,
+                cranker: stake_record.cranker,
+                total_active_balance_after: state.validator_system.total_active_balance,
+                available_reserve_balance_after,
+            });
         }
+        state.on_stake_reserve_status(StakeReserveStatus::Staked, clock);
+        state.on_stake_operations(clock.epoch, chunks);
+        // Any stake-delta activity must activate stake delta mode
+        state.stake_system.last_stake_delta_epoch = clock.epoch;
+        state.validator_system.set(
+            &mut self.validator_list.data.as_ref().borrow_mut(),
+            validator_index,
+            validator,
+        )?;
+        if state.invariant_checks {
+            // the stake delta must decrease by the lamports taken from the reserve.
+            // The pre-funded part of the stake is not in the reserve
+            let expected = total_stake_delta as i128 - reserve_amount as i128;
+            let observed = state
+                .stake_reserve_delta(self.reserve_pda.lamports())
+                .max(0);
+            if observed != expected {
+                msg!(
+                    "Stake delta invariant violation: observed {} expected {}",
+                    observed,
+                    expected
+                );
+                return err!(MarinadeError::InvariantViolation);
+            }
+        }
+
+        Ok(StakeReserveResult {
+            status: StakeReserveStatus::Staked,
+            staked: stake_amount,
+            remaining_stake_delta,
+            unstake_delta: 0,
+            version: STAKE_RESERVE_VERSION,
+        })
+    }
+
+    fn create_stake_account(&self, stake_account: &AccountInfo<'info>) -> Result<()> {
+        msg!("Create stake account {}", stake_account.key);
+        let space = std::mem::size_of::<StakeState>();
+        create_account(
+            CpiContext::new(
+                self.system_program.clone(),
+                CreateAccount {
+                    from: self.rent_payer.clone(),
+                    to: stake_account.clone(),
+                },
+            ),
+            StakeSystem::stake_account_rent_exempt(&self.rent, space),
+            space as u64,
+            &stake::program::ID,
+        )
+    }
 
+    /// transfers reserve_amount from the reserve into the stake account, initializes and delegates it.
+    /// Returns the available reserve balance after the transfer
+    #[allow(clippy::too_many_arguments)]
+    fn delegate_from_reserve(
+        &self,
+        state: &mut State,
+        clock: &Clock,
+        stake_account: &AccountInfo<'info>,
+        validator_vote: &AccountInfo<'info>,
+        reserve_amount: u64,
+        staker: &Pubkey,
+        withdrawer: &Pubkey,
+    ) -> Result<u64> {
         // transfer SOL from reserve_pda to the stake-account
-        sol_log_compute_units();
+        state.check_reserve_transfer(self.reserve_pda.lamports(), reserve_amount, &self.rent)?;
+        log_compute_units();
         msg!("Transfer to stake account");
         transfer(
             CpiContext::new_with_signer(
-                self.system_program.to_account_info(),
+                self.system_program.clone(),
                 Transfer {
-                    from: self.reserve_pda.to_account_info(),
-                    to: self.stake_account.to_account_info(),
+                    from: self.reserve_pda.clone(),
+                    to: stake_account.clone(),
                 },
                 &[&[
-                    &self.state.key().to_bytes(),
+                    &self.state_address.to_bytes(),
                     State::RESERVE_SEED,
-                    &[self.state.reserve_bump_seed],
+                    &[state.reserve_bump_seed],
                 ]],
             ),
-            stake_target,
+            reserve_amount,
         )?;
-        self.state.on_transfer_from_reserve(stake_target);
+        let available_reserve_balance_after =
+            state.on_stake_from_reserve(&self.state_address, reserve_amount, clock.epoch)?;
 
-        sol_log_compute_units();
+        log_compute_units();
         msg!("Initialize stake");
         invoke(
             &stake::instruction::initialize(
-                &self.stake_account.key(),
-                &Authorized { staker, withdrawer },
-                &Lockup::default(),
+                stake_account.key,
+                &Authorized {
+                    staker: *staker,
+                    withdrawer: *withdrawer,
+                },
+                &state.stake_lockup(withdrawer),
             ),
             &[
-                self.stake_program.to_account_info(),
-                self.stake_account.to_account_info(),
-                self.rent.to_account_info(),
+                self.stake_program.clone(),
+                stake_account.clone(),
+                self.rent_sysvar.clone(),
             ],
         )?;
 
-        sol_log_compute_units();
+        log_compute_units();
         msg!("Delegate stake");
         invoke_signed(
-            &stake::instruction::delegate_stake(
-                &self.stake_account.key(),
-                &staker,
-                self.validator_vote.key,
-            ),
+            &stake::instruction::delegate_stake(stake_account.key, staker, validator_vote.key),
             &[
-                self.stake_program.to_account_info(),
-                self.stake_account.to_account_info(),
-                self.stake_deposit_authority.to_account_info(),
-                self.validator_vote.to_account_info(),
-                self.clock.to_account_info(),
-                self.stake_history.to_account_info(),
-                self.stake_config.to_account_info(),
+                self.stake_program.clone(),
+                stake_account.clone(),
+                self.stake_deposit_authority.clone(),
+                validator_vote.clone(),
+                self.clock.clone(),
+                self.stake_history.clone(),
+                self.stake_config.clone(),
             ],
             &[&[
-                &self.state.key().to_bytes(),
+                &self.state_address.to_bytes(),
                 StakeSystem::STAKE_DEPOSIT_SEED,
-                &[self.state.stake_system.stake_deposit_bump_seed],
+                &[state.stake_system.stake_deposit_bump_seed],
             ]],
         )?;
+        Ok(available_reserve_balance_after)
+    }
+}
 
-        self.state.stake_system.add(
-            &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
-            &self.stake_account.key(),
-            stake_target,
-            &self.clock,
-            0, // is_emergency_unstaking? no
-        )?;
+/// false for the validators stake_reserve would skip regardless of the amounts
+pub(crate) fn can_stake_in_epoch(state: &State, validator: &ValidatorRecord, epoch: u64) -> bool {
+    validator.score != 0
+        && !validator.drain_only
+        && (validator.last_stake_delta_epoch != epoch
+            || state.stake_system.extra_stake_delta_runs != 0)
+        && !state.in_stake_cooldown(validator, epoch)
+}
 
-        // update validator record and store in list
-        validator.active_balance += stake_target;
-        validator.last_stake_delta_epoch = self.clock.epoch;
-        // Any stake-delta activity must activate stake delta mode
-        self.state.stake_system.last_stake_delta_epoch = self.clock.epoch;
-        self.state.validator_system.set(
-            &mut self
-                .validator_list
-                .to_account_info()
-                .data
-                .as_ref()
-                .borrow_mut(),
-            validator_index,
-            validator,
-        )?;
-        // update also total_active_balance
-        self.state.validator_system.total_active_balance += stake_target;
+/// Greedy selection: index of the validator with the largest gap to its stake target
+/// in validator_list[start_index..start_index + max_scan], skipping the validators
+/// which can not be staked in the epoch. None if no validator is under its target.
+/// Equal gaps: the lowest selection_rank wins. The rank depends only on state.selection_seed,
+/// the epoch and the index, so all the keepers select the same validator in the epoch
+/// and the winner of the ties rotates between the epochs (seed 0: the lowest index wins)
+pub(crate) fn select_validator_for_stake(
+    state: &State,
+    validator_list_data: &[u8],
+    total_stake_target: u64,
+    epoch: u64,
+    start_index: u32,
+    max_scan: u32,
+) -> Result<Option<u32>> {
+    let end_index = start_index
+        .saturating_add(max_scan)
+        .min(state.validator_system.validator_count());
+    let mut best: Option<(u32, u64, u64)> = None;
+    for index in start_index..end_index {
+        let validator = state.validator_system.get(validator_list_data, index)?;
+        if !can_stake_in_epoch(state, &validator, epoch) {
+            continue;
+        }
+        let gap = state
+            .ramped_stake_target(
+                &validator,
+                state.validator_stake_target(&validator, total_stake_target)?,
+                epoch,
+            )
+            .saturating_sub(validator.active_balance);
+        if gap == 0 {
+            continue;
+        }
+        let rank = selection_rank(state.selection_seed, epoch, index);
+        let better = match best {
+            None => true,
+            Some((_, best_gap, best_rank)) => {
+                gap > best_gap || (gap == best_gap && rank < best_rank)
+            }
+        };
+        if better {
+            best = Some((index, gap, rank));
+        }
+    }
+    Ok(best.map(|(index, _, _)| index))
+}
 
-        emit!(StakeReserveEvent {
-            state: self.state.key(),
-            epoch: self.clock.epoch,
-            stake_index: self.state.stake_system.stake_count() - 1,
-            stake_account: self.stake_account.key(),
-            validator_index,
-            validator_vote: self.validator_vote.key(),
-            amount: stake_target,
+/// rank of the validator among the equal gaps of select_validator_for_stake.
+/// Seed 0 keeps the list order, otherwise a splitmix64 mix of the seed, the epoch and the index
+pub(crate) fn selection_rank(seed: u64, epoch: u64, index: u32) -> u64 {
+    if seed == 0 {
+        return index as u64;
+    }
+    let mut z = seed
+        ^ epoch.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (index as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// ValidatorDelinquent when check_vote_activity is on
+/// and the vote account did not vote in the last max_idle_epochs epochs
+pub(crate) fn check_vote_activity(
+    state: &State,
+    validator_vote: &AccountInfo,
+    clock: &Clock,
+    epoch_schedule: &EpochSchedule,
+) -> Result<Option<StakeReserveStatus>> {
+    if !state.check_vote_activity {
+        return Ok(None);
+    }
+    let last_voted_epoch = vote_account_last_voted_slot(validator_vote)
+        .map_err(|e| e.with_account_name("validator_vote"))?
+        .map(|slot| epoch_schedule.get_epoch(slot));
+    match last_voted_epoch {
+        Some(epoch) if epoch.saturating_add(state.max_idle_epochs) >= clock.epoch => Ok(None),
+        _ => {
+            msg!(
+                "Vote account {} last voted in epoch {:?}",
+                validator_vote.key,
+                last_voted_epoch
+            );
+            Ok(Some(StakeReserveStatus::ValidatorDelinquent))
+        }
+    }
+}
+
+/// Sizing of stake_reserve shared by stake_reserve and stake_reserve_multi
+/// Returns the no-op reason when nothing must be staked into the validator.
+/// sweep (stake_reserve_sweep) replaces the validator stake target by the whole stake delta.
+/// requested_amount replaces the computed amount, raised to min stake and capped by the computed amount
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn plan_stake_reserve(
+    state: &State,
+    validator_list_data: &[u8],
+    validator_index: u32,
+    validator_vote: &Pubkey,
+    reserve_balance: u64,
+    clock: &Clock,
+    last_slot: u64,
+    sweep: bool,
+    requested_amount: Option<u64>,
+) -> Result<std::result::Result<StakeReservePlan, StakeReserveStatus>> {
+    let stake_delta = state.stake_reserve_delta(reserve_balance);
+    if stake_delta <= 0 {
+        if stake_delta < 0 {
+            msg!("Must unstake {} instead of staking", -stake_delta);
+        } else {
+            msg!("Noting to do");
+        }
+        return Ok(Err(StakeReserveStatus::NothingToStake));
+    }
+    let total_active_balance = state.validator_system.total_active_balance;
+    if state.total_active_balance_room() == 0 {
+        msg!(
+            "Total active balance {} reached the cap",
+            total_active_balance
+        );
+        return Ok(Err(StakeReserveStatus::GlobalCapReached));
+    }
+    if state.stake_operations_throttled(clock.epoch, 1) {
+        msg!(
+            "Throttled: {} stakes in epoch {}",
+            state.max_stake_operations_per_epoch,
+            clock.epoch
+        );
+        return Ok(Err(StakeReserveStatus::Throttled));
+    }
+    let total_stake_delta = u64_from_i128(stake_delta)?;
+    if state.stake_delta_below_action_threshold(total_stake_delta) {
+        msg!(
+            "Stake delta {} is below the action threshold {}",
+            total_stake_delta,
+            state.min_stake_delta_to_act
+        );
+        return Ok(Err(StakeReserveStatus::DeltaBelowActionThreshold));
+    }
+    let total_stake_target = total_active_balance
+        .checked_add(total_stake_delta)
+        .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+
+    let validator = state
+        .validator_system
+        .get_checked(validator_list_data, validator_index, validator_vote)
+        .map_err(|e| e.with_account_name("validator_vote"))?;
+    let validator_active_balance = validator.active_balance;
+
+    if validator.drain_only {
+        msg!(
+            "Validator {} is drain-only. Please stake into another validator",
+            validator.validator_account
+        );
+        return Ok(Err(StakeReserveStatus::ValidatorDrainOnly));
+    }
+
+    // the whole delta can not make a stake account of min stake:
+    // stop before consuming an extra run instead of creating a dust stake
+    let min_stake = state.stake_system.validator_min_stake(&validator);
+    if total_stake_delta < min_stake {
+        msg!(
+            "Stake delta {} is lower than min stake allowed {}",
+            total_stake_delta,
+            min_stake
+        );
+        return Ok(Err(StakeReserveStatus::BelowMinStake));
+    }
+
+    if state.in_stake_cooldown(&validator, clock.epoch) {
+        msg!(
+            "Validator {} is in stake cooldown since epoch {}",
+            validator.validator_account,
+            validator.last_stake_delta_epoch
+        );
+        return Ok(Err(StakeReserveStatus::ValidatorCooldown));
+    }
+
+    if validator.last_stake_delta_epoch == clock.epoch {
+        // check if we have some extra stake runs allowed.
+        // The run is used by the caller when it stakes
+        if state.stake_system.extra_stake_delta_runs == 0 {
+            msg!(
+                "Double delta stake command for validator {} in epoch {}",
+                validator.validator_account,
+                clock.epoch
+            );
+            return Ok(Err(StakeReserveStatus::DoubleStakeDelta));
+        }
+    }
+    let extra_stake_delta_run = validator.last_stake_delta_epoch == clock.epoch;
+
+    require_gte!(
+        clock.slot,
+        state.stake_system.stake_window_open_slot(last_slot),
+        MarinadeError::TooEarlyForStakeDelta
+    );
+
+    let mut validator_stake_target = state.ramped_stake_target(
+        &validator,
+        state.validator_stake_target(&validator, total_stake_target)?,
+        clock.epoch,
+    );
+
+    if sweep {
+        // operator override: the validator can take all the delta over its target
+        validator_stake_target = validator_active_balance
+            .checked_add(total_stake_delta)
+            .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+    }
+
+    //verify the validator is under-staked
+    if validator_active_balance >= validator_stake_target {
+        // bounded scan: a longer list is never detected all at target
+        let all_at_target = state.all_validators_at_target(
+            validator_list_data,
             total_stake_target,
+            0,
+            State::ALL_AT_TARGET_MAX_SCAN,
+        )?;
+        if all_at_target != Some(true) {
+            msg!(
+                "Validator {} has already reached stake target {}. Please stake into another validator",
+                validator.validator_account,
+                validator_stake_target
+            );
+            if all_at_target.is_none() {
+                msg!(
+                    "Only the first {} validators are scanned for all at target",
+                    State::ALL_AT_TARGET_MAX_SCAN
+                );
+            }
+            return Ok(Err(StakeReserveStatus::ValidatorOnTarget));
+        }
+        msg!(
+            "All validators reached stake target but stake delta {} remains",
+            total_stake_delta
+        );
+        if !state.stake_over_target {
+            // the surplus stays in the reserve
+            return Ok(Err(StakeReserveStatus::AllValidatorsAtTarget));
+        }
+        // controlled overshoot: the validator can get its share of the remaining delta over the target
+        validator_stake_target = validator_active_balance
+            .checked_add(
+                state
+                    .validator_system
+                    .validator_surplus_share(&validator, total_stake_delta)?,
+            )
+            .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+    }
+
+    let max_stake_room = validator.max_stake_room();
+    if max_stake_room == 0 {
+        msg!(
+            "Validator {} has already reached max stake {}",
+            validator.validator_account,
+            validator.max_stake
+        );
+        return Ok(Err(StakeReserveStatus::ValidatorOnTarget));
+    }
+
+    // compute stake_target
+    let mut stake_target = state
+        .stake_reserve_target(
             validator_stake_target,
-            reserve_balance,
-            total_active_balance,
             validator_active_balance,
             total_stake_delta,
-        });
-        Ok(())
+            clock,
+            last_slot,
+        )
+        .min(max_stake_room)
+        .min(state.stake_per_call_cap());
+    if let Some(requested_amount) = requested_amount {
+        // before the min stake check and the split into stake accounts
+        let clamped = requested_amount.max(min_stake).min(stake_target);
+        msg!("Requested {} clamped to {}", requested_amount, clamped);
+        stake_target = clamped;
     }
 
-    pub fn return_unused_stake_account_rent(&self) -> Result<()> {
-        // Return back the rent reserve of unused stake account in case of early return
-        withdraw(
-            CpiContext::new(
-                self.stake_program.to_account_info(),
-                Withdraw {
-                    stake: self.stake_account.to_account_info(),
-                    withdrawer: self.stake_account.to_account_info(),
-                    to: self.rent_payer.to_account_info(),
-                    clock: self.clock.to_account_info(),
-                    stake_history: self.stake_history.to_account_info(),
+    // if the amount to stake is < min_stake (e.g. less than 1 SOL)
+    // we don't stake to avoid creating a stake account with less than 1 SOL
+    if stake_target < min_stake {
+        msg!(
+            "Resulting stake {} is lower than min stake allowed {}",
+            stake_target,
+            min_stake
+        );
+        return Ok(Err(StakeReserveStatus::BelowMinStake));
+    }
+    state.check_stake_accounts_limit(&validator, 1)?;
+    // a split stake takes an operation per stake account
+    let chunks = state.stake_chunks(stake_target, min_stake);
+    if state.stake_operations_throttled(clock.epoch, chunks) {
+        msg!(
+            "Throttled: {} stakes in epoch {}, the stake needs {} more",
+            state.stake_operations_in_epoch(clock.epoch),
+            clock.epoch,
+            chunks
+        );
+        return Ok(Err(StakeReserveStatus::Throttled));
+    }
+
+    Ok(Ok(StakeReservePlan {
+        validator,
+        total_stake_delta,
+        total_stake_target,
+        validator_stake_target,
+        stake_target,
+        extra_stake_delta_run,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::*;
+    use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+
+    use super::{
+        check_stake_reserve_crank, plan_stake_reserve, select_validator_for_stake, selection_rank,
+        StakeReserveParams, StakeReservePlan, StakeReserveResult,
+    };
+    use crate::instructions::crank::stake_reserve_multi::one_stake_account_plan;
+    use crate::{
+        calc::u64_from_i128,
+        error::MarinadeError,
+        state::{
+            list::List,
+            stake_system::{StakeReserveStatus, StakeSystem},
+            validator_system::{ValidatorRecord, ValidatorSystem},
+            Fee,
+        },
+        State,
+    };
+
+    const RENT_EXEMPT_FOR_TOKEN_ACC: u64 = 2_039_280;
+    const VALIDATOR_COUNT: u32 = 2;
+
+    /// In-memory state, validator list and clock for plan_stake_reserve,
+    /// the sizing and no-op decisions of stake_reserve (the CPIs need the runtime)
+    struct StakeReserveFixture {
+        state: State,
+        validator_votes: Vec<Pubkey>,
+        validator_list_data: Vec<u8>,
+        reserve_lamports: u64,
+        clock: Clock,
+        last_slot: u64,
+    }
+
+    impl StakeReserveFixture {
+        /// VALIDATOR_COUNT validators of the same score, 100 SOL of stake delta,
+        /// the clock in the stake-delta window
+        fn new() -> Self {
+            let state_address = Pubkey::new_unique();
+            let mut stake_list_data = [0; 8];
+            let mut validator_list_data = vec![
+                0;
+                List::bytes_for(ValidatorRecord::serialized_len(), VALIDATOR_COUNT)
+                    as usize
+            ];
+            let mut validator_system = ValidatorSystem::new(
+                Pubkey::new_unique(),
+                &mut validator_list_data,
+                Pubkey::new_unique(),
+                0,
+            )
+            .unwrap();
+            let validator_votes: Vec<Pubkey> =
+                (0..VALIDATOR_COUNT).map(|_| Pubkey::new_unique()).collect();
+            for vote in &validator_votes {
+                let (duplication_flag, _) =
+                    ValidatorRecord::find_duplication_flag(&state_address, vote);
+                validator_system
+                    .add(
+                        &mut validator_list_data,
+                        *vote,
+                        100,
+                        &state_address,
+                        &duplication_flag,
+                        0,
+                    )
+                    .unwrap();
+            }
+            let mut state = State::for_tests(
+                StakeSystem::new(
+                    &state_address,
+                    Pubkey::new_unique(),
+                    &mut stake_list_data,
+                    StakeSystem::MIN_UPDATE_WINDOW,
+                    LAMPORTS_PER_SOL,
+                    0,
+                    0,
+                )
+                .unwrap(),
+                validator_system,
+            );
+            state.rent_exempt_for_token_acc = RENT_EXEMPT_FOR_TOKEN_ACC;
+            let last_slot = 100_000;
+            Self {
+                state,
+                validator_votes,
+                validator_list_data,
+                reserve_lamports: RENT_EXEMPT_FOR_TOKEN_ACC + 100 * LAMPORTS_PER_SOL,
+                clock: Clock {
+                    slot: last_slot - 1_000,
+                    epoch: 10,
+                    ..Clock::default()
                 },
-            ),
-            self.stake_account.to_account_info().lamports(),
-            None,
+                last_slot,
+            }
+        }
+
+        fn reserve_lamports(mut self, lamports: u64) -> Self {
+            self.reserve_lamports = lamports;
+            self
+        }
+
+        fn validator_active_balance(mut self, index: u32, active_balance: u64) -> Self {
+            let mut validator = self
+                .state
+                .validator_system
+                .get(&self.validator_list_data, index)
+                .unwrap();
+            self.state.validator_system.total_active_balance =
+                self.state.validator_system.total_active_balance - validator.active_balance
+                    + active_balance;
+            validator.active_balance = active_balance;
+            self.set_validator(index, validator)
+        }
+
+        fn validator_last_stake_delta_epoch(self, index: u32, epoch: u64) -> Self {
+            let mut validator = self
+                .state
+                .validator_system
+                .get(&self.validator_list_data, index)
+                .unwrap();
+            validator.last_stake_delta_epoch = epoch;
+            self.set_validator(index, validator)
+        }
+
+        fn set_validator(mut self, index: u32, validator: ValidatorRecord) -> Self {
+            self.state
+                .validator_system
+                .set(&mut self.validator_list_data, index, validator)
+                .unwrap();
+            self
+        }
+
+        fn min_stake(mut self, min_stake: u64) -> Self {
+            self.state.stake_system.min_stake = min_stake;
+            self
+        }
+
+        fn slots_for_stake_delta(mut self, slots_for_stake_delta: u64) -> Self {
+            self.state.stake_system.slots_for_stake_delta = slots_for_stake_delta;
+            self
+        }
+
+        fn clock(mut self, slot: u64, epoch: u64) -> Self {
+            self.clock.slot = slot;
+            self.clock.epoch = epoch;
+            self
+        }
+
+        fn plan(
+            &mut self,
+            index: u32,
+        ) -> Result<std::result::Result<StakeReservePlan, StakeReserveStatus>> {
+            self.plan_with(index, false)
+        }
+
+        fn plan_with(
+            &mut self,
+            index: u32,
+            sweep: bool,
+        ) -> Result<std::result::Result<StakeReservePlan, StakeReserveStatus>> {
+            self.plan_requested(index, sweep, None)
+        }
+
+        fn plan_requested(
+            &mut self,
+            index: u32,
+            sweep: bool,
+            requested_amount: Option<u64>,
+        ) -> Result<std::result::Result<StakeReservePlan, StakeReserveStatus>> {
+            plan_stake_reserve(
+                &self.state,
+                &self.validator_list_data,
+                index,
+                &self.validator_votes[index as usize],
+                self.reserve_lamports,
+                &self.clock,
+                self.last_slot,
+                sweep,
+                requested_amount,
+            )
+        }
+
+        fn plan_status(&mut self, index: u32) -> StakeReserveStatus {
+            match self.plan(index).unwrap() {
+                Ok(_) => StakeReserveStatus::Staked,
+                Err(status) => status,
+            }
+        }
+
+        fn select(&self, start_index: u32) -> Option<u32> {
+            select_validator_for_stake(
+                &self.state,
+                &self.validator_list_data,
+                self.state
+                    .total_stake_target(self.reserve_lamports)
+                    .unwrap(),
+                self.clock.epoch,
+                start_index,
+                u32::MAX,
+            )
+            .unwrap()
+        }
+    }
+
+    #[test]
+    fn test_stake_reserve_stakes_validator_share() {
+        let mut fixture = StakeReserveFixture::new();
+        let plan = fixture.plan(0).unwrap().unwrap();
+        assert_eq!(plan.total_stake_delta, 100 * LAMPORTS_PER_SOL);
+        // the same score: half of the delta, the remainder is left for the other validator
+        assert_eq!(plan.validator_stake_target, 50 * LAMPORTS_PER_SOL);
+        assert_eq!(plan.stake_target, 50 * LAMPORTS_PER_SOL);
+        assert_eq!(plan.validator.validator_account, fixture.validator_votes[0]);
+    }
+
+    #[test]
+    fn test_stake_reserve_nothing_to_stake() {
+        let mut fixture = StakeReserveFixture::new().reserve_lamports(RENT_EXEMPT_FOR_TOKEN_ACC);
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::NothingToStake);
+    }
+
+    #[test]
+    fn test_stake_reserve_double_stake_delta() {
+        let mut fixture = StakeReserveFixture::new().validator_last_stake_delta_epoch(0, 10);
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::DoubleStakeDelta);
+        // the extra run is used only by the stake, not by the plan
+        fixture.state.stake_system.extra_stake_delta_runs = 1;
+        assert!(fixture.plan(0).unwrap().unwrap().extra_stake_delta_run);
+        assert_eq!(fixture.state.stake_system.extra_stake_delta_runs, 1);
+        assert!(!fixture.plan(1).unwrap().unwrap().extra_stake_delta_run);
+        // a no-op keeps the run
+        let mut fixture = fixture.validator_active_balance(0, 1_000 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            fixture.plan_status(0),
+            StakeReserveStatus::ValidatorOnTarget
+        );
+        assert_eq!(fixture.state.stake_system.extra_stake_delta_runs, 1);
+        // the previous epoch stake does not block
+        let mut fixture = StakeReserveFixture::new().validator_last_stake_delta_epoch(0, 9);
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::Staked);
+    }
+
+    #[test]
+    fn test_stake_reserve_validator_on_target() {
+        let mut fixture =
+            StakeReserveFixture::new().validator_active_balance(0, 1_000 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            fixture.plan_status(0),
+            StakeReserveStatus::ValidatorOnTarget
+        );
+        assert_eq!(fixture.plan_status(1), StakeReserveStatus::Staked);
+    }
+
+    #[test]
+    fn test_stake_reserve_drain_only() {
+        let fixture = StakeReserveFixture::new();
+        let mut validator = fixture
+            .state
+            .validator_system
+            .get(&fixture.validator_list_data, 0)
+            .unwrap();
+        validator.drain_only = true;
+        let mut fixture = fixture.set_validator(0, validator);
+        assert_eq!(
+            fixture.plan_status(0),
+            StakeReserveStatus::ValidatorDrainOnly
+        );
+        assert_eq!(fixture.select(0), Some(1));
+        assert_eq!(fixture.plan_status(1), StakeReserveStatus::Staked);
+    }
+
+    #[test]
+    fn test_stake_reserve_delta_below_action_threshold() {
+        let mut fixture = StakeReserveFixture::new();
+        let stake_delta = fixture.state.stake_reserve_delta(fixture.reserve_lamports) as u64;
+        fixture.state.min_stake_delta_to_act = stake_delta + 1;
+        assert_eq!(
+            fixture.plan_status(0),
+            StakeReserveStatus::DeltaBelowActionThreshold
+        );
+        fixture.state.min_stake_delta_to_act = stake_delta;
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::Staked);
+    }
+
+    #[test]
+    fn test_stake_reserve_new_validator_ramp() {
+        let fixture = StakeReserveFixture::new();
+        let mut validator = fixture
+            .state
+            .validator_system
+            .get(&fixture.validator_list_data, 0)
+            .unwrap();
+        validator.added_epoch = 10;
+        let mut fixture = fixture.set_validator(0, validator);
+        // 100% = no ramp
+        assert_eq!(
+            fixture.plan(0).unwrap().unwrap().validator_stake_target,
+            50 * LAMPORTS_PER_SOL
+        );
+        fixture.state.new_validator_ramp = Fee::from_basis_points(2000);
+        let plan = fixture.plan(0).unwrap().unwrap();
+        assert_eq!(plan.validator_stake_target, 10 * LAMPORTS_PER_SOL);
+        assert_eq!(plan.stake_target, 10 * LAMPORTS_PER_SOL);
+        // the other validator is added earlier
+        assert_eq!(
+            fixture.plan(1).unwrap().unwrap().validator_stake_target,
+            50 * LAMPORTS_PER_SOL
+        );
+        // the next epoch the full target
+        let mut fixture = fixture.clock(99_000, 11);
+        assert_eq!(
+            fixture.plan(0).unwrap().unwrap().validator_stake_target,
+            50 * LAMPORTS_PER_SOL
+        );
+    }
+
+    #[test]
+    fn test_stake_reserve_sweep() {
+        // on target: the sweep takes the whole delta over the target
+        let mut fixture =
+            StakeReserveFixture::new().validator_active_balance(0, 1_000 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            fixture.plan_status(0),
+            StakeReserveStatus::ValidatorOnTarget
+        );
+        let plan = fixture.plan_with(0, true).unwrap().unwrap();
+        assert_eq!(plan.stake_target, 100 * LAMPORTS_PER_SOL);
+        // still capped by the validator max stake
+        let mut validator = fixture
+            .state
+            .validator_system
+            .get(&fixture.validator_list_data, 0)
+            .unwrap();
+        validator.max_stake = 1_030 * LAMPORTS_PER_SOL;
+        let mut fixture = fixture.set_validator(0, validator);
+        let plan = fixture.plan_with(0, true).unwrap().unwrap();
+        assert_eq!(plan.stake_target, 30 * LAMPORTS_PER_SOL);
+        // and by the global cap
+        fixture.state.max_total_active_balance =
+            fixture.state.validator_system.total_active_balance + 20 * LAMPORTS_PER_SOL;
+        let plan = fixture.plan_with(0, true).unwrap().unwrap();
+        assert_eq!(plan.stake_target, 20 * LAMPORTS_PER_SOL);
+    }
+
+    #[test]
+    fn test_stake_reserve_requested_amount() {
+        // the computed 50 SOL split into stake accounts of 10 SOL
+        let mut fixture = StakeReserveFixture::new();
+        fixture.state.max_stake_per_account = 10 * LAMPORTS_PER_SOL;
+        let chunks = |fixture: &StakeReserveFixture, plan: &StakeReservePlan| {
+            fixture
+                .state
+                .stake_chunks(plan.stake_target, fixture.state.stake_system.min_stake)
+        };
+        let plan = fixture
+            .plan_requested(0, false, Some(30 * LAMPORTS_PER_SOL))
+            .unwrap()
+            .unwrap();
+        assert_eq!(plan.stake_target, 30 * LAMPORTS_PER_SOL);
+        assert_eq!(chunks(&fixture, &plan), 3);
+        // capped by the computed amount
+        let plan = fixture
+            .plan_requested(0, false, Some(1_000 * LAMPORTS_PER_SOL))
+            .unwrap()
+            .unwrap();
+        assert_eq!(plan.stake_target, 50 * LAMPORTS_PER_SOL);
+        assert_eq!(chunks(&fixture, &plan), 5);
+        // raised to min stake
+        let plan = fixture
+            .plan_requested(0, false, Some(LAMPORTS_PER_SOL / 2))
+            .unwrap()
+            .unwrap();
+        assert_eq!(plan.stake_target, LAMPORTS_PER_SOL);
+        assert_eq!(chunks(&fixture, &plan), 1);
+        // the throttle counts the stake accounts of the requested amount
+        fixture.state.max_stake_operations_per_epoch = 3;
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::Throttled);
+        let plan = fixture
+            .plan_requested(0, false, Some(30 * LAMPORTS_PER_SOL))
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunks(&fixture, &plan), 3);
+    }
+
+    #[test]
+    fn test_stake_reserve_multi_one_stake_account() {
+        let mut fixture = StakeReserveFixture::new();
+        let plan = fixture.plan(0).unwrap();
+        // no split: unchanged
+        let stake_target = one_stake_account_plan(&fixture.state, plan)
+            .unwrap()
+            .stake_target;
+        assert_eq!(stake_target, 50 * LAMPORTS_PER_SOL);
+        // stake_reserve would split it, stake_reserve_multi stakes one chunk
+        fixture.state.max_stake_per_account = 20 * LAMPORTS_PER_SOL;
+        let plan = fixture.plan(0).unwrap();
+        let plan = one_stake_account_plan(&fixture.state, plan).unwrap();
+        assert_eq!(plan.stake_target, 20 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            fixture
+                .state
+                .stake_chunks(plan.stake_target, fixture.state.stake_system.min_stake),
+            1
+        );
+        // the clamp below min stake is a no-op
+        fixture.state.max_stake_per_account = LAMPORTS_PER_SOL / 2;
+        let plan = fixture.plan(0).unwrap();
+        assert_eq!(
+            one_stake_account_plan(&fixture.state, plan).err(),
+            Some(StakeReserveStatus::BelowMinStake)
+        );
+        // the no-ops pass through
+        let mut fixture = fixture.validator_last_stake_delta_epoch(0, 10);
+        let plan = fixture.plan(0).unwrap();
+        assert_eq!(
+            one_stake_account_plan(&fixture.state, plan).err(),
+            Some(StakeReserveStatus::DoubleStakeDelta)
+        );
+    }
+
+    #[test]
+    fn test_stake_reserve_params_old_layouts() {
+        let params = StakeReserveParams {
+            validator_index: 7,
+            fallback_validator_index: Some(3),
+            dry_run: true,
+            strict: true,
+            requested_amount: Some(LAMPORTS_PER_SOL),
+        };
+        let data = params.try_to_vec().unwrap();
+        assert_eq!(StakeReserveParams::try_from_slice(&data).unwrap(), params);
+        // the bare validator_index of stake_reserve
+        assert_eq!(
+            StakeReserveParams::try_from_slice(&7u32.to_le_bytes()).unwrap(),
+            StakeReserveParams::from(7)
+        );
+        // the layout before requested_amount
+        let mut data = 7u32.try_to_vec().unwrap();
+        data.extend(Some(3u32).try_to_vec().unwrap());
+        data.extend([1, 1]);
+        assert_eq!(
+            StakeReserveParams::try_from_slice(&data).unwrap(),
+            StakeReserveParams {
+                requested_amount: None,
+                ..params
+            }
+        );
+        // a field can not be cut
+        assert!(StakeReserveParams::try_from_slice(&data[..6]).is_err());
+        assert!(StakeReserveParams::try_from_slice(&[7]).is_err());
+    }
+
+    #[test]
+    fn test_stake_reserve_throttled_split() {
+        let mut fixture = StakeReserveFixture::new();
+        fixture.state.max_stake_operations_per_epoch = 2;
+        fixture.state.on_stake_operations(10, 1);
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::Staked);
+        // 50 SOL in 3 stake accounts exceed the operations left
+        fixture.state.max_stake_per_account = 20 * LAMPORTS_PER_SOL;
+        fixture.state.max_stake_operations_per_epoch = 3;
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::Throttled);
+        // the previous epoch operations do not count
+        let mut fixture = fixture.clock(99_000, 11);
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::Staked);
+    }
+
+    #[test]
+    fn test_stake_reserve_below_min_stake() {
+        let mut fixture = StakeReserveFixture::new().min_stake(200 * LAMPORTS_PER_SOL);
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::BelowMinStake);
+    }
+
+    #[test]
+    fn test_stake_reserve_stake_delta_overflow() {
+        assert_eq!(u64_from_i128(u64::MAX as i128).unwrap(), u64::MAX);
+        assert!(u64_from_i128(u64::MAX as i128 + 1).is_err());
+        assert!(u64_from_i128(-1).is_err());
+        // the positive stake delta does not fit in u64: an error instead of the panic
+        let mut fixture = StakeReserveFixture::new().reserve_lamports(u64::MAX);
+        fixture.state.stake_system.delayed_unstake_cooling_down = u64::MAX;
+        assert!(fixture.state.stake_reserve_delta(u64::MAX) > u64::MAX as i128);
+        assert_eq!(
+            fixture.plan(0).err(),
+            Some(error!(MarinadeError::CalculationFailure))
+        );
+        assert!(fixture.state.total_stake_target(u64::MAX).is_err());
+        assert!(StakeReserveResult::no_op(
+            &fixture.state,
+            u64::MAX,
+            StakeReserveStatus::NothingToStake
         )
+        .is_err());
+        // the lowest negative stake delta fits
+        fixture.state.stake_system.delayed_unstake_cooling_down = 0;
+        fixture.state.circulating_ticket_balance = u64::MAX;
+        let no_op =
+            StakeReserveResult::no_op(&fixture.state, 0, StakeReserveStatus::NothingToStake)
+                .unwrap();
+        assert_eq!(no_op.unstake_delta, u64::MAX);
+    }
+
+    #[test]
+    fn test_stake_reserve_reserve_floor() {
+        let mut fixture = StakeReserveFixture::new();
+        fixture.state.reserve_retention_lamports = LAMPORTS_PER_SOL;
+        let rent = Rent::default();
+        let floor = rent.minimum_balance(0) + LAMPORTS_PER_SOL;
+        assert_eq!(fixture.state.reserve_floor(&rent), floor);
+        let reserve_balance = floor + 10 * LAMPORTS_PER_SOL;
+        // drains the reserve exactly to the floor
+        assert!(fixture
+            .state
+            .check_reserve_transfer(reserve_balance, reserve_balance - floor, &rent)
+            .is_ok());
+        assert_eq!(
+            fixture
+                .state
+                .check_reserve_transfer(reserve_balance, reserve_balance - floor + 1, &rent)
+                .err(),
+            Some(error!(MarinadeError::ReserveWouldNotBeRentExempt))
+        );
+        assert!(fixture
+            .state
+            .check_reserve_transfer(reserve_balance, reserve_balance + 1, &rent)
+            .is_err());
+    }
+
+    #[test]
+    fn test_stake_reserve_max_stake_per_call() {
+        let mut fixture =
+            StakeReserveFixture::new().validator_active_balance(1, 50 * LAMPORTS_PER_SOL);
+        // without the cap the validator share takes the remainder below min stake
+        fixture.state.stake_system.min_stake = 60 * LAMPORTS_PER_SOL;
+        assert_eq!(
+            fixture.plan(0).unwrap().unwrap().stake_target,
+            100 * LAMPORTS_PER_SOL
+        );
+        fixture.state.max_stake_per_call = 70 * LAMPORTS_PER_SOL;
+        let plan = fixture.plan(0).unwrap().unwrap();
+        assert_eq!(plan.stake_target, 70 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            plan.total_stake_delta - plan.stake_target,
+            30 * LAMPORTS_PER_SOL
+        );
+    }
+
+    #[test]
+    fn test_stake_reserve_all_validators_at_target() {
+        // half of the target is allocated to a pool without validators
+        let mut fixture = StakeReserveFixture::new()
+            .validator_active_balance(0, 100 * LAMPORTS_PER_SOL)
+            .validator_active_balance(1, 100 * LAMPORTS_PER_SOL);
+        fixture.state.pool_allocations[0] = Fee::from_basis_points(5000);
+        fixture.state.pool_allocations[1] = Fee::from_basis_points(5000);
+        assert_eq!(
+            fixture.plan_status(0),
+            StakeReserveStatus::AllValidatorsAtTarget
+        );
+        // the overshoot takes the score share of the surplus
+        fixture.state.stake_over_target = true;
+        let plan = fixture.plan(0).unwrap().unwrap();
+        assert_eq!(plan.stake_target, 50 * LAMPORTS_PER_SOL);
+        assert_eq!(plan.validator_stake_target, 150 * LAMPORTS_PER_SOL);
+        // the bounded scan
+        let total_stake_target = plan.total_stake_target;
+        let at_target = |start_index, max_scan| {
+            fixture
+                .state
+                .all_validators_at_target(
+                    &fixture.validator_list_data,
+                    total_stake_target,
+                    start_index,
+                    max_scan,
+                )
+                .unwrap()
+        };
+        assert_eq!(at_target(0, 2), Some(true));
+        assert_eq!(at_target(0, 1), None);
+        assert_eq!(at_target(1, 5), None);
+        let fixture = fixture.validator_active_balance(1, 0);
+        assert_eq!(
+            fixture
+                .state
+                .all_validators_at_target(&fixture.validator_list_data, total_stake_target, 0, 2)
+                .unwrap(),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_stake_reserve_max_total_active_balance() {
+        let mut fixture =
+            StakeReserveFixture::new().validator_active_balance(1, 100 * LAMPORTS_PER_SOL);
+        // the stake lands exactly on the cap
+        fixture.state.max_total_active_balance = 130 * LAMPORTS_PER_SOL;
+        let plan = fixture.plan(0).unwrap().unwrap();
+        assert_eq!(plan.stake_target, 30 * LAMPORTS_PER_SOL);
+        // the room below min stake
+        fixture.state.max_total_active_balance = 100 * LAMPORTS_PER_SOL + LAMPORTS_PER_SOL / 2;
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::BelowMinStake);
+        fixture.state.max_total_active_balance = 100 * LAMPORTS_PER_SOL;
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::GlobalCapReached);
+    }
+
+    #[test]
+    fn test_select_validator_for_stake() {
+        // equal gaps: the lowest index
+        let fixture = StakeReserveFixture::new();
+        assert_eq!(fixture.select(0), Some(0));
+        assert_eq!(fixture.select(1), Some(1));
+        // the largest gap
+        let fixture = StakeReserveFixture::new().validator_active_balance(0, LAMPORTS_PER_SOL);
+        assert_eq!(fixture.select(0), Some(1));
+        // zero score validators are excluded
+        let mut fixture = StakeReserveFixture::new();
+        let mut validator = fixture
+            .state
+            .validator_system
+            .get(&fixture.validator_list_data, 0)
+            .unwrap();
+        fixture.state.validator_system.total_validator_score -= validator.score;
+        validator.score = 0;
+        let fixture = fixture.set_validator(0, validator);
+        assert_eq!(fixture.select(0), Some(1));
+        // all at target
+        let mut fixture = StakeReserveFixture::new()
+            .validator_active_balance(0, 100 * LAMPORTS_PER_SOL)
+            .validator_active_balance(1, 100 * LAMPORTS_PER_SOL);
+        fixture.state.pool_allocations[0] = Fee::from_basis_points(5000);
+        fixture.state.pool_allocations[1] = Fee::from_basis_points(5000);
+        assert_eq!(fixture.select(0), None);
+    }
+
+    #[test]
+    fn test_stake_reserve_crank_before_no_op() {
+        // all at target: stake_reserve_greedy and stake_reserve_preferred would record a no-op
+        let mut fixture = StakeReserveFixture::new()
+            .validator_active_balance(0, 100 * LAMPORTS_PER_SOL)
+            .validator_active_balance(1, 100 * LAMPORTS_PER_SOL);
+        fixture.state.pool_allocations[0] = Fee::from_basis_points(5000);
+        fixture.state.pool_allocations[1] = Fee::from_basis_points(5000);
+        assert_eq!(fixture.select(0), None);
+        let signer = Pubkey::new_unique();
+        let validator_list_len = fixture.validator_list_data.len();
+        let check = |state: &State, validator_list_len| {
+            check_stake_reserve_crank(state, &[&signer], validator_list_len, 8).err()
+        };
+        assert_eq!(check(&fixture.state, validator_list_len), None);
+        fixture.state.crank_authorities[0] = Pubkey::new_unique();
+        fixture.state.crank_authority_count = 1;
+        assert_eq!(
+            check(&fixture.state, validator_list_len),
+            Some(error!(MarinadeError::UnauthorizedCrank))
+        );
+        fixture.state.crank_authority_count = 0;
+        assert_eq!(
+            check(&fixture.state, validator_list_len - 1),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+    }
+
+    #[test]
+    fn test_select_validator_for_stake_seed() {
+        let mut fixture = StakeReserveFixture::new();
+        fixture.state.selection_seed = 42;
+        let mut winners = [0; VALIDATOR_COUNT as usize];
+        for epoch in 0..16 {
+            fixture.clock.epoch = epoch;
+            let selected = fixture.select(0).unwrap();
+            // deterministic: the lowest rank of the equal gaps
+            assert_eq!(fixture.select(0), Some(selected));
+            let other = 1 - selected;
+            assert!(selection_rank(42, epoch, selected) < selection_rank(42, epoch, other));
+            winners[selected as usize] += 1;
+        }
+        // the ties rotate between the epochs
+        assert!(winners.iter().all(|&wins| wins > 0));
+        // the larger gap still wins
+        let mut fixture = fixture.validator_active_balance(0, LAMPORTS_PER_SOL);
+        for epoch in 0..16 {
+            fixture.clock.epoch = epoch;
+            assert_eq!(fixture.select(0), Some(1));
+        }
+        assert_eq!(selection_rank(0, 7, 3), 3);
+    }
+
+    #[test]
+    fn test_find_validator_by_vote() {
+        let fixture = StakeReserveFixture::new();
+        let validator_system = &fixture.state.validator_system;
+        let data = &fixture.validator_list_data;
+        let vote = fixture.validator_votes[1];
+        assert_eq!(
+            validator_system
+                .find_by_vote(data, &vote, 0, u32::MAX)
+                .unwrap(),
+            Some(1)
+        );
+        // out of the scanned range
+        assert_eq!(
+            validator_system.find_by_vote(data, &vote, 0, 1).unwrap(),
+            None
+        );
+        assert_eq!(
+            validator_system
+                .find_by_vote(data, &Pubkey::new_unique(), 0, u32::MAX)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_stake_reserve_too_early() {
+        let mut fixture = StakeReserveFixture::new()
+            .slots_for_stake_delta(StakeSystem::MIN_UPDATE_WINDOW)
+            .clock(1_000, 10);
+        assert_eq!(
+            fixture.plan(0).err(),
+            Some(error!(MarinadeError::TooEarlyForStakeDelta))
+        );
+    }
+
+    #[test]
+    fn test_stake_reserve_window_open_slot() {
+        // stake_window_status reports the exact first accepted slot
+        let fixture =
+            StakeReserveFixture::new().slots_for_stake_delta(StakeSystem::MIN_UPDATE_WINDOW);
+        let open_slot = fixture
+            .state
+            .stake_system
+            .stake_window_open_slot(fixture.last_slot);
+        let mut fixture = fixture.clock(open_slot - 1, 10);
+        assert_eq!(
+            fixture.plan(0).err(),
+            Some(error!(MarinadeError::TooEarlyForStakeDelta))
+        );
+        let mut fixture = fixture.clock(open_slot, 10);
+        assert_eq!(fixture.plan_status(0), StakeReserveStatus::Staked);
     }
 }
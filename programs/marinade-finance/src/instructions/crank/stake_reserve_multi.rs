@@ -0,0 +1,219 @@
+use crate::{
+    error::MarinadeError,
+    events::crank::StakeReserveSkippedEvent,
+    state::{
+        stake_system::{StakeList, StakeReserveStatus, StakeSystem},
+        validator_system::ValidatorList,
+    },
+    State,
+};
+use anchor_lang::prelude::*;
+use anchor_lang::{
+    solana_program::{stake, sysvar::stake_history},
+    system_program,
+};
+use anchor_spl::stake::Stake;
+
+use super::{
+    check_vote_activity, plan_stake_reserve, StakeReserveAccounts, StakeReservePlan,
+    StakeReserveResult,
+};
+
+#[derive(Accounts)]
+pub struct StakeReserveMulti<'info> {
+    #[account(
+        mut,
+        // the first constraint: Anchor checks it after deserializing the accounts, before the address and PDA checks
+        constraint = !state.is_paused_for(State::PAUSE_STAKING) @ MarinadeError::StakingPaused,
+    )]
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        address = state.validator_system.validator_list.account,
+    )]
+    pub validator_list: Account<'info, ValidatorList>,
+    #[account(
+        mut,
+        address = state.stake_system.stake_list.account,
+    )]
+    pub stake_list: Account<'info, StakeList>,
+    #[account(
+        mut,
+        seeds = [
+            &state.key().to_bytes(),
+            State::RESERVE_SEED
+        ],
+        bump = state.reserve_bump_seed
+    )]
+    pub reserve_pda: SystemAccount<'info>,
+    /// CHECK: PDA
+    #[account(
+        seeds = [
+            &state.key().to_bytes(),
+            StakeSystem::STAKE_DEPOSIT_SEED
+        ],
+        bump = state.stake_system.stake_deposit_bump_seed
+    )]
+    pub stake_deposit_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        owner = system_program::ID
+    )]
+    pub rent_payer: Signer<'info>,
+    /// optional. Recorded in the stake records as the bot created the stakes.
+    /// With state.crank_authorities set, it or rent_payer must be one of them
+    pub cranker: Option<Signer<'info>>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub epoch_schedule: Sysvar<'info, EpochSchedule>,
+    pub rent: Sysvar<'info, Rent>,
+    /// CHECK: have no CPU budget to parse
+    #[account(address = stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+    /// CHECK: CPI
+    #[account(address = stake::config::ID)]
+    pub stake_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub stake_program: Program<'info, Stake>,
+}
+
+impl<'info> StakeReserveMulti<'info> {
+    /// called by the bot
+    /// stake_reserve for each of validator_indices in one transaction.
+    /// `accounts` are the pairs [validator_vote, stake_account] for each index,
+    /// stake_account is a new keypair, the account is created only when staking into the validator.
+    /// One stake account per validator: the stake is clamped by max_stake_per_account,
+    /// the rest of the validator gap is left for the next calls.
+    /// Returns the result of every processed index. Stops when the stake delta is exhausted
+    pub fn process(
+        &mut self,
+        validator_indices: Vec<u32>,
+        accounts: &[AccountInfo<'info>],
+    ) -> Result<Vec<StakeReserveResult>> {
+        require!(!self.state.paused, MarinadeError::ProgramIsPaused);
+        let stake_reserve_accounts = self.stake_reserve_accounts();
+        stake_reserve_accounts.check_crank(&self.state)?;
+        require_eq!(
+            accounts.len(),
+            validator_indices.len() * 2,
+            MarinadeError::UnexpectedAccount
+        );
+        for validator_index in &validator_indices {
+            self.state
+                .validator_system
+                .check_validator_index(*validator_index)?;
+        }
+        for validator_accounts in accounts.chunks(2) {
+            stake_reserve_accounts
+                .check_distinct_stake_account(validator_accounts[1].key, "stake_account")?;
+        }
+
+        let last_slot = self.epoch_schedule.get_last_slot_in_epoch(self.clock.epoch);
+        let mut results = Vec::with_capacity(validator_indices.len());
+        for (validator_index, validator_accounts) in
+            validator_indices.into_iter().zip(accounts.chunks(2))
+        {
+            let validator_vote = &validator_accounts[0];
+            let stake_account = &validator_accounts[1];
+
+            let reserve_balance = self.reserve_pda.lamports();
+            let mut plan = plan_stake_reserve(
+                &self.state,
+                &self.validator_list.to_account_info().data.as_ref().borrow(),
+                validator_index,
+                validator_vote.key,
+                reserve_balance,
+                &self.clock,
+                last_slot,
+                false,
+                None,
+            )?;
+            if plan.is_ok() {
+                if let Some(status) = check_vote_activity(
+                    &self.state,
+                    validator_vote,
+                    &self.clock,
+                    &self.epoch_schedule,
+                )? {
+                    plan = Err(status);
+                }
+            }
+            match one_stake_account_plan(&self.state, plan) {
+                Ok(plan) => results.push(stake_reserve_accounts.stake_planned(
+                    &mut self.state,
+                    &self.clock,
+                    validator_index,
+                    validator_vote,
+                    plan,
+                    None,
+                    std::slice::from_ref(stake_account),
+                    0,
+                    false,
+                )?),
+                Err(reason) => {
+                    self.state.on_stake_reserve_status(reason, &self.clock);
+                    emit!(StakeReserveSkippedEvent {
+                        state: self.state.key(),
+                        epoch: self.clock.epoch,
+                        validator_index,
+                        validator_vote: validator_vote.key(),
+                        reason,
+                    });
+                    results.push(StakeReserveResult::no_op(
+                        &self.state,
+                        reserve_balance,
+                        reason,
+                    )?);
+                    if reason == StakeReserveStatus::NothingToStake
+                        || reason == StakeReserveStatus::GlobalCapReached
+                        || reason == StakeReserveStatus::Throttled
+                    {
+                        break; // Not an error. The stake delta is exhausted
+                    }
+                    // Not an error. Try the next validator
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    fn stake_reserve_accounts(&self) -> StakeReserveAccounts<'info> {
+        StakeReserveAccounts::new(
+            &self.state,
+            &self.validator_list,
+            &self.stake_list,
+            &self.reserve_pda,
+            &self.stake_deposit_authority,
+            &self.rent_payer,
+            self.cranker.as_ref(),
+            &self.clock,
+            &self.rent,
+            &self.stake_history,
+            &self.stake_config,
+            &self.system_program,
+            &self.stake_program,
+        )
+    }
+}
+
+/// stake_reserve_multi has one stake account per validator:
+/// the stake is clamped by max_stake_per_account, a no-op when it gets lower than min stake
+pub(crate) fn one_stake_account_plan(
+    state: &State,
+    plan: std::result::Result<StakeReservePlan, StakeReserveStatus>,
+) -> std::result::Result<StakeReservePlan, StakeReserveStatus> {
+    let mut plan = plan?;
+    if state.max_stake_per_account != 0 && plan.stake_target > state.max_stake_per_account {
+        msg!(
+            "Stake {} clamped to max stake per account {}",
+            plan.stake_target,
+            state.max_stake_per_account
+        );
+        plan.stake_target = state.max_stake_per_account;
+        if plan.stake_target < state.stake_system.validator_min_stake(&plan.validator) {
+            return Err(StakeReserveStatus::BelowMinStake);
+        }
+    }
+    Ok(plan)
+}
@@ -0,0 +1,191 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::MarinadeError, events::crank::ApplyOracleScoresEvent, State};
+
+// the StakeReserve accounts are nested: Accounts needs its generated client modules in scope
+use super::stake_reserve::{
+    __client_accounts_stake_reserve, __cpi_client_accounts_stake_reserve, StakeReserve,
+    StakeReserveParams, StakeReserveResult,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct ValidatorScoreUpdate {
+    pub validator_index: u32,
+    pub score: u32,
+}
+
+#[derive(Accounts)]
+pub struct StakeReserveWithScores<'info> {
+    pub stake_reserve: StakeReserve<'info>,
+    #[account(
+        address = stake_reserve.state.score_oracle @ MarinadeError::InvalidScoreOracle
+    )]
+    pub score_oracle: Signer<'info>,
+}
+
+impl<'info> StakeReserveWithScores<'info> {
+    /// called by the bot with the scores signed by the oracle.
+    /// The scores are written to the validator list for stake_reserve
+    /// and restored in the reverse order after it (the same index can be repeated)
+    /// unless persist_scores
+    pub fn process(
+        &mut self,
+        params: StakeReserveParams,
+        scores: Vec<ValidatorScoreUpdate>,
+        persist_scores: bool,
+        extra_stake_accounts: &[AccountInfo<'info>],
+    ) -> Result<StakeReserveResult> {
+        let validator_list_info = self.stake_reserve.validator_list.to_account_info();
+        let old_scores = apply_scores(
+            &mut self.stake_reserve.state,
+            &mut validator_list_info.data.borrow_mut(),
+            &scores,
+        )?;
+        let result = self.stake_reserve.process(params, extra_stake_accounts)?;
+        if !persist_scores {
+            apply_scores(
+                &mut self.stake_reserve.state,
+                &mut validator_list_info.data.borrow_mut(),
+                &old_scores,
+            )?;
+        }
+        emit!(ApplyOracleScoresEvent {
+            state: self.stake_reserve.state.key(),
+            epoch: Clock::get()?.epoch,
+            scores,
+            persisted: persist_scores,
+        });
+        Ok(result)
+    }
+}
+
+/// sets the scores, returns the replaced scores in the reverse order
+pub(crate) fn apply_scores(
+    state: &mut State,
+    validator_list_data: &mut [u8],
+    scores: &[ValidatorScoreUpdate],
+) -> Result<Vec<ValidatorScoreUpdate>> {
+    let mut old_scores = Vec::with_capacity(scores.len());
+    for update in scores.iter() {
+        state
+            .validator_system
+            .check_validator_index(update.validator_index)?;
+        let mut validator = state
+            .validator_system
+            .get(validator_list_data, update.validator_index)?;
+        old_scores.push(ValidatorScoreUpdate {
+            validator_index: update.validator_index,
+            score: validator.score,
+        });
+        state.set_validator_score(&mut validator, update.score);
+        state
+            .validator_system
+            .set(validator_list_data, update.validator_index, validator)?;
+    }
+    old_scores.reverse();
+    Ok(old_scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::*;
+    use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+
+    use super::{apply_scores, ValidatorScoreUpdate};
+    use crate::{
+        error::MarinadeError,
+        state::{
+            list::List,
+            stake_system::StakeSystem,
+            validator_system::{ValidatorRecord, ValidatorSystem},
+        },
+        State,
+    };
+
+    fn update(validator_index: u32, score: u32) -> ValidatorScoreUpdate {
+        ValidatorScoreUpdate {
+            validator_index,
+            score,
+        }
+    }
+
+    fn scores(state: &State, validator_list_data: &[u8]) -> Vec<u32> {
+        (0..state.validator_system.validator_count())
+            .map(|index| {
+                state
+                    .validator_system
+                    .get(validator_list_data, index)
+                    .unwrap()
+                    .score
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_scores() {
+        let state_address = Pubkey::new_unique();
+        let mut stake_list_data = [0; 8];
+        let mut validator_list_data =
+            vec![0; List::bytes_for(ValidatorRecord::serialized_len(), 2) as usize];
+        let validator_system = ValidatorSystem::new(
+            Pubkey::new_unique(),
+            &mut validator_list_data,
+            Pubkey::new_unique(),
+            0,
+        )
+        .unwrap();
+        let mut state = State::for_tests(
+            StakeSystem::new(
+                &state_address,
+                Pubkey::new_unique(),
+                &mut stake_list_data,
+                StakeSystem::MIN_UPDATE_WINDOW,
+                LAMPORTS_PER_SOL,
+                0,
+                0,
+            )
+            .unwrap(),
+            validator_system,
+        );
+        for _ in 0..2 {
+            let vote = Pubkey::new_unique();
+            let (duplication_flag, _) =
+                ValidatorRecord::find_duplication_flag(&state_address, &vote);
+            state
+                .validator_system
+                .add(
+                    &mut validator_list_data,
+                    vote,
+                    100,
+                    &state_address,
+                    &duplication_flag,
+                    0,
+                )
+                .unwrap();
+            state.add_pool_score(0, 100);
+        }
+
+        // the same index repeated
+        let old_scores = apply_scores(
+            &mut state,
+            &mut validator_list_data,
+            &[update(0, 50), update(1, 0), update(0, 70)],
+        )
+        .unwrap();
+        assert_eq!(scores(&state, &validator_list_data), [70, 0]);
+        assert_eq!(state.validator_system.total_validator_score, 70);
+        assert_eq!(state.pool_scores[0], 70);
+        assert_eq!(old_scores, [update(0, 50), update(1, 100), update(0, 100)]);
+
+        // restored in the reverse order
+        apply_scores(&mut state, &mut validator_list_data, &old_scores).unwrap();
+        assert_eq!(scores(&state, &validator_list_data), [100, 100]);
+        assert_eq!(state.validator_system.total_validator_score, 200);
+        assert_eq!(state.pool_scores[0], 200);
+
+        assert_eq!(
+            apply_scores(&mut state, &mut validator_list_data, &[update(2, 10)]).err(),
+            Some(error!(MarinadeError::InvalidValidatorIndex))
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use crate::State;
+use anchor_lang::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct StakeWindowStatus {
+    pub epoch: u64,
+    pub last_slot_in_epoch: u64,
+    /// first slot stake_reserve accepts: last_slot_in_epoch - slots_for_stake_delta
+    pub window_open_slot: u64,
+    pub slot: u64,
+    pub is_open: bool,
+    /// 0 when open, keepers can sleep that long after TooEarlyForStakeDelta
+    pub slots_until_open: u64,
+}
+
+#[derive(Accounts)]
+pub struct GetStakeWindowStatus<'info> {
+    pub state: Box<Account<'info, State>>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub epoch_schedule: Sysvar<'info, EpochSchedule>,
+}
+
+impl<'info> GetStakeWindowStatus<'info> {
+    /// View for the bots: the stake-delta slot window of the current epoch,
+    /// stake_reserve fails with TooEarlyForStakeDelta before window_open_slot.
+    /// Anchor does not keep return data of a failed transaction,
+    /// so the keeper calls this view after the failure instead
+    pub fn process(&self) -> Result<StakeWindowStatus> {
+        let last_slot_in_epoch = self.epoch_schedule.get_last_slot_in_epoch(self.clock.epoch);
+        // the same computation as in stake_reserve
+        let window_open_slot = self
+            .state
+            .stake_system
+            .stake_window_open_slot(last_slot_in_epoch);
+        Ok(StakeWindowStatus {
+            epoch: self.clock.epoch,
+            last_slot_in_epoch,
+            window_open_slot,
+            slot: self.clock.slot,
+            is_open: self.clock.slot >= window_open_slot,
+            slots_until_open: window_open_slot.saturating_sub(self.clock.slot),
+        })
+    }
+}
@@ -0,0 +1,25 @@
+use crate::State;
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct TotalActiveBalanceGap<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        seeds = [
+            &state.key().to_bytes(),
+            State::RESERVE_SEED
+        ],
+        bump = state.reserve_bump_seed
+    )]
+    pub reserve_pda: SystemAccount<'info>,
+}
+
+impl<'info> TotalActiveBalanceGap<'info> {
+    /// View for monitoring: total_stake_target - total_active_balance.
+    /// Positive when there is SOL to stake, negative when there is SOL to unstake
+    pub fn process(&self) -> Result<i128> {
+        // total_stake_target = total_active_balance + stake_delta
+        // so the gap is exactly the current stake delta
+        Ok(self.state.stake_delta(self.reserve_pda.lamports()))
+    }
+}
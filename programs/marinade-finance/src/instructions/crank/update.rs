@@ -156,6 +156,7 @@ impl<'info> UpdateCommon<'info> {
             .reserve_pda
             .lamports()
             .saturating_sub(self.state.rent_exempt_for_token_acc);
+        self.state.on_reserve_balance_changed();
         // Update mSOL supply
         // impossible to happen check outside bug (msol mint auth is a PDA)
         if self.msol_mint.supply > self.state.msol_supply {
@@ -203,7 +204,8 @@ impl<'info> UpdateCommon<'info> {
                     ]],
                 ),
                 amount,
-                None,
+                // the custodian of the stake_reserve lockup
+                Some(self.stake_withdraw_authority.to_account_info()),
             )?;
             self.state.on_transfer_to_reserve(amount);
         }
@@ -310,6 +312,7 @@ impl<'info> UpdateActive<'info> {
             // by withdrawing to reserve, we add to the SOL assets under control,
             // and by that we increase the mSOL price
             self.withdraw_to_reserve(extra_lamports)?;
+            self.state.on_rewards_to_reserve(extra_lamports);
             // after sending to reserve, we take protocol_fees as minted mSOL
             if is_treasury_msol_ready_for_transfer {
                 Some(self.mint_protocol_fees(extra_lamports)?)
@@ -482,6 +485,10 @@ impl<'info> UpdateDeactivated<'info> {
         // withdraw all to reserve (the stake account will be marked for deletion by the system)
         self.common
             .withdraw_to_reserve(self.stake_account.to_account_info().lamports())?;
+        // the rewards part of it
+        self.state.on_rewards_to_reserve(
+            stake_balance_without_rent.saturating_sub(stake.last_update_delegated_lamports),
+        );
         // but send the rent-exempt lamports part to operational_sol_account for the future recreation of this slot's account
         transfer(
             CpiContext::new_with_signer(
@@ -498,7 +505,10 @@ impl<'info> UpdateDeactivated<'info> {
             ),
             rent,
         )?;
-        self.state.on_transfer_from_reserve(rent);
+        let state_address = self.state.key();
+        let epoch = self.clock.epoch;
+        self.state
+            .on_transfer_from_reserve(&state_address, rent, epoch)?;
 
         if stake.last_update_delegated_lamports != 0 {
             if stake.is_emergency_unstaking == 0 {
@@ -517,7 +527,7 @@ impl<'info> UpdateDeactivated<'info> {
         let msol_price_change = self.update_msol_price()?;
 
         //remove deleted stake-account from our list
-        self.common.state.stake_system.remove(
+        self.common.state.remove_stake(
             &mut self
                 .common
                 .stake_list
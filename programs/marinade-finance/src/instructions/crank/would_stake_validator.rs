@@ -0,0 +1,96 @@
+use crate::{
+    state::{stake_system::StakeReserveStatus, validator_system::ValidatorList},
+    State,
+};
+use anchor_lang::prelude::*;
+
+use super::{check_vote_activity, plan_stake_reserve, select_validator_for_stake};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct WouldStakeValidatorResult {
+    /// the greedy selection over the whole validator list picks the validator
+    pub selected: bool,
+    /// lamports stake_reserve would delegate to the validator. 0 when not selected
+    pub amount: u64,
+    /// why nothing would be staked into the selected validator
+    pub no_op_reason: Option<StakeReserveStatus>,
+}
+
+#[derive(Accounts)]
+pub struct WouldStakeValidator<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(address = state.validator_system.validator_list.account)]
+    pub validator_list: Account<'info, ValidatorList>,
+    #[account(
+        seeds = [
+            &state.key().to_bytes(),
+            State::RESERVE_SEED
+        ],
+        bump = state.reserve_bump_seed
+    )]
+    pub reserve_pda: SystemAccount<'info>,
+    /// CHECK: compared with the validator record, parsed by check_vote_activity
+    pub validator_vote: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub epoch_schedule: Sysvar<'info, EpochSchedule>,
+}
+
+impl<'info> WouldStakeValidator<'info> {
+    /// View for the operators: whether stake_reserve_greedy(0, u32::MAX) would stake
+    /// into the validator now, and the amount. Runs the same selection and sizing without mutation
+    pub fn process(&self, validator_index: u32) -> Result<WouldStakeValidatorResult> {
+        self.state
+            .validator_system
+            .check_validator_index(validator_index)?;
+        let validator_list_info = self.validator_list.to_account_info();
+        let validator_list_data = validator_list_info.data.borrow();
+        let reserve_balance = self.reserve_pda.lamports();
+        let selected = select_validator_for_stake(
+            &self.state,
+            &validator_list_data,
+            self.state.total_stake_target(reserve_balance)?,
+            self.clock.epoch,
+            0,
+            u32::MAX,
+        )?;
+        if selected != Some(validator_index) {
+            msg!("Greedy selection is {:?}", selected);
+            return Ok(WouldStakeValidatorResult::default());
+        }
+
+        let mut plan = plan_stake_reserve(
+            &self.state,
+            &validator_list_data,
+            validator_index,
+            self.validator_vote.key,
+            reserve_balance,
+            &self.clock,
+            self.epoch_schedule.get_last_slot_in_epoch(self.clock.epoch),
+            false,
+            None,
+        )?;
+        if plan.is_ok() {
+            if let Some(status) = check_vote_activity(
+                &self.state,
+                &self.validator_vote,
+                &self.clock,
+                &self.epoch_schedule,
+            )? {
+                plan = Err(status);
+            }
+        }
+        Ok(match plan {
+            Ok(plan) => WouldStakeValidatorResult {
+                selected: true,
+                amount: plan.stake_target,
+                no_op_reason: None,
+            },
+            Err(reason) => WouldStakeValidatorResult {
+                selected: true,
+                amount: 0,
+                no_op_reason: Some(reason),
+            },
+        })
+    }
+}
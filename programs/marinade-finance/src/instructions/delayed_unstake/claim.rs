@@ -134,7 +134,9 @@ impl<'info> Claim<'info> {
             ),
             lamports,
         )?;
-        self.state.on_transfer_from_reserve(lamports);
+        let state_address = self.state.key();
+        self.state
+            .on_transfer_from_reserve(&state_address, lamports, self.clock.epoch)?;
 
         emit!(ClaimEvent {
             state: self.state.key(),
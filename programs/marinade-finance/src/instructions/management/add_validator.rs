@@ -1,7 +1,7 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::system_program;
 
-use crate::events::management::AddValidatorEvent;
+use crate::events::{admin::ValidatorListNearCapacityEvent, management::AddValidatorEvent};
 use crate::state::validator_system::{ValidatorList, ValidatorRecord};
 use crate::{error::MarinadeError, State};
 
@@ -59,7 +59,10 @@ impl<'info> AddValidator<'info> {
             score,
             &state_address,
             self.duplication_flag.key,
+            self.clock.epoch,
         )?;
+        // new validators start in the default pool
+        self.state.add_pool_score(0, score);
 
         emit!(AddValidatorEvent {
             state: self.state.key(),
@@ -67,6 +70,19 @@ impl<'info> AddValidator<'info> {
             index: self.state.validator_system.validator_count() - 1,
             score
         });
+        if let Some(capacity) = self
+            .state
+            .validator_system
+            .validator_list_crossed_near_capacity(
+                self.validator_list.to_account_info().data_len(),
+            )?
+        {
+            emit!(ValidatorListNearCapacityEvent {
+                state: self.state.key(),
+                count: self.state.validator_system.validator_count(),
+                capacity,
+            });
+        }
 
         Ok(())
     }
@@ -0,0 +1,87 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError,
+    events::{management::ConfigValidatorEvent, U64ValueChange, U8ValueChange},
+    state::validator_system::ValidatorList,
+    State,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct ConfigValidatorParams {
+    /// 0 to unset and use global min_stake
+    pub min_delegation: Option<u64>,
+    /// validator pool index, see config_validator_pools
+    pub pool: Option<u8>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigValidator<'info> {
+    #[account(mut)]
+    pub state: Account<'info, State>,
+    #[account(
+        address = state.validator_system.manager_authority
+            @ MarinadeError::InvalidValidatorManager
+    )]
+    pub manager_authority: Signer<'info>,
+    #[account(
+        mut,
+        address = state.validator_system.validator_list.account,
+    )]
+    pub validator_list: Account<'info, ValidatorList>,
+}
+
+impl<'info> ConfigValidator<'info> {
+    pub fn process(
+        &mut self,
+        index: u32,
+        validator_vote: Pubkey,
+        ConfigValidatorParams {
+            min_delegation,
+            pool,
+        }: ConfigValidatorParams,
+    ) -> Result<()> {
+        require!(!self.state.paused, MarinadeError::ProgramIsPaused);
+
+        let mut validator = self.state.validator_system.get_checked(
+            &self.validator_list.to_account_info().data.borrow(),
+            index,
+            &validator_vote,
+        )?;
+
+        let min_delegation_change = if let Some(min_delegation) = min_delegation {
+            let old = validator.min_delegation;
+            validator.min_delegation = min_delegation;
+            Some(U64ValueChange {
+                old,
+                new: min_delegation,
+            })
+        } else {
+            None
+        };
+
+        let pool_change = if let Some(pool) = pool {
+            let old = validator.pool;
+            self.state.set_validator_pool(&mut validator, pool)?;
+            Some(U8ValueChange { old, new: pool })
+        } else {
+            None
+        };
+
+        self.state.validator_system.set(
+            &mut self.validator_list.to_account_info().data.borrow_mut(),
+            index,
+            validator,
+        )?;
+
+        emit!(ConfigValidatorEvent {
+            state: self.state.key(),
+            validator: validator_vote,
+            index,
+            min_delegation_change,
+            pool_change,
+        });
+
+        Ok(())
+    }
+}
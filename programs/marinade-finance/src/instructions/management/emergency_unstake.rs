@@ -103,6 +103,7 @@ impl<'info> EmergencyUnstake<'info> {
 
         // we now consider amount no longer "active" for this specific validator
         validator.active_balance -= unstake_amount;
+        validator.on_stake_account_removed();
         // and in state totals,
         // move from total_active_balance -> total_cooling_down
         self.state.validator_system.total_active_balance -= unstake_amount;
@@ -1,11 +1,15 @@
 pub mod add_validator;
+pub mod config_validator;
 pub mod emergency_unstake;
 pub mod partial_unstake;
 pub mod remove_validator;
 pub mod set_validator_score;
+pub mod stake_reserve_sweep;
 
 pub use add_validator::*;
+pub use config_validator::*;
 pub use emergency_unstake::*;
 pub use partial_unstake::*;
 pub use remove_validator::*;
 pub use set_validator_score::*;
+pub use stake_reserve_sweep::*;
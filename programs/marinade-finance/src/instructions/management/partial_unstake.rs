@@ -128,7 +128,6 @@ impl<'info> PartialUnstake<'info> {
         // compute target for this particular validator (total_stake_target * score/total_score)
         let validator_stake_target = self
             .state
-            .validator_system
             .validator_stake_target(&validator, total_stake_target)?;
         // if validator is already on-target (or the split will be lower than min_stake), exit now
         if validator.active_balance <= validator_stake_target + self.state.stake_system.min_stake {
@@ -176,6 +175,7 @@ impl<'info> PartialUnstake<'info> {
 
             // mark as emergency_unstaking, so the SOL will be re-staked ASAP
             stake.is_emergency_unstaking = 1;
+            validator.on_stake_account_removed();
             // Return back the rent reserve of unused split stake account
             self.return_unused_split_stake_account_rent()?;
             // effective unstaked_from_account
@@ -191,9 +191,10 @@ impl<'info> PartialUnstake<'info> {
             );
 
             // add new account to Marinade stake-accounts list
-            self.state.stake_system.add(
+            self.state.add_stake(
                 &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
                 &self.split_stake_account.key(),
+                &validator.validator_account,
                 unstake_amount,
                 &self.clock,
                 1, // is_emergency_unstaking
@@ -69,6 +69,8 @@ impl<'info> RemoveValidator<'info> {
             index,
             validator,
         )?;
+        self.state
+            .remove_pool_score(validator.pool, validator.score);
 
         // record for event, then remove all flag-account lamports to remove flag
         let operational_sol_balance = self.operational_sol_account.lamports();
@@ -33,13 +33,11 @@ impl<'info> SetValidatorScore<'info> {
             &validator_vote,
         )?;
 
-        self.state.validator_system.total_validator_score -= validator.score;
-        let score_change = {
-            let old = validator.score;
-            validator.score = score;
-            U32ValueChange { old, new: score }
+        let score_change = U32ValueChange {
+            old: validator.score,
+            new: score,
         };
-        self.state.validator_system.total_validator_score += score;
+        self.state.set_validator_score(&mut validator, score);
         self.state.validator_system.set(
             &mut self.validator_list.to_account_info().data.borrow_mut(),
             index,
@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::MarinadeError,
+    events::management::SweepStakeEvent,
+    instructions::crank::stake_reserve::{
+        __client_accounts_stake_reserve, __cpi_client_accounts_stake_reserve, StakeReserve,
+        StakeReserveResult,
+    },
+    state::stake_system::StakeReserveStatus,
+};
+
+#[derive(Accounts)]
+pub struct StakeReserveSweep<'info> {
+    pub stake_reserve: StakeReserve<'info>,
+    #[account(
+        address = stake_reserve.state.validator_system.manager_authority
+            @ MarinadeError::InvalidValidatorManager
+    )]
+    pub manager_authority: Signer<'info>,
+}
+
+impl<'info> StakeReserveSweep<'info> {
+    /// Stakes the whole stake delta into the validator ignoring its stake target.
+    /// All the other stake_reserve checks apply: pause, slot window, double stake delta,
+    /// min stake, max_stake of the validator, max_total_active_balance and the reserve floor
+    pub fn process(
+        &mut self,
+        validator_index: u32,
+        extra_stake_accounts: &[AccountInfo<'info>],
+    ) -> Result<StakeReserveResult> {
+        let result = self
+            .stake_reserve
+            .process_sweep(validator_index, extra_stake_accounts)?;
+        if result.status == StakeReserveStatus::Staked {
+            emit!(SweepStakeEvent {
+                state: self.stake_reserve.state.key(),
+                epoch: Clock::get()?.epoch,
+                validator_index,
+                validator_vote: self.stake_reserve.validator_vote.key(),
+                amount: result.staked,
+                remaining_stake_delta: result.remaining_stake_delta,
+            });
+        }
+        Ok(result)
+    }
+}
@@ -15,7 +15,9 @@ use crate::{require_lte, State};
 pub struct Deposit<'info> {
     #[account(
         mut,
-        has_one = msol_mint
+        has_one = msol_mint,
+        // the first constraint: Anchor checks it after deserializing the accounts, before the address and PDA checks
+        constraint = !state.is_paused_for(State::PAUSE_DEPOSITS) @ MarinadeError::DepositsPaused,
     )]
     pub state: Box<Account<'info, State>>,
 
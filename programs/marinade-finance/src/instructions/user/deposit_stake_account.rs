@@ -6,16 +6,18 @@ use anchor_lang::solana_program::{
 use anchor_spl::stake::{Stake, StakeAccount};
 use anchor_spl::token::{mint_to, Mint, MintTo, Token, TokenAccount};
 
-use crate::events::user::DepositStakeAccountEvent;
+use crate::events::{admin::StakeListNearCapacityEvent, user::DepositStakeAccountEvent};
 use crate::state::stake_system::StakeList;
 use crate::state::validator_system::ValidatorList;
-use crate::{error::MarinadeError, require_lte, state::stake_system::StakeSystem, State, ID};
+use crate::{error::MarinadeError, require_lte, state::stake_system::StakeSystem, State};
 
 #[derive(Accounts)]
 pub struct DepositStakeAccount<'info> {
     #[account(
         mut,
-        has_one = msol_mint
+        has_one = msol_mint,
+        // the first constraint: Anchor checks it after deserializing the accounts, before the address and PDA checks
+        constraint = !state.is_paused_for(State::PAUSE_DEPOSITS) @ MarinadeError::DepositsPaused,
     )]
     pub state: Box<Account<'info, State>>,
 
@@ -139,6 +141,7 @@ impl<'info> DepositStakeAccount<'info> {
         let validator_active_balance = validator.active_balance;
         // update validator.active_balance
         validator.active_balance += delegation.stake;
+        validator.on_stake_account_added();
         self.state.validator_system.set(
             &mut self
                 .validator_list
@@ -151,15 +154,10 @@ impl<'info> DepositStakeAccount<'info> {
         )?;
 
         {
-            let new_staker = Pubkey::create_program_address(
-                &[
-                    &self.state.key().to_bytes(),
-                    StakeSystem::STAKE_DEPOSIT_SEED,
-                    &[self.state.stake_system.stake_deposit_bump_seed],
-                ],
-                &ID,
-            )
-            .unwrap();
+            let new_staker = StakeSystem::stake_deposit_authority(
+                &self.state.key(),
+                self.state.stake_system.stake_deposit_bump_seed,
+            );
             let old_staker = self.stake_account.meta().unwrap().authorized.staker;
             // Can not deposit stake already under marinade stake auth. old staker must be different than ours
             require_keys_neq!(
@@ -207,15 +205,10 @@ impl<'info> DepositStakeAccount<'info> {
 
         let old_withdrawer = self.stake_account.meta().unwrap().authorized.withdrawer;
         {
-            let new_withdrawer = Pubkey::create_program_address(
-                &[
-                    &self.state.key().to_bytes(),
-                    StakeSystem::STAKE_WITHDRAW_SEED,
-                    &[self.state.stake_system.stake_withdraw_bump_seed],
-                ],
-                &ID,
-            )
-            .unwrap();
+            let new_withdrawer = StakeSystem::stake_withdraw_authority(
+                &self.state.key(),
+                self.state.stake_system.stake_withdraw_bump_seed,
+            );
             // Can not deposit stake already under marinade stake auth. old_withdrawer must be different than ours
             require_keys_neq!(
                 old_withdrawer,
@@ -240,13 +233,25 @@ impl<'info> DepositStakeAccount<'info> {
             )?;
         }
 
-        self.state.stake_system.add(
+        let stake_index = self.state.add_stake(
             &mut self.stake_list.to_account_info().data.as_ref().borrow_mut(),
             self.stake_account.to_account_info().key,
+            &delegation.voter_pubkey,
             delegation.stake,
             &self.clock,
             0, // is_emergency_unstaking? no
         )?;
+        if let Some(capacity) = self
+            .state
+            .stake_system
+            .stake_list_crossed_near_capacity(self.stake_list.to_account_info().data_len(), 1)?
+        {
+            emit!(StakeListNearCapacityEvent {
+                state: self.state.key(),
+                count: self.state.stake_system.stake_count(),
+                capacity,
+            });
+        }
 
         let msol_to_mint = self.state.calc_msol_from_lamports(delegation.stake)?;
 
@@ -278,7 +283,7 @@ impl<'info> DepositStakeAccount<'info> {
             stake: self.stake_account.key(),
             delegated: delegation.stake,
             withdrawer: old_withdrawer,
-            stake_index: self.state.stake_system.stake_count() - 1,
+            stake_index,
             validator: delegation.voter_pubkey,
             validator_index,
             validator_active_balance,
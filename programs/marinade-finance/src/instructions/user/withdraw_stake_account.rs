@@ -101,6 +101,9 @@ pub struct WithdrawStakeAccount<'info> {
 }
 
 impl<'info> WithdrawStakeAccount<'info> {
+    /// A stake created under the stake_reserve lockup (state.stake_lockup_epoch) is handed over
+    /// with the lockup in force: until it expires only the program as custodian can lift it
+    /// (admin lift_stake_lockup), the user can not withdraw its lamports before that
     pub fn process(
         &mut self,
         stake_index: u32,
@@ -327,7 +330,9 @@ impl<'info> WithdrawStakeAccount<'info> {
                 self.stake_withdraw_authority.key,
                 &beneficiary,
                 StakeAuthorize::Withdrawer,
-                None,
+                // the custodian of the stake_reserve lockup, the split stake keeps the lockup
+                // until it expires or the admin lifts it (lift_stake_lockup)
+                Some(self.stake_withdraw_authority.key),
             ),
             &[
                 self.split_stake_account.to_account_info(),
@@ -16,6 +16,11 @@ use instructions::*;
 #[cfg(not(feature = "no-entrypoint"))]
 use solana_security_txt::security_txt;
 pub use state::State;
+use state::{
+    stake_system::{StakeReserveLastStatus, StakeReserveReport},
+    validator_system::MAX_VALIDATOR_POOLS,
+    Fee,
+};
 
 declare_id!("MarBmsSgKXdrN1egZf5sqe1TMai9K1rChYNDJgjq7aD");
 
@@ -32,9 +37,7 @@ security_txt! {
 }
 
 fn check_context<T>(ctx: &Context<T>) -> Result<()> {
-    if !check_id(ctx.program_id) {
-        return err!(MarinadeError::InvalidProgramId);
-    }
+    check_program_id(ctx)?;
     // make sure there are no extra accounts
     if !ctx.remaining_accounts.is_empty() {
         return err!(MarinadeError::UnexpectedAccount);
@@ -43,6 +46,23 @@ fn check_context<T>(ctx: &Context<T>) -> Result<()> {
     Ok(())
 }
 
+/// Logs the remaining compute units when built with the debug-compute-logging feature, no-op otherwise.
+/// The repo has no measured CU figures: the CU of the stake_reserve phases
+/// are read from the logs of such a build on a validator
+#[inline(always)]
+pub(crate) fn log_compute_units() {
+    #[cfg(feature = "debug-compute-logging")]
+    anchor_lang::solana_program::log::sol_log_compute_units();
+}
+
+/// for the instructions receiving remaining accounts
+fn check_program_id<T>(ctx: &Context<T>) -> Result<()> {
+    if !check_id(ctx.program_id) {
+        return err!(MarinadeError::InvalidProgramId);
+    }
+    Ok(())
+}
+
 //-----------------------------------------------------
 #[program]
 pub mod marinade_finance {
@@ -97,6 +117,89 @@ pub mod marinade_finance {
         ctx.accounts.process(index, validator_vote, score)
     }
 
+    pub fn config_validator(
+        ctx: Context<ConfigValidator>,
+        index: u32,
+        validator_vote: Pubkey,
+        params: ConfigValidatorParams,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(index, validator_vote, params)
+    }
+
+    pub fn config_validator_pools(
+        ctx: Context<ConfigValidatorPools>,
+        allocations: [Fee; MAX_VALIDATOR_POOLS],
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(allocations)
+    }
+
+    pub fn set_crank_authorities(
+        ctx: Context<SetCrankAuthorities>,
+        crank_authorities: Vec<Pubkey>,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(crank_authorities)
+    }
+
+    pub fn set_preferred_validators(
+        ctx: Context<SetPreferredValidators>,
+        validator_indexes: Vec<u32>,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(validator_indexes)
+    }
+
+    pub fn set_stake_delta_window(
+        ctx: Context<SetStakeDeltaWindow>,
+        slots_for_stake_delta: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(slots_for_stake_delta)
+    }
+
+    pub fn set_validator_drain_only(
+        ctx: Context<SetValidatorDrainOnly>,
+        index: u32,
+        validator_vote: Pubkey,
+        drain_only: bool,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(index, validator_vote, drain_only)
+    }
+
+    pub fn set_validator_max_stake(
+        ctx: Context<SetValidatorMaxStake>,
+        index: u32,
+        validator_vote: Pubkey,
+        max_stake: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(index, validator_vote, max_stake)
+    }
+
+    pub fn reset_reserve_low_water(ctx: Context<ResetReserveLowWater>) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    pub fn refill_extra_stake_delta_runs(
+        ctx: Context<RefillExtraStakeDeltaRuns>,
+        count: u32,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(count)
+    }
+
+    pub fn absorb_stake_account(
+        ctx: Context<AbsorbStakeAccount>,
+        validator_index: u32,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(validator_index)
+    }
+
     pub fn config_validator_system(
         ctx: Context<ConfigValidatorSystem>,
         extra_runs: u32,
@@ -166,11 +269,202 @@ pub mod marinade_finance {
         ctx.accounts.process()
     }
 
-    pub fn stake_reserve(ctx: Context<StakeReserve>, validator_index: u32) -> Result<()> {
+    /// stake_reserve into the most under-staked validator of the scanned range.
+    /// remaining accounts: extra stake accounts (signers) when the stake is split by max_stake_per_account
+    pub fn stake_reserve_greedy<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeReserve<'info>>,
+        start_index: u32,
+        max_scan: u32,
+    ) -> Result<StakeReserveResult> {
+        check_program_id(&ctx)?;
+        ctx.accounts
+            .process_greedy(start_index, max_scan, ctx.remaining_accounts)
+    }
+
+    /// stake_reserve into validator_vote, its index is found in validator_list[start_index..start_index + max_scan].
+    /// remaining accounts: extra stake accounts (signers) when the stake is split by max_stake_per_account
+    pub fn stake_reserve_by_vote<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeReserve<'info>>,
+        start_index: u32,
+        max_scan: u32,
+    ) -> Result<StakeReserveResult> {
+        check_program_id(&ctx)?;
+        ctx.accounts
+            .process_by_vote(start_index, max_scan, ctx.remaining_accounts)
+    }
+
+    /// stake_reserve into the first under-staked validator of state.preferred_validators.
+    /// remaining accounts: extra stake accounts (signers) when the stake is split by max_stake_per_account
+    pub fn stake_reserve_preferred<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeReserve<'info>>,
+    ) -> Result<StakeReservePreferredResult> {
+        check_program_id(&ctx)?;
+        ctx.accounts.process_preferred(ctx.remaining_accounts)
+    }
+
+    /// deprecated: use stake_reserve_with_params, the arguments will not be extended
+    pub fn stake_reserve(
+        ctx: Context<StakeReserve>,
+        validator_index: u32,
+    ) -> Result<StakeReserveResult> {
+        check_context(&ctx)?;
+        ctx.accounts.process(validator_index.into(), &[])
+    }
+
+    /// remaining accounts: extra stake accounts (signers) when the stake is split by max_stake_per_account
+    pub fn stake_reserve_with_params<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeReserve<'info>>,
+        params: StakeReserveParams,
+    ) -> Result<StakeReserveResult> {
+        check_program_id(&ctx)?;
+        ctx.accounts.process(params, ctx.remaining_accounts)
+    }
+
+    /// stake_reserve_with_params with the validator scores signed by state.score_oracle
+    /// applied for the stake targets. The stored scores are restored after staking unless persist_scores.
+    /// remaining accounts: extra stake accounts (signers) when the stake is split by max_stake_per_account
+    pub fn stake_reserve_with_scores<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeReserveWithScores<'info>>,
+        params: StakeReserveParams,
+        scores: Vec<ValidatorScoreUpdate>,
+        persist_scores: bool,
+    ) -> Result<StakeReserveResult> {
+        check_program_id(&ctx)?;
+        ctx.accounts
+            .process(params, scores, persist_scores, ctx.remaining_accounts)
+    }
+
+    /// validator manager override: stake_reserve of the whole stake delta into the validator
+    /// over its stake target, usually the last stake of the epoch.
+    /// remaining accounts: extra stake accounts (signers) when the stake is split by max_stake_per_account
+    pub fn stake_reserve_sweep<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeReserveSweep<'info>>,
+        validator_index: u32,
+    ) -> Result<StakeReserveResult> {
+        check_program_id(&ctx)?;
+        ctx.accounts
+            .process(validator_index, ctx.remaining_accounts)
+    }
+
+    /// stake_reserve into several validators.
+    /// remaining accounts: [validator_vote, stake_account] for each of validator_indices.
+    /// Returns the stake_reserve result of every processed index
+    pub fn stake_reserve_multi<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeReserveMulti<'info>>,
+        validator_indices: Vec<u32>,
+    ) -> Result<Vec<StakeReserveResult>> {
+        check_program_id(&ctx)?;
+        ctx.accounts
+            .process(validator_indices, ctx.remaining_accounts)
+    }
+
+    /// view. Returns the number of stake_reserve calls needed to deploy the stake delta
+    pub fn estimate_stake_reserve_runs(ctx: Context<EstimateStakeReserveRuns>) -> Result<u32> {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    /// view. Returns the stake_reserve counters of the current epoch
+    pub fn get_stake_reserve_report(
+        ctx: Context<GetStakeReserveReport>,
+    ) -> Result<StakeReserveReport> {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    /// view. Returns the outcome of the last not reverted stake_reserve call
+    pub fn get_stake_reserve_last_status(
+        ctx: Context<GetStakeReserveLastStatus>,
+    ) -> Result<StakeReserveLastStatus> {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    /// view. Returns the last stake-delta epoch and whether it is the current one
+    pub fn get_stake_delta_mode(ctx: Context<GetStakeDeltaMode>) -> Result<StakeDeltaMode> {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    /// view. Returns the stake-delta slot window of the current epoch and whether it is open
+    pub fn stake_window_status(ctx: Context<GetStakeWindowStatus>) -> Result<StakeWindowStatus> {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    /// view. Returns the stake delta and the amount stake_reserve would delegate to the validator
+    pub fn compute_stake_delta(
+        ctx: Context<ComputeStakeDelta>,
+        validator_index: Option<u32>,
+    ) -> Result<StakeDeltaSimulation> {
+        check_context(&ctx)?;
+        ctx.accounts.process(validator_index)
+    }
+
+    /// view. Returns the stake target and the active balance of the validator
+    pub fn get_validator_stake_target(
+        ctx: Context<GetValidatorStakeTarget>,
+        validator_index: u32,
+    ) -> Result<ValidatorStakeTarget> {
+        check_context(&ctx)?;
+        ctx.accounts.process(validator_index)
+    }
+
+    /// view. Returns whether the greedy stake_reserve would stake into the validator now
+    pub fn would_stake_validator(
+        ctx: Context<WouldStakeValidator>,
+        validator_index: u32,
+    ) -> Result<WouldStakeValidatorResult> {
         check_context(&ctx)?;
         ctx.accounts.process(validator_index)
     }
 
+    /// view. Returns the validator of the identity
+    /// found in validator_list[start_index..start_index + max_scan].
+    /// remaining accounts: candidate vote accounts
+    pub fn find_validator_by_identity<'info>(
+        ctx: Context<'_, '_, '_, 'info, FindValidatorByIdentity<'info>>,
+        identity: Pubkey,
+        start_index: u32,
+        max_scan: u32,
+    ) -> Result<ValidatorByIdentity> {
+        check_program_id(&ctx)?;
+        ctx.accounts
+            .process(identity, start_index, max_scan, ctx.remaining_accounts)
+    }
+
+    /// view. Returns the stake targets of the validators starting from start_index
+    pub fn preview_all_validator_targets(
+        ctx: Context<PreviewAllValidatorTargets>,
+        start_index: u32,
+    ) -> Result<Vec<ValidatorTargetPreview>> {
+        check_context(&ctx)?;
+        ctx.accounts.process(start_index)
+    }
+
+    /// view. Returns the stakes stake_reserve would create for the reserve balance
+    pub fn simulate_stake_plan(
+        ctx: Context<SimulateStakePlan>,
+        reserve_balance: u64,
+        cursor: Option<StakePlanCursor>,
+        max_items: u32,
+    ) -> Result<StakePlan> {
+        check_context(&ctx)?;
+        ctx.accounts.process(reserve_balance, cursor, max_items)
+    }
+
+    /// view. Returns the reserve lamports not needed for rent and delayed-unstake tickets
+    pub fn get_idle_reserve(ctx: Context<GetIdleReserve>) -> Result<u64> {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    /// view. Returns total_stake_target - total_active_balance
+    pub fn total_active_balance_gap(ctx: Context<TotalActiveBalanceGap>) -> Result<i128> {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
     pub fn update_active(
         ctx: Context<UpdateActive>,
         stake_index: u32,
@@ -224,6 +518,12 @@ pub mod marinade_finance {
             .process(destination_stake_index, source_stake_index, validator_index)
     }
 
+    /// returns whether the stake is delegated to the recorded validator
+    pub fn confirm_delegation(ctx: Context<ConfirmDelegation>, stake_index: u32) -> Result<bool> {
+        check_context(&ctx)?;
+        ctx.accounts.process(stake_index)
+    }
+
     pub fn redelegate(
         ctx: Context<ReDelegate>,
         stake_index: u32,
@@ -247,6 +547,11 @@ pub mod marinade_finance {
         ctx.accounts.resume()
     }
 
+    pub fn set_pause(ctx: Context<EmergencyPause>, pause_flags: u8) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.set_pause(pause_flags)
+    }
+
     // immediate withdraw of an active stake account - feature can be enabled or disable by the DAO
     pub fn withdraw_stake_account(
         ctx: Context<WithdrawStakeAccount>,
@@ -269,4 +574,46 @@ pub mod marinade_finance {
         check_context(&ctx)?;
         ctx.accounts.process(capacity)
     }
+
+    pub fn realloc_state(ctx: Context<ReallocState>) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    pub fn reconcile_stake(
+        ctx: Context<ReconcileStake>,
+        stake_index: u32,
+        validator_index: u32,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(stake_index, validator_index)
+    }
+
+    pub fn reconcile_total_active_balance(
+        ctx: Context<ReconcileTotalActiveBalance>,
+        start_index: u32,
+        count: u32,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(start_index, count)
+    }
+
+    pub fn lift_stake_lockup(ctx: Context<LiftStakeLockup>) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process()
+    }
+
+    pub fn recompute_pool_scores(
+        ctx: Context<RecomputePoolScores>,
+        start_index: u32,
+        count: u32,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+        ctx.accounts.process(start_index, count)
+    }
+
+    pub fn sort_stake_list(ctx: Context<SortStakeList>) -> Result<()> {
+        check_program_id(&ctx)?;
+        ctx.accounts.process(ctx.remaining_accounts)
+    }
 }
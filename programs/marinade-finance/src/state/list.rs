@@ -1,10 +1,11 @@
 use std::io::Cursor;
 
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
 use borsh::BorshSchema;
 use std::convert::TryFrom;
 
-use crate::{error::MarinadeError, require_lt};
+use crate::{error::MarinadeError, require_lt, require_lte};
 
 #[derive(Default, Clone, AnchorSerialize, AnchorDeserialize, BorshSchema, Debug)]
 pub struct List {
@@ -17,6 +18,9 @@ pub struct List {
 }
 
 impl List {
+    /// the list is considered close to full when more than this percentage of the capacity is used
+    pub const NEAR_CAPACITY_PERCENT: u64 = 90;
+
     pub fn new(
         discriminator: &[u8; 8],
         item_size: u32,
@@ -38,6 +42,13 @@ impl List {
         8 + count * item_size
     }
 
+    /// The account length of one realloc towards `target_len`.
+    /// The runtime grows an account by at most MAX_PERMITTED_DATA_INCREASE (10 KiB) per instruction,
+    /// a larger growth takes several reallocs
+    pub fn realloc_len(account_len: usize, target_len: usize) -> usize {
+        target_len.min(account_len.saturating_add(MAX_PERMITTED_DATA_INCREASE))
+    }
+
     pub fn capacity_of(item_size: u32, account_len: usize) -> u32 {
         (account_len as u32 - 8) / item_size
     }
@@ -81,11 +92,83 @@ impl List {
         .unwrap_or(std::u32::MAX)) // for zst element (why you are using it in list?)
     }
 
+    /// Validates the header against the account before reading the items:
+    /// the item size fits the record layout and the account holds all the counted items
+    pub fn check_account(&self, account_len: usize, record_size: u32) -> Result<()> {
+        if self.item_size() < record_size {
+            msg!(
+                "List item size {} is lower than the record size {}",
+                self.item_size(),
+                record_size
+            );
+            return err!(MarinadeError::InvalidListAccount);
+        }
+        if account_len < 8 {
+            msg!("List account of {} bytes has no discriminator", account_len);
+            return err!(MarinadeError::InvalidListAccount);
+        }
+        let capacity = self.capacity(account_len)?;
+        if self.len() > capacity {
+            msg!(
+                "List count {} exceeds the capacity {} of the account of {} bytes",
+                self.len(),
+                capacity,
+                account_len
+            );
+            return err!(MarinadeError::InvalidListAccount);
+        }
+        Ok(())
+    }
+
+    /// Grows every item slot to `item_size` bytes keeping the content and zero-filling the extra space
+    /// (used for migrating to a longer record layout).
+    /// `data` must be already reallocated to fit `len()` items of the new size
+    pub fn set_item_size(&mut self, data: &mut [u8], item_size: u32) -> Result<()> {
+        require_gte!(item_size, self.item_size());
+        require_gte!(
+            data.len(),
+            Self::bytes_for(item_size, self.len()) as usize,
+            MarinadeError::ListOverflow
+        );
+        let old_item_size = self.item_size() as usize;
+        // move from the last item because items are moving to greater offsets
+        for index in (0..self.len()).rev() {
+            let old_start = 8 + (index * self.item_size()) as usize;
+            let new_start = 8 + (index * item_size) as usize;
+            data.copy_within(old_start..old_start + old_item_size, new_start);
+            data[new_start + old_item_size..new_start + item_size as usize].fill(0);
+        }
+        self.item_size = item_size;
+
+        Ok(())
+    }
+
+    fn is_near_capacity(count: u32, capacity: u32) -> bool {
+        count as u64 * 100 > capacity as u64 * Self::NEAR_CAPACITY_PERCENT
+    }
+
+    /// Returns Some(capacity) if the last `added` pushes made the list cross the near capacity threshold
+    pub fn crossed_near_capacity(&self, account_len: usize, added: u32) -> Result<Option<u32>> {
+        let capacity = self.capacity(account_len)?;
+        Ok(
+            if Self::is_near_capacity(self.len(), capacity)
+                && !Self::is_near_capacity(self.len().saturating_sub(added), capacity)
+            {
+                Some(capacity)
+            } else {
+                None
+            },
+        )
+    }
+
     pub fn get<I: AnchorDeserialize>(&self, data: &[u8], index: u32) -> Result<I> {
         require_lt!(index, self.len(), MarinadeError::ListIndexOutOfBounds);
 
         let start = 8 + (index * self.item_size()) as usize;
-        I::deserialize(&mut &data[start..(start + self.item_size() as usize)]).map_err(|err| {
+        let mut item_data = data
+            .get(start..(start + self.item_size() as usize))
+            .ok_or_else(|| error!(MarinadeError::InvalidListAccount))?;
+        I::deserialize(&mut item_data).map_err(|err| {
             Error::from(ProgramError::BorshIoError(err.to_string())).with_source(source!())
         })
     }
@@ -94,7 +177,10 @@ impl List {
         require_lt!(index, self.len(), MarinadeError::ListIndexOutOfBounds);
 
         let start = 8 + (index * self.item_size()) as usize;
-        let mut cursor = Cursor::new(&mut data[start..(start + self.item_size() as usize)]);
+        let item_data = data
+            .get_mut(start..(start + self.item_size() as usize))
+            .ok_or_else(|| error!(MarinadeError::InvalidListAccount))?;
+        let mut cursor = Cursor::new(item_data);
         item.serialize(&mut cursor)?;
 
         Ok(())
@@ -113,6 +199,44 @@ impl List {
         Ok(())
     }
 
+    /// Inserts the item at `index` moving all the following items one position forward.
+    /// Warn: the indexes of the following items change, an index read before the call
+    /// (e.g. stake_index of a bot transaction) points to another item after it
+    pub fn insert<I: AnchorSerialize>(
+        &mut self,
+        data: &mut [u8],
+        index: u32,
+        item: I,
+    ) -> Result<()> {
+        require_lte!(index, self.len(), MarinadeError::ListIndexOutOfBounds);
+        let capacity = self.capacity(data.len())?;
+        require_lt!(self.len(), capacity, MarinadeError::ListOverflow);
+
+        let start = 8 + (index * self.item_size()) as usize;
+        let end = 8 + (self.len() * self.item_size()) as usize;
+        data.copy_within(start..end, start + self.item_size() as usize);
+        let mut cursor = Cursor::new(&mut data[start..(start + self.item_size() as usize)]);
+        item.serialize(&mut cursor)?;
+
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// Removes the item at `index` moving all the following items one position back.
+    /// Slower than remove() but keeps the order of the items.
+    /// Warn: the indexes of the following items change as for insert()
+    pub fn remove_ordered(&mut self, data: &mut [u8], index: u32) -> Result<()> {
+        require_lt!(index, self.len(), MarinadeError::ListIndexOutOfBounds);
+
+        let start = 8 + (index * self.item_size()) as usize;
+        let end = 8 + (self.len() * self.item_size()) as usize;
+        data.copy_within(start + self.item_size() as usize..end, start);
+        self.count -= 1;
+
+        Ok(())
+    }
+
     pub fn remove(&mut self, data: &mut [u8], index: u32) -> Result<()> {
         require_lt!(index, self.len(), MarinadeError::ListIndexOutOfBounds);
 
@@ -138,6 +262,7 @@ mod tests {
     use anchor_lang::prelude::*;
 
     use super::List;
+    use crate::error::MarinadeError;
 
     #[test]
     fn test_remove() -> Result<()> {
@@ -169,4 +294,171 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_insert_and_remove_ordered() -> Result<()> {
+        const COUNT: usize = 10;
+        let mut list_data = [0; COUNT + 8];
+        let list_account = Pubkey::new_unique();
+        let discriminator = &[1, 2, 3, 4, 5, 6, 7, 8];
+        let mut list = List::new(discriminator, 1u32, list_account, &mut list_data)?;
+        let mut expected = vec![];
+        for i in 0..COUNT {
+            // insert every item in the middle
+            let index = (expected.len() / 2) as u32;
+            list.insert::<u8>(&mut list_data, index, i as u8)?;
+            expected.insert(index as usize, i as u8);
+        }
+        assert!(list.insert::<u8>(&mut list_data, 0, 0).is_err());
+        let items = |list: &List, data: &[u8]| {
+            (0..list.len())
+                .map(|i| list.get::<u8>(data, i))
+                .collect::<Result<Vec<u8>>>()
+        };
+        assert_eq!(items(&list, &list_data)?, expected);
+
+        for remove_index in [3u32, 0, 7] {
+            list.remove_ordered(&mut list_data, remove_index)?;
+            expected.remove(remove_index as usize);
+            assert_eq!(items(&list, &list_data)?, expected);
+        }
+        assert!(list.remove_ordered(&mut list_data, 7).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_item_size() -> Result<()> {
+        const COUNT: usize = 10;
+        let mut list_data = [0xff; 3 * COUNT + 8];
+        list_data[0..8].fill(0);
+        let list_account = Pubkey::new_unique();
+        let discriminator = &[1, 2, 3, 4, 5, 6, 7, 8];
+        let mut list = List::new(
+            discriminator,
+            2u32,
+            list_account,
+            &mut list_data[..2 * COUNT + 8],
+        )?;
+        for i in 0..COUNT {
+            list.push::<u16>(&mut list_data[..2 * COUNT + 8], 0x100 + i as u16)?;
+        }
+
+        list.set_item_size(&mut list_data, 3)?;
+        assert_eq!(list.item_size(), 3);
+        assert_eq!(list.len(), COUNT as u32);
+        for i in 0..COUNT {
+            // old content is kept and the new space is zeroed
+            assert_eq!(
+                list.get::<(u16, u8)>(&list_data, i as u32)?,
+                (0x100 + i as u16, 0)
+            );
+        }
+        assert!(list.set_item_size(&mut list_data, 2).is_err());
+        assert!(list.set_item_size(&mut list_data, 4).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_crossed_near_capacity() -> Result<()> {
+        const CAPACITY: usize = 20;
+        let mut list_data = [0; CAPACITY + 8];
+        let list_account = Pubkey::new_unique();
+        let discriminator = &[1, 2, 3, 4, 5, 6, 7, 8];
+        let mut list = List::new(discriminator, 1u32, list_account, &mut list_data)?;
+        let mut crossed_at = vec![];
+        for i in 0..CAPACITY {
+            list.push::<u8>(&mut list_data, i as u8)?;
+            if let Some(capacity) = list.crossed_near_capacity(list_data.len(), 1)? {
+                assert_eq!(capacity, CAPACITY as u32);
+                crossed_at.push(list.len());
+            }
+        }
+        // reported only once, when going over 90%
+        assert_eq!(crossed_at, vec![19]);
+        assert_eq!(list.crossed_near_capacity(list_data.len(), 2)?, Some(20));
+        Ok(())
+    }
+
+    #[test]
+    fn test_realloc_len() -> Result<()> {
+        // 500 items growing from 100 to 160 bytes: 30_000 bytes over the 10 KiB realloc limit
+        const COUNT: u32 = 500;
+        let mut list_data = vec![0; List::bytes_for(100, COUNT) as usize];
+        let discriminator = &[1, 2, 3, 4, 5, 6, 7, 8];
+        let mut list = List::new(discriminator, 100, Pubkey::new_unique(), &mut list_data)?;
+        for i in 0..COUNT {
+            list.push::<u32>(&mut list_data, i)?;
+        }
+        let target_len = List::bytes_for(160, COUNT) as usize;
+        let mut reallocs = 0;
+        while list_data.len() < target_len {
+            // the records can not be moved before the account fits them
+            assert_eq!(
+                list.clone().set_item_size(&mut list_data, 160).err(),
+                Some(error!(MarinadeError::ListOverflow))
+            );
+            let len = List::realloc_len(list_data.len(), target_len);
+            assert!(len - list_data.len() <= 10 * 1024);
+            list_data.resize(len, 0);
+            reallocs += 1;
+        }
+        assert_eq!(reallocs, 3);
+        assert_eq!(List::realloc_len(list_data.len(), target_len), target_len);
+        list.set_item_size(&mut list_data, 160)?;
+        for i in 0..COUNT {
+            assert_eq!(list.get::<u32>(&list_data, i)?, i);
+        }
+        // shrinking is not limited
+        assert_eq!(List::realloc_len(target_len, 8), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_account() -> Result<()> {
+        const COUNT: usize = 4;
+        let mut list_data = [0; 8 + COUNT * 4 + 100];
+        let discriminator = &[1, 2, 3, 4, 5, 6, 7, 8];
+        let mut list = List::new(discriminator, 4u32, Pubkey::new_unique(), &mut list_data)?;
+        for i in 0..COUNT {
+            list.push::<u32>(&mut list_data, i as u32)?;
+        }
+        // over-sized buffer: spare capacity is fine
+        list.check_account(list_data.len(), 4)?;
+        list.check_account(8 + COUNT * 4, 4)?;
+        // the record does not fit the item size
+        assert_eq!(
+            list.check_account(list_data.len(), 8).err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+        // truncated buffer: the last item is cut
+        let truncated = &list_data[..8 + COUNT * 4 - 1];
+        assert_eq!(
+            list.check_account(truncated.len(), 4).err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+        assert_eq!(
+            list.get::<u32>(truncated, COUNT as u32 - 1).err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+        assert_eq!(list.get::<u32>(truncated, 0)?, 0);
+        assert_eq!(
+            list.check_account(4, 4).err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+        // stale count over the account data
+        list.count = 100;
+        assert_eq!(
+            list.check_account(list_data.len(), 4).err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+        assert_eq!(
+            list.set::<u32>(&mut list_data, 99, 1).err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+        assert_eq!(
+            list.get::<u32>(&list_data, 99).err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+        Ok(())
+    }
 }
@@ -1,6 +1,7 @@
 use crate::{
-    calc::{shares_from_value, value_from_shares},
+    calc::{shares_from_value, u64_from_i128, value_from_shares},
     error::MarinadeError,
+    events::crank::ReserveTransferEvent,
     require_lte, ID,
 };
 use anchor_lang::{
@@ -9,7 +10,11 @@ use anchor_lang::{
 use anchor_spl::token::spl_token;
 use std::mem::MaybeUninit;
 
-use self::{liq_pool::LiqPool, stake_system::StakeSystem, validator_system::ValidatorSystem};
+use self::{
+    liq_pool::LiqPool,
+    stake_system::{StakeReserveLastStatus, StakeReserveReport, StakeSystem},
+    validator_system::{ValidatorSystem, MAX_PREFERRED_VALIDATORS, MAX_VALIDATOR_POOLS},
+};
 
 pub mod delayed_unstake_ticket;
 pub mod fee;
@@ -21,6 +26,17 @@ pub mod validator_system;
 pub use fee::Fee;
 pub use fee::FeeCents;
 
+pub const MAX_CRANK_AUTHORITIES: usize = 4;
+
+/// Part of the reserve deployed by stake_reserve
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum ReserveStakeSource {
+    #[default]
+    Combined,
+    Principal,
+    Rewards,
+}
+
 #[account]
 #[derive(Debug)]
 pub struct State {
@@ -91,6 +107,136 @@ pub struct State {
     pub last_stake_move_epoch: u64, // epoch of the last stake move action
     pub stake_moved: u64,           // total amount of moved SOL during the epoch #stake_move_epoch
     pub max_stake_moved_per_epoch: Fee, // % of total_lamports_under_control
+
+    // The fields below are appended after the layout of the first deployment (State::LAYOUT_V0_LEN bytes).
+    // New fields go only to the end: realloc_state migrates the older accounts and sets the defaults
+    pub layout_version: u8,
+
+    // hard cap of validator_system.total_active_balance for stake_reserve
+    pub max_total_active_balance: u64,
+
+    // part of available_reserve_balance received as staking rewards (withdrawn by update_active/update_deactivated).
+    // The rest is principal. Transfers from the reserve consume the principal first
+    pub reserve_rewards_balance: u64,
+    pub reserve_stake_source: ReserveStakeSource,
+    // buffer kept in the reserve for liquid_unstake, never staked by stake_reserve
+    pub reserve_retention_lamports: u64,
+    // lowest available_reserve_balance after a transfer from the reserve since the last reset
+    pub reserve_low_water_lamports: u64,
+    // set by pause_authority, pauses parts of the program (PAUSE_* bits) without the full pause
+    pub pause_flags: u8,
+    // set by admin, enables the accounting invariant checks (extra CU, for audited deployments)
+    pub invariant_checks: bool,
+    // reconcile_total_active_balance progress: next validator index and the active balances summed so far
+    pub reconcile_next_validator_index: u32,
+    pub reconcile_active_balance_sum: u64,
+    // set by admin, validator indexes in the order stake_reserve_preferred tries them.
+    // Only the first preferred_validator_count are used
+    pub preferred_validators: [u32; MAX_PREFERRED_VALIDATORS],
+    pub preferred_validator_count: u8,
+    // set by admin, when not empty stake_reserve must be signed (as cranker or rent_payer)
+    // by one of the first crank_authority_count keys. Empty: permissionless
+    pub crank_authorities: [Pubkey; MAX_CRANK_AUTHORITIES],
+    pub crank_authority_count: u8,
+    // set by admin (change_authority), signs the validator scores of stake_reserve_with_scores.
+    // Pubkey::default() disables it
+    pub score_oracle: Pubkey,
+
+    // stake_reserve settings and bookkeeping of the stake system
+    /// set by admin, keep stake_list sorted by StakeRecord::validator to simplify searching for merge candidates
+    /// instead of appending new records to the end.
+    /// Warn: sorted insertion and removal shift the stake_index of the following records at any time of the epoch,
+    /// including the stake-delta window. Bots must re-read stake_list before every stake_index based instruction
+    /// (and retry on WrongStakeAccountOrIndex)
+    pub sort_stakes_by_validator: bool,
+    /// number of leading stake_list records which are sorted by validator.
+    /// Sorted insertion starts working when all the records are sorted (see sort_stake_list)
+    pub sorted_stake_count: u32,
+
+    pub stake_reserve_report: StakeReserveReport,
+    pub stake_reserve_last_status: StakeReserveLastStatus,
+
+    /// set by admin, refuse to delegate to the vote accounts which are not rent exempt.
+    /// Off by default (initialize and realloc_state): the delegations accepted before keep working
+    pub check_vote_rent_exempt: bool,
+
+    /// set by admin, share of the epoch stake delta stake_reserve can deploy at the start of the stake-delta window.
+    /// The allowance grows linearly up to 100% at the last slot of the epoch
+    pub stake_window_start_allowance: Fee,
+
+    /// set by admin, what stake_reserve does when all the validators are at target but stake delta remains:
+    /// false - leave the surplus in the reserve, true - stake it over the targets proportionally to the scores.
+    /// Detected only for the lists of at most ALL_AT_TARGET_MAX_SCAN validators
+    pub stake_over_target: bool,
+
+    /// set by admin, upper bound of extra_stake_delta_runs for refill_extra_stake_delta_runs
+    pub max_extra_stake_delta_runs: u32,
+
+    /// set by admin, stake_reserve refuses to create more active stake accounts for a validator. 0 means no limit
+    pub max_stake_accounts_per_validator: u32,
+
+    /// set by admin, stake_reserve reads the latest stake_history entry to check it is up to date
+    pub parse_stake_history: bool,
+
+    /// set by admin, epochs after the last stake delta of a validator before it can be staked again. 0 = no cooldown
+    pub stake_cooldown_epochs: u64,
+
+    /// set by admin, stake_reserve skips the vote accounts not voted in the last max_idle_epochs epochs
+    pub check_vote_activity: bool,
+    pub max_idle_epochs: u64,
+
+    /// set by admin, max stake_reserve stakes of all the bots in an epoch. 0 = unlimited
+    pub max_stake_operations_per_epoch: u32,
+
+    /// set by admin, lockup of the stake accounts created by stake_reserve
+    /// with the stake withdraw authority as custodian. Both 0 = no lockup.
+    /// The program signs as the custodian for its withdrawals.
+    /// withdraw_stake_account hands the users such stakes with the lockup in force:
+    /// until it expires only the program as custodian can lift it (lift_stake_lockup)
+    pub stake_lockup_epoch: u64,
+    pub stake_lockup_unix_timestamp: i64,
+
+    /// set by admin, stake_reserve splits larger stakes into several stake accounts. 0 = no split
+    pub max_stake_per_account: u64,
+
+    /// set by admin, stake_reserve rounds the stake amounts down to its multiples. 0 = no rounding
+    pub stake_rounding_lamports: u64,
+
+    /// set by admin, the most one stake_reserve delegates, the rest of the delta is left for the next calls.
+    /// 0 = no cap
+    pub max_stake_per_call: u64,
+
+    /// set by admin, stake_reserve no-ops while the positive stake delta is lower.
+    /// Unlike min_stake it does not size the stake accounts. 0 = act on any delta
+    pub min_stake_delta_to_act: u64,
+
+    /// set by admin, share of the stake target stake_reserve uses for a validator
+    /// in the epoch it was added. 100% = no ramp
+    pub new_validator_ramp: Fee,
+
+    // validator pools of the validator system
+    /// share of the total stake target of every validator pool.
+    /// All zeroes means pools are not used and all the validators share the target by score
+    pub pool_allocations: [Fee; MAX_VALIDATOR_POOLS],
+    /// sum of the validator scores in every pool
+    pub pool_scores: [u32; MAX_VALIDATOR_POOLS],
+
+    /// stake accounts delegated by stake_reserve in stake_operations_epoch,
+    /// checked against max_stake_operations_per_epoch
+    pub stake_operations_this_epoch: u32,
+    pub stake_operations_epoch: u64,
+
+    /// pool_scores are the sums of the validator record scores: set by recompute_pool_scores,
+    /// cleared when a pool score would get out of the u32 range.
+    /// config_validator_pools enables the allocations only when set
+    pub pool_scores_valid: bool,
+    // recompute_pool_scores progress: next validator index and the pool scores summed so far
+    pub recompute_pool_scores_next_index: u32,
+    pub recompute_pool_scores_sums: [u32; MAX_VALIDATOR_POOLS],
+
+    /// set by admin, orders the validators of equal gaps in the greedy selection of stake_reserve,
+    /// the order changes every epoch. 0 = the lowest index wins
+    pub selection_seed: u64,
 }
 
 impl State {
@@ -103,6 +249,12 @@ impl State {
     pub const STAKE_LIST_SEED: &'static str = "stake_list";
     pub const VALIDATOR_LIST_SEED: &'static str = "validator_list";
 
+    pub const PAUSE_STAKING: u8 = 1 << 0;
+    pub const PAUSE_UNSTAKING: u8 = 1 << 1;
+    pub const PAUSE_DEPOSITS: u8 = 1 << 2;
+    pub const PAUSE_FLAGS_MASK: u8 =
+        Self::PAUSE_STAKING | Self::PAUSE_UNSTAKING | Self::PAUSE_DEPOSITS;
+
     pub const MAX_REWARD_FEE: Fee = Fee::from_basis_points(1_000); // 10% max reward fee
     pub const MAX_WITHDRAW_ATOM: u64 = LAMPORTS_PER_SOL / 10;
 
@@ -115,6 +267,11 @@ impl State {
     // min_stake minimum value is MIN_STAKE_MULTIPLIER * rent_exempt_for_token_acc
     pub const MIN_STAKE_LOWER_LIMIT: u64 = LAMPORTS_PER_SOL / 100;
 
+    /// serialized_len() of the first deployed layout, the accounts without layout_version
+    pub const LAYOUT_V0_LEN: usize = 638;
+    /// layout_version of the current State layout
+    pub const LAYOUT_VERSION: u8 = 1;
+
     pub fn serialized_len() -> usize {
         unsafe { MaybeUninit::<Self>::zeroed().assume_init() }
             .try_to_vec()
@@ -123,6 +280,91 @@ impl State {
             + 8
     }
 
+    /// layout version of the State account data (with the discriminator).
+    /// The accounts of the first layout are LAYOUT_V0_LEN long (or longer but zeroed after it)
+    pub fn layout_version(data: &[u8]) -> Result<u8> {
+        require_gte!(
+            data.len(),
+            Self::LAYOUT_V0_LEN,
+            MarinadeError::InvalidStateLayout
+        );
+        Ok(data.get(Self::LAYOUT_V0_LEN).copied().unwrap_or(0))
+    }
+
+    /// Migrates the State account data of an older layout in place.
+    /// `data` must already be reallocated to serialized_len(), the fields appended after
+    /// the old layout are set to their defaults. Returns the old layout version
+    pub fn migrate_layout(data: &mut [u8]) -> Result<u8> {
+        let old_version = Self::layout_version(data)?;
+        require_gt!(
+            Self::LAYOUT_VERSION,
+            old_version,
+            MarinadeError::InvalidStateLayout
+        );
+        require_gte!(
+            data.len(),
+            Self::serialized_len(),
+            MarinadeError::InvalidStateLayout
+        );
+        // layout 0: nothing after the first layout, the tail can contain only the realloc zeroes
+        data[Self::LAYOUT_V0_LEN..].fill(0);
+        let mut state = Self::try_deserialize(&mut &data[..])?;
+        state.set_layout_v1_defaults();
+        state.try_serialize(&mut &mut data[..])?;
+        Ok(old_version)
+    }
+
+    /// defaults of the fields appended in layout 1, the same as set by initialize
+    fn set_layout_v1_defaults(&mut self) {
+        self.layout_version = 1;
+        self.max_total_active_balance = std::u64::MAX;
+        self.reserve_rewards_balance = 0;
+        self.reserve_stake_source = ReserveStakeSource::Combined;
+        self.reserve_retention_lamports = 0;
+        self.reserve_low_water_lamports = std::u64::MAX;
+        self.pause_flags = 0;
+        self.invariant_checks = false;
+        self.reconcile_next_validator_index = 0;
+        self.reconcile_active_balance_sum = 0;
+        self.preferred_validators = [0; MAX_PREFERRED_VALIDATORS];
+        self.preferred_validator_count = 0;
+        self.crank_authorities = [Pubkey::default(); MAX_CRANK_AUTHORITIES];
+        self.crank_authority_count = 0;
+        self.score_oracle = Pubkey::default();
+        self.sort_stakes_by_validator = false;
+        self.sorted_stake_count = 0;
+        self.stake_reserve_report = StakeReserveReport::default();
+        self.stake_reserve_last_status = StakeReserveLastStatus::default();
+        self.check_vote_rent_exempt = false;
+        self.stake_window_start_allowance = Fee::from_basis_points(Fee::MAX_BASIS_POINTS);
+        self.stake_over_target = false;
+        self.max_extra_stake_delta_runs = std::u32::MAX;
+        self.max_stake_accounts_per_validator = 0;
+        self.parse_stake_history = false;
+        self.stake_cooldown_epochs = 0;
+        self.check_vote_activity = false;
+        self.max_idle_epochs = 0;
+        self.max_stake_operations_per_epoch = 0;
+        self.stake_lockup_epoch = 0;
+        self.stake_lockup_unix_timestamp = 0;
+        self.max_stake_per_account = 0;
+        self.stake_rounding_lamports = 0;
+        self.max_stake_per_call = 0;
+        self.min_stake_delta_to_act = 0;
+        self.new_validator_ramp = Fee::from_basis_points(Fee::MAX_BASIS_POINTS);
+        self.pool_allocations = [Fee::from_basis_points(0); MAX_VALIDATOR_POOLS];
+        // the migrated validator records are in the default pool
+        self.pool_scores = [0; MAX_VALIDATOR_POOLS];
+        self.pool_scores[0] = self.validator_system.total_validator_score;
+        self.stake_operations_this_epoch = 0;
+        self.stake_operations_epoch = 0;
+        // the records can be in any pool, recompute_pool_scores before enabling the allocations
+        self.pool_scores_valid = false;
+        self.recompute_pool_scores_next_index = 0;
+        self.recompute_pool_scores_sums = [0; MAX_VALIDATOR_POOLS];
+        self.selection_seed = 0;
+    }
+
     pub fn find_msol_mint_authority(state: &Pubkey) -> (Pubkey, u8) {
         Pubkey::find_program_address(
             &[&state.to_bytes()[..32], State::MSOL_MINT_AUTHORITY_SEED],
@@ -203,6 +445,42 @@ impl State {
         Ok(())
     }
 
+    /// Amount stake_reserve delegates to a validator under its target (before the min stake check):
+    /// stake_target = validator_target - validator_actual_balance, at most total_stake_delta.
+    /// If what's left in stake_delta after this operation is < min_stake, take all the remainder from stake_delta.
+    /// The cap is applied after the remainder is taken so total_active_balance never exceeds it.
+    /// Staking is spread across the stake-delta window: only the allowed share of the epoch delta
+    /// (staked in this epoch + still to stake) can be deployed by now
+    pub fn stake_reserve_target(
+        &self,
+        validator_stake_target: u64,
+        validator_active_balance: u64,
+        total_stake_delta: u64,
+        clock: &Clock,
+        last_slot_in_epoch: u64,
+    ) -> u64 {
+        let stake_target = self
+            .stake_reserve_amount(
+                validator_stake_target,
+                validator_active_balance,
+                total_stake_delta,
+            )
+            .min(self.total_active_balance_room());
+
+        let staked_in_epoch = self.stake_reserve_report.staked_in_epoch(clock.epoch);
+        let stake_window_allowance = self
+            .stake_window_allowance(clock.slot, last_slot_in_epoch)
+            .apply(total_stake_delta + staked_in_epoch)
+            .saturating_sub(staked_in_epoch);
+        stake_target.min(stake_window_allowance)
+    }
+
+    /// how much stake_reserve can delegate until reaching max_total_active_balance
+    pub fn total_active_balance_room(&self) -> u64 {
+        self.max_total_active_balance
+            .saturating_sub(self.validator_system.total_active_balance)
+    }
+
     pub fn total_virtual_staked_lamports(&self) -> u64 {
         // if we get slashed it may be negative but we must use 0 instead
         self.total_lamports_under_control()
@@ -227,6 +505,36 @@ impl State {
         )
     }
 
+    pub fn is_paused_for(&self, pause_flag: u8) -> bool {
+        self.pause_flags & pause_flag != 0
+    }
+
+    pub fn preferred_validators(&self) -> &[u32] {
+        &self.preferred_validators[..self.preferred_validator_count as usize]
+    }
+
+    pub fn crank_authorities(&self) -> &[Pubkey] {
+        &self.crank_authorities[..self.crank_authority_count as usize]
+    }
+
+    /// Ok when the crank is permissionless or one of the signers is allowed
+    pub fn check_crank_authority(&self, signers: &[&Pubkey]) -> Result<()> {
+        let crank_authorities = self.crank_authorities();
+        if crank_authorities.is_empty()
+            || signers
+                .iter()
+                .any(|signer| crank_authorities.contains(signer))
+        {
+            Ok(())
+        } else {
+            msg!(
+                "No allowed crank authority signed, {} allowed",
+                crank_authorities.len()
+            );
+            err!(MarinadeError::UnauthorizedCrank)
+        }
+    }
+
     // **i128**: when do staking/unstaking use real reserve balance instead of virtual field
     pub fn stake_delta(&self, reserve_balance: u64) -> i128 {
         // Never try to stake lamports from emergency_cooling_down
@@ -236,12 +544,16 @@ impl State {
         // preventing unstake duplication by recalculating stake-delta for negative values
 
         // OK. Lets get stake_delta without emergency first
+        // (i128 holds the sum of the u64 terms, the result may not fit in u64 though:
+        // convert it with u64_from_i128)
         let raw = reserve_balance.saturating_sub(self.rent_exempt_for_token_acc) as i128
             + self.stake_system.delayed_unstake_cooling_down as i128
             - self.circulating_ticket_balance as i128;
         if raw >= 0 {
             // When it >= 0 it is right value to use
-            raw
+            // keeping the retention buffer in the reserve.
+            // The buffer is not an obligation: it stops staking but does not trigger unstaking
+            (raw - self.reserve_retention_lamports as i128).max(0)
         } else {
             // Otherwise try to recalculate it with emergency
             let with_emergency = raw + self.emergency_cooling_down as i128;
@@ -250,12 +562,113 @@ impl State {
         }
     }
 
+    /// stake_delta limited by the part of the reserve selected by reserve_stake_source
+    pub fn stake_reserve_delta(&self, reserve_balance: u64) -> i128 {
+        let stake_delta = self.stake_delta(reserve_balance);
+        if stake_delta <= 0 {
+            return stake_delta;
+        }
+        match self.reserve_stake_source {
+            ReserveStakeSource::Combined => stake_delta,
+            ReserveStakeSource::Principal => {
+                stake_delta.min(self.reserve_principal_balance() as i128)
+            }
+            ReserveStakeSource::Rewards => stake_delta.min(self.reserve_rewards_balance as i128),
+        }
+    }
+
+    /// total active balance after deploying the positive stake delta
+    pub fn total_stake_target(&self, reserve_balance: u64) -> Result<u64> {
+        let stake_delta = self.stake_reserve_delta(reserve_balance);
+        Ok(self
+            .validator_system
+            .total_active_balance
+            .saturating_add(u64_from_i128(stake_delta.max(0))?))
+    }
+
+    /// lowest reserve_pda balance a stake_reserve transfer may leave:
+    /// the rent exempt minimum of the system account plus the retention buffer
+    pub fn reserve_floor(&self, rent: &Rent) -> u64 {
+        rent.minimum_balance(0)
+            .saturating_add(self.reserve_retention_lamports)
+    }
+
+    /// stake_delta keeps rent_exempt_for_token_acc in the reserve,
+    /// this also holds if rent_exempt_for_token_acc is misconfigured
+    pub fn check_reserve_transfer(
+        &self,
+        reserve_balance: u64,
+        amount: u64,
+        rent: &Rent,
+    ) -> Result<()> {
+        require_gte!(
+            reserve_balance.saturating_sub(amount),
+            self.reserve_floor(rent),
+            MarinadeError::ReserveWouldNotBeRentExempt
+        );
+        Ok(())
+    }
+
+    pub fn reserve_principal_balance(&self) -> u64 {
+        self.available_reserve_balance
+            .saturating_sub(self.reserve_rewards_balance)
+    }
+
     pub fn on_transfer_to_reserve(&mut self, amount: u64) {
         self.available_reserve_balance += amount
     }
 
-    pub fn on_transfer_from_reserve(&mut self, amount: u64) {
-        self.available_reserve_balance -= amount
+    /// call after on_transfer_to_reserve for the rewards part of the transfer
+    pub fn on_rewards_to_reserve(&mut self, amount: u64) {
+        self.reserve_rewards_balance =
+            (self.reserve_rewards_balance + amount).min(self.available_reserve_balance)
+    }
+
+    /// Emits ReserveTransferEvent. Returns the new available_reserve_balance
+    pub fn on_transfer_from_reserve(
+        &mut self,
+        state_address: &Pubkey,
+        amount: u64,
+        epoch: u64,
+    ) -> Result<u64> {
+        self.available_reserve_balance = self
+            .available_reserve_balance
+            .checked_sub(amount)
+            .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+        self.reserve_low_water_lamports = self
+            .reserve_low_water_lamports
+            .min(self.available_reserve_balance);
+        self.on_reserve_balance_changed();
+        emit!(ReserveTransferEvent {
+            state: *state_address,
+            epoch,
+            amount,
+            available_reserve_balance: self.available_reserve_balance,
+        });
+        Ok(self.available_reserve_balance)
+    }
+
+    /// stake_reserve takes the lamports from the selected part of the reserve
+    pub fn on_stake_from_reserve(
+        &mut self,
+        state_address: &Pubkey,
+        amount: u64,
+        epoch: u64,
+    ) -> Result<u64> {
+        if self.reserve_stake_source == ReserveStakeSource::Rewards {
+            self.reserve_rewards_balance = self
+                .reserve_rewards_balance
+                .checked_sub(amount)
+                .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+        }
+        self.on_transfer_from_reserve(state_address, amount, epoch)
+    }
+
+    /// keeps reserve_rewards_balance <= available_reserve_balance
+    pub fn on_reserve_balance_changed(&mut self) {
+        self.reserve_rewards_balance = self
+            .reserve_rewards_balance
+            .min(self.available_reserve_balance);
     }
 
     pub fn on_msol_mint(&mut self, amount: u64) {
@@ -281,3 +694,207 @@ impl State {
         Ok(())
     }
 }
+
+#[cfg(test)]
+impl State {
+    /// State of the unit tests with the defaults of initialize
+    pub fn for_tests(stake_system: StakeSystem, validator_system: ValidatorSystem) -> Self {
+        let mut pool_scores = [0; MAX_VALIDATOR_POOLS];
+        pool_scores[0] = validator_system.total_validator_score;
+        State {
+            msol_mint: Pubkey::new_unique(),
+            admin_authority: Pubkey::new_unique(),
+            operational_sol_account: Pubkey::new_unique(),
+            treasury_msol_account: Pubkey::new_unique(),
+            reserve_bump_seed: 0,
+            msol_mint_authority_bump_seed: 0,
+            rent_exempt_for_token_acc: 2_039_280,
+            reward_fee: Fee::from_basis_points(0),
+            stake_system,
+            validator_system,
+            liq_pool: LiqPool {
+                lp_mint: Pubkey::new_unique(),
+                lp_mint_authority_bump_seed: 0,
+                sol_leg_bump_seed: 0,
+                msol_leg_authority_bump_seed: 0,
+                msol_leg: Pubkey::new_unique(),
+                lp_liquidity_target: LiqPool::MIN_LIQUIDITY_TARGET,
+                lp_max_fee: Fee::from_basis_points(300),
+                lp_min_fee: Fee::from_basis_points(30),
+                treasury_cut: Fee::from_basis_points(2500),
+                lp_supply: 0,
+                lent_from_sol_leg: 0,
+                liquidity_sol_cap: std::u64::MAX,
+            },
+            available_reserve_balance: 0,
+            msol_supply: 0,
+            msol_price: State::PRICE_DENOMINATOR,
+            circulating_ticket_count: 0,
+            circulating_ticket_balance: 0,
+            lent_from_reserve: 0,
+            min_deposit: 1,
+            min_withdraw: 1,
+            staking_sol_cap: std::u64::MAX,
+            emergency_cooling_down: 0,
+            pause_authority: Pubkey::new_unique(),
+            paused: false,
+            delayed_unstake_fee: FeeCents::from_bp_cents(0),
+            withdraw_stake_account_fee: FeeCents::from_bp_cents(0),
+            withdraw_stake_account_enabled: false,
+            last_stake_move_epoch: 0,
+            stake_moved: 0,
+            max_stake_moved_per_epoch: Fee::from_basis_points(10000),
+            layout_version: State::LAYOUT_VERSION,
+            max_total_active_balance: std::u64::MAX,
+            reserve_rewards_balance: 0,
+            reserve_stake_source: ReserveStakeSource::Combined,
+            reserve_retention_lamports: 0,
+            reserve_low_water_lamports: std::u64::MAX,
+            pause_flags: 0,
+            invariant_checks: false,
+            reconcile_next_validator_index: 0,
+            reconcile_active_balance_sum: 0,
+            preferred_validators: [0; MAX_PREFERRED_VALIDATORS],
+            preferred_validator_count: 0,
+            crank_authorities: [Pubkey::default(); MAX_CRANK_AUTHORITIES],
+            crank_authority_count: 0,
+            score_oracle: Pubkey::default(),
+            sort_stakes_by_validator: false,
+            sorted_stake_count: 0,
+            stake_reserve_report: StakeReserveReport::default(),
+            stake_reserve_last_status: StakeReserveLastStatus::default(),
+            check_vote_rent_exempt: false,
+            stake_window_start_allowance: Fee::from_basis_points(Fee::MAX_BASIS_POINTS),
+            stake_over_target: false,
+            max_extra_stake_delta_runs: std::u32::MAX,
+            max_stake_accounts_per_validator: 0,
+            parse_stake_history: false,
+            stake_cooldown_epochs: 0,
+            check_vote_activity: false,
+            max_idle_epochs: 0,
+            max_stake_operations_per_epoch: 0,
+            stake_lockup_epoch: 0,
+            stake_lockup_unix_timestamp: 0,
+            max_stake_per_account: 0,
+            stake_rounding_lamports: 0,
+            max_stake_per_call: 0,
+            min_stake_delta_to_act: 0,
+            new_validator_ramp: Fee::from_basis_points(Fee::MAX_BASIS_POINTS),
+            pool_allocations: [Fee::from_basis_points(0); MAX_VALIDATOR_POOLS],
+            pool_scores,
+            stake_operations_this_epoch: 0,
+            stake_operations_epoch: 0,
+            pool_scores_valid: false,
+            recompute_pool_scores_next_index: 0,
+            recompute_pool_scores_sums: [0; MAX_VALIDATOR_POOLS],
+            selection_seed: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_layout_v0() -> Result<()> {
+        let mut stake_list_data = [0; 8];
+        let mut validator_list_data = [0; 8];
+        let mut validator_system = ValidatorSystem::new(
+            Pubkey::new_unique(),
+            &mut validator_list_data,
+            Pubkey::new_unique(),
+            0,
+        )?;
+        validator_system.total_validator_score = 300;
+        let mut state = State::for_tests(
+            StakeSystem::new(
+                &Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                &mut stake_list_data,
+                StakeSystem::MIN_UPDATE_WINDOW,
+                LAMPORTS_PER_SOL,
+                0,
+                0,
+            )?,
+            validator_system,
+        );
+        state.msol_price = 2 * State::PRICE_DENOMINATOR;
+        state.max_stake_moved_per_epoch = Fee::from_basis_points(123);
+        let mut data = vec![];
+        state.try_serialize(&mut data)?;
+        assert_eq!(data.len(), State::serialized_len());
+        // the appended fields start right after the first layout
+        assert_eq!(data[State::LAYOUT_V0_LEN], State::LAYOUT_VERSION);
+        assert_eq!(State::layout_version(&data)?, State::LAYOUT_VERSION);
+
+        // the account of the first layout: only the bytes of the old fields
+        let mut old_data = data[..State::LAYOUT_V0_LEN].to_vec();
+        assert_eq!(State::layout_version(&old_data)?, 0);
+        assert!(State::try_deserialize(&mut old_data.as_slice()).is_err());
+        assert!(State::migrate_layout(&mut old_data).is_err()); // not reallocated
+        assert!(State::layout_version(&old_data[..State::LAYOUT_V0_LEN - 1]).is_err());
+
+        old_data.resize(State::serialized_len(), 0);
+        assert_eq!(State::migrate_layout(&mut old_data)?, 0);
+        let migrated = State::try_deserialize(&mut old_data.as_slice())?;
+        assert_eq!(migrated.admin_authority, state.admin_authority);
+        assert_eq!(migrated.msol_price, 2 * State::PRICE_DENOMINATOR);
+        assert_eq!(
+            migrated.max_stake_moved_per_epoch,
+            Fee::from_basis_points(123)
+        );
+        // the defaults of initialize, the validators are in the default pool
+        assert_eq!(migrated.pool_scores[0], 300);
+        assert!(!migrated.pool_scores_valid);
+        assert_eq!(old_data, data);
+        // already migrated
+        assert!(State::migrate_layout(&mut old_data).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_crank_authority() -> Result<()> {
+        let mut stake_list_data = [0; 8];
+        let mut validator_list_data = [0; 8];
+        let mut state = State::for_tests(
+            StakeSystem::new(
+                &Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                &mut stake_list_data,
+                StakeSystem::MIN_UPDATE_WINDOW,
+                LAMPORTS_PER_SOL,
+                0,
+                0,
+            )?,
+            ValidatorSystem::new(
+                Pubkey::new_unique(),
+                &mut validator_list_data,
+                Pubkey::new_unique(),
+                0,
+            )?,
+        );
+        let signer = Pubkey::new_unique();
+        let crank_authority = Pubkey::new_unique();
+        // permissionless
+        state.check_crank_authority(&[&signer])?;
+        state.crank_authorities[0] = crank_authority;
+        state.crank_authority_count = 1;
+        assert_eq!(
+            state.check_crank_authority(&[&signer]).err(),
+            Some(error!(MarinadeError::UnauthorizedCrank))
+        );
+        // rent_payer or cranker
+        state.check_crank_authority(&[&signer, &crank_authority])?;
+        state.check_crank_authority(&[&crank_authority, &signer])?;
+        // only the first crank_authority_count keys are allowed
+        state.crank_authorities[1] = signer;
+        assert_eq!(
+            state.check_crank_authority(&[&signer]).err(),
+            Some(error!(MarinadeError::UnauthorizedCrank))
+        );
+        state.crank_authority_count = 2;
+        state.check_crank_authority(&[&signer])?;
+        Ok(())
+    }
+}
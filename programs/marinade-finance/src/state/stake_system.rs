@@ -1,9 +1,9 @@
 use crate::error::MarinadeError;
-use crate::ID;
-use anchor_lang::solana_program::clock::Epoch;
+use crate::{require_lt, ID};
+use anchor_lang::solana_program::{clock::Epoch, stake::state::Lockup};
 use anchor_lang::{prelude::*, Discriminator};
 
-use super::list::List;
+use super::{list::List, validator_system::ValidatorRecord, Fee, State};
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
 pub struct StakeRecord {
@@ -11,11 +11,21 @@ pub struct StakeRecord {
     pub last_update_delegated_lamports: u64,
     pub last_update_epoch: u64,
     pub is_emergency_unstaking: u8, // 1 for cooling down after emergency unstake, 0 otherwise
+
+    /// Validator vote account the stake was delegated to when added to the list.
+    /// Default pubkey for records migrated from the older layout until sort_stake_list fills it
+    pub validator: Pubkey,
+    /// Bot which created the stake by stake_reserve (for crank rewards and audit).
+    /// Default pubkey when no cranker was designated
+    pub cranker: Pubkey,
+    /// 1 after confirm_delegation found the stake delegated to `validator`, 0 otherwise
+    pub delegation_confirmed: u8,
 }
 
 impl StakeRecord {
     pub fn new(
         stake_account: &Pubkey,
+        validator: &Pubkey,
         delegated_lamports: u64,
         clock: &Clock,
         is_emergency_unstaking: u8,
@@ -25,8 +35,109 @@ impl StakeRecord {
             last_update_delegated_lamports: delegated_lamports,
             last_update_epoch: clock.epoch,
             is_emergency_unstaking,
+            validator: *validator,
+            cranker: Pubkey::default(),
+            delegation_confirmed: 0,
+        }
+    }
+
+    pub fn serialized_len() -> u32 {
+        Self::default().try_to_vec().unwrap().len() as u32
+    }
+}
+
+/// stake_reserve counters of one epoch
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct StakeReserveReport {
+    pub epoch: u64,
+    pub total_staked: u64,
+    pub stake_accounts_created: u32,
+    pub validators_touched: u32,
+    // no-op calls by reason
+    pub noop_nothing_to_stake: u32,
+    pub noop_double_stake_delta: u32,
+    pub noop_validator_on_target: u32,
+    pub noop_below_min_stake: u32,
+    pub noop_global_cap_reached: u32,
+    pub noop_all_validators_at_target: u32,
+    pub noop_validator_cooldown: u32,
+    pub noop_validator_delinquent: u32,
+    pub noop_throttled: u32,
+    pub noop_delta_below_action_threshold: u32,
+}
+
+impl StakeReserveReport {
+    pub fn staked_in_epoch(&self, epoch: u64) -> u64 {
+        if self.epoch == epoch {
+            self.total_staked
+        } else {
+            0
+        }
+    }
+
+    pub fn on_staked(&mut self, amount: u64, first_stake_delta_in_epoch: bool) {
+        self.total_staked += amount;
+        self.stake_accounts_created += 1;
+        if first_stake_delta_in_epoch {
+            self.validators_touched += 1;
         }
     }
+
+    /// counters of the epoch, resets them when the epoch is changed
+    pub fn for_epoch(&mut self, epoch: u64) -> &mut Self {
+        if self.epoch != epoch {
+            *self = Self {
+                epoch,
+                ..Default::default()
+            };
+        }
+        self
+    }
+}
+
+/// Outcome of a stake_reserve call
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum StakeReserveStatus {
+    #[default]
+    None, // never called
+    Staked,
+    NothingToStake,
+    GlobalCapReached,
+    DoubleStakeDelta,
+    ValidatorOnTarget,
+    BelowMinStake,
+    AllValidatorsAtTarget,
+    ValidatorCooldown,
+    ValidatorDelinquent,
+    Throttled,
+    ValidatorDrainOnly,
+    DeltaBelowActionThreshold,
+}
+
+impl StakeReserveStatus {
+    /// error of the no-op in strict mode. None if the no-op is allowed
+    pub fn strict_error(&self) -> Option<MarinadeError> {
+        match self {
+            Self::NothingToStake | Self::DeltaBelowActionThreshold => {
+                Some(MarinadeError::NothingToStake)
+            }
+            Self::DoubleStakeDelta => Some(MarinadeError::DoubleStakeDelta),
+            Self::ValidatorOnTarget | Self::AllValidatorsAtTarget | Self::ValidatorDrainOnly => {
+                Some(MarinadeError::ValidatorAlreadyOnTarget)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The last recorded stake_reserve outcome.
+/// A failed call reverts all its state changes, so only the successful and no-op calls are recorded here.
+/// The reason of a failure is in the error code of the transaction
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct StakeReserveLastStatus {
+    pub status: StakeReserveStatus,
+    pub epoch: u64,
+    pub slot: u64,
 }
 
 #[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize)]
@@ -94,15 +205,37 @@ impl StakeSystem {
 
     pub fn bytes_for_list(count: u32, additional_record_space: u32) -> u32 {
         List::bytes_for(
-            StakeRecord::default().try_to_vec().unwrap().len() as u32 + additional_record_space,
+            StakeRecord::serialized_len() + additional_record_space,
             count,
         )
     }
 
+    /// rent-exempt minimum of a stake account by its actual data length
+    /// so it follows the account layout of the stake program
+    pub fn stake_account_rent_exempt(rent: &Rent, data_len: usize) -> u64 {
+        rent.minimum_balance(data_len)
+    }
+
     pub fn find_stake_withdraw_authority(state: &Pubkey) -> (Pubkey, u8) {
         Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::STAKE_WITHDRAW_SEED], &ID)
     }
 
+    pub fn stake_withdraw_authority(state: &Pubkey, bump: u8) -> Pubkey {
+        Pubkey::create_program_address(
+            &[&state.to_bytes()[..32], Self::STAKE_WITHDRAW_SEED, &[bump]],
+            &ID,
+        )
+        .unwrap()
+    }
+
+    pub fn stake_deposit_authority(state: &Pubkey, bump: u8) -> Pubkey {
+        Pubkey::create_program_address(
+            &[&state.to_bytes()[..32], Self::STAKE_DEPOSIT_SEED, &[bump]],
+            &ID,
+        )
+        .unwrap()
+    }
+
     pub fn find_stake_deposit_authority(state: &Pubkey) -> (Pubkey, u8) {
         Pubkey::find_program_address(&[&state.to_bytes()[..32], Self::STAKE_DEPOSIT_SEED], &ID)
     }
@@ -118,7 +251,7 @@ impl StakeSystem {
     ) -> Result<Self> {
         let stake_list = List::new(
             &StakeList::DISCRIMINATOR,
-            StakeRecord::default().try_to_vec().unwrap().len() as u32 + additional_record_space,
+            StakeRecord::serialized_len() + additional_record_space,
             stake_list_account,
             stake_list_data,
         )
@@ -148,30 +281,56 @@ impl StakeSystem {
         self.stake_list.capacity(stake_list_len)
     }
 
+    /// Some(capacity) if the last `added` stake records made the list cross the near capacity threshold
+    pub fn stake_list_crossed_near_capacity(
+        &self,
+        stake_list_len: usize,
+        added: u32,
+    ) -> Result<Option<u32>> {
+        self.stake_list.crossed_near_capacity(stake_list_len, added)
+    }
+
+    /// the validator may require bigger stake accounts than the global min_stake
+    pub fn validator_min_stake(&self, validator: &ValidatorRecord) -> u64 {
+        self.min_stake.max(validator.min_delegation)
+    }
+
+    /// first slot of the epoch stake_reserve accepts, before it fails with TooEarlyForStakeDelta
+    pub fn stake_window_open_slot(&self, last_slot_in_epoch: u64) -> u64 {
+        last_slot_in_epoch.saturating_sub(self.slots_for_stake_delta)
+    }
+
+    /// InvalidListAccount when the list header does not match the account data
+    /// or the records are not migrated to the current layout (realloc_stake_list)
+    pub fn check_stake_list(&self, stake_list_len: usize) -> Result<()> {
+        self.stake_list
+            .check_account(stake_list_len, StakeRecord::serialized_len())
+            .map_err(|e| e.with_account_name("stake_list"))
+    }
+
     pub fn stake_record_size(&self) -> u32 {
         self.stake_list.item_size()
     }
 
-    pub fn add(
-        &mut self,
-        stake_list_data: &mut [u8],
-        stake_account: &Pubkey,
-        delegated_lamports: u64,
-        clock: &Clock,
-        is_emergency_unstaking: u8,
-    ) -> Result<()> {
+    /// Record size of the list after migration to the current StakeRecord layout.
+    /// Lists created with enough additional_record_space keep their record size
+    pub fn upgraded_stake_record_size(&self) -> u32 {
+        self.stake_record_size().max(StakeRecord::serialized_len())
+    }
+
+    /// Moves the records apart when the list was created for an older (shorter) StakeRecord layout.
+    /// New fields of the migrated records are zeroed.
+    /// Returns the old record size if migration happened
+    pub fn upgrade_stake_record_size(&mut self, stake_list_data: &mut [u8]) -> Result<Option<u32>> {
+        let old = self.stake_record_size();
+        let new = self.upgraded_stake_record_size();
+        if new == old {
+            return Ok(None);
+        }
         self.stake_list
-            .push(
-                stake_list_data,
-                StakeRecord::new(
-                    stake_account,
-                    delegated_lamports,
-                    clock,
-                    is_emergency_unstaking,
-                ),
-            )
+            .set_item_size(stake_list_data, new)
             .map_err(|e| e.with_account_name("stake_list"))?;
-        Ok(())
+        Ok(Some(old))
     }
 
     fn get(&self, stake_list_data: &[u8], index: u32) -> Result<StakeRecord> {
@@ -180,6 +339,25 @@ impl StakeSystem {
             .map_err(|e| e.with_account_name("stake_list"))
     }
 
+    /// linear scan of the stake list, reads only the stake account of every record
+    pub fn contains_stake_account(
+        &self,
+        stake_list_data: &[u8],
+        stake_account: &Pubkey,
+    ) -> Result<bool> {
+        for index in 0..self.stake_count() {
+            // stake_account is the first field of StakeRecord
+            let record_stake_account: Pubkey = self
+                .stake_list
+                .get(stake_list_data, index)
+                .map_err(|e| e.with_account_name("stake_list"))?;
+            if record_stake_account == *stake_account {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     /// get the stake account record from an index, and check that the account is the same passed as parameter to the instruction
     pub fn get_checked(
         &self,
@@ -201,9 +379,638 @@ impl StakeSystem {
             .set(stake_list_data, index, stake)
             .map_err(|e| e.with_account_name("stake_list"))
     }
-    pub fn remove(&mut self, stake_list_data: &mut [u8], index: u32) -> Result<()> {
-        self.stake_list
-            .remove(stake_list_data, index)
-            .map_err(|e| e.with_account_name("stake_list"))
+}
+
+// stake system logic using the settings appended to the State layout
+impl State {
+    /// the validator already has max_stake_accounts_per_validator active stake accounts
+    /// (counted by ValidatorRecord::stake_accounts_count), stake_reserve must not create another one
+    /// until some of them are merged or deactivated. Never reached when the limit is 0
+    pub fn stake_accounts_limit_reached(&self, validator: &ValidatorRecord) -> bool {
+        self.max_stake_accounts_per_validator != 0
+            && validator.stake_accounts_count >= self.max_stake_accounts_per_validator
+    }
+
+    /// TooManyStakeAccounts when new_stake_accounts more would exceed max_stake_accounts_per_validator
+    pub fn check_stake_accounts_limit(
+        &self,
+        validator: &ValidatorRecord,
+        new_stake_accounts: u32,
+    ) -> Result<()> {
+        if self.max_stake_accounts_per_validator != 0
+            && validator
+                .stake_accounts_count
+                .saturating_add(new_stake_accounts)
+                > self.max_stake_accounts_per_validator
+        {
+            msg!(
+                "Validator {} has {} stake accounts, {} more requested. Merge them before staking more",
+                validator.validator_account,
+                validator.stake_accounts_count,
+                new_stake_accounts
+            );
+            return Err(error!(MarinadeError::TooManyStakeAccounts).with_values((
+                validator.stake_accounts_count,
+                self.max_stake_accounts_per_validator,
+            )));
+        }
+        Ok(())
+    }
+
+    /// Amount stake_reserve delegates to a validator:
+    /// validator_target - validator_actual_balance, at most total_stake_delta,
+    /// rounded down by stake_rounding_lamports.
+    /// Takes all the total_stake_delta if the remainder would be < min_stake.
+    /// The caller must skip the amounts lower than validator_min_stake
+    pub fn stake_reserve_amount(
+        &self,
+        validator_stake_target: u64,
+        validator_active_balance: u64,
+        total_stake_delta: u64,
+    ) -> u64 {
+        let stake_target = self.round_stake_amount(
+            validator_stake_target
+                .saturating_sub(validator_active_balance)
+                .min(total_stake_delta),
+        );
+        if total_stake_delta - stake_target < self.stake_system.min_stake {
+            total_stake_delta
+        } else {
+            stake_target
+        }
+    }
+
+    /// stake target of stake_reserve: ramped by new_validator_ramp in the epoch the validator was added
+    pub fn ramped_stake_target(
+        &self,
+        validator: &ValidatorRecord,
+        validator_stake_target: u64,
+        epoch: u64,
+    ) -> u64 {
+        if validator.added_epoch == epoch {
+            self.new_validator_ramp.apply(validator_stake_target)
+        } else {
+            validator_stake_target
+        }
+    }
+
+    /// nothing to stake or the delta is not worth a stake_reserve transaction
+    pub fn stake_delta_below_action_threshold(&self, stake_delta: u64) -> bool {
+        stake_delta == 0 || stake_delta < self.min_stake_delta_to_act
+    }
+
+    /// max_stake_per_call, u64::MAX when not set.
+    /// Applied after the remainder rule: a capped stake doesn't take the remainder
+    pub fn stake_per_call_cap(&self) -> u64 {
+        if self.max_stake_per_call == 0 {
+            std::u64::MAX
+        } else {
+            self.max_stake_per_call
+        }
+    }
+
+    /// amount rounded down to a multiple of stake_rounding_lamports,
+    /// unchanged if rounding would take it below min_stake
+    pub fn round_stake_amount(&self, amount: u64) -> u64 {
+        if self.stake_rounding_lamports == 0 {
+            return amount;
+        }
+        let rounded = amount - amount % self.stake_rounding_lamports;
+        if rounded < self.stake_system.min_stake {
+            amount
+        } else {
+            rounded
+        }
+    }
+
+    /// the validator got a stake delta (stake or unstake) in one of the last stake_cooldown_epochs epochs.
+    /// The current epoch is left to the double stake delta check
+    pub fn in_stake_cooldown(&self, validator: &ValidatorRecord, epoch: u64) -> bool {
+        validator.last_stake_delta_epoch < epoch
+            && validator
+                .last_stake_delta_epoch
+                .saturating_add(self.stake_cooldown_epochs)
+                > epoch
+    }
+
+    /// new_stake_operations more stake accounts would exceed max_stake_operations_per_epoch in the epoch
+    pub fn stake_operations_throttled(&self, epoch: u64, new_stake_operations: u64) -> bool {
+        self.max_stake_operations_per_epoch != 0
+            && (self.stake_operations_in_epoch(epoch) as u64).saturating_add(new_stake_operations)
+                > self.max_stake_operations_per_epoch as u64
+    }
+
+    pub fn stake_operations_in_epoch(&self, epoch: u64) -> u32 {
+        if self.stake_operations_epoch == epoch {
+            self.stake_operations_this_epoch
+        } else {
+            0
+        }
+    }
+
+    /// counts the stake accounts delegated by stake_reserve, resets the counter in a new epoch
+    pub fn on_stake_operations(&mut self, epoch: u64, stake_operations: u64) {
+        self.stake_operations_this_epoch = self
+            .stake_operations_in_epoch(epoch)
+            .saturating_add(stake_operations as u32);
+        self.stake_operations_epoch = epoch;
+    }
+
+    /// number of the stake accounts to split stake_target into by max_stake_per_account,
+    /// never making a part lower than min_stake
+    pub fn stake_chunks(&self, stake_target: u64, min_stake: u64) -> u64 {
+        if self.max_stake_per_account == 0 {
+            return 1;
+        }
+        let chunks = stake_target.div_ceil(self.max_stake_per_account);
+        chunks.min(stake_target / min_stake.max(1)).max(1)
+    }
+
+    /// lockup for the new stake accounts. `withdrawer` is the stake withdraw authority PDA
+    pub fn stake_lockup(&self, withdrawer: &Pubkey) -> Lockup {
+        if self.stake_lockup_epoch == 0 && self.stake_lockup_unix_timestamp == 0 {
+            Lockup::default()
+        } else {
+            Lockup {
+                unix_timestamp: self.stake_lockup_unix_timestamp,
+                epoch: self.stake_lockup_epoch,
+                custodian: *withdrawer,
+            }
+        }
+    }
+
+    /// records the outcome of stake_reserve and counts no-ops in the epoch report
+    pub fn on_stake_reserve_status(&mut self, status: StakeReserveStatus, clock: &Clock) {
+        self.stake_reserve_last_status = StakeReserveLastStatus {
+            status,
+            epoch: clock.epoch,
+            slot: clock.slot,
+        };
+        let report = self.stake_reserve_report.for_epoch(clock.epoch);
+        match status {
+            StakeReserveStatus::NothingToStake => report.noop_nothing_to_stake += 1,
+            StakeReserveStatus::GlobalCapReached => report.noop_global_cap_reached += 1,
+            StakeReserveStatus::DoubleStakeDelta => report.noop_double_stake_delta += 1,
+            // the drain-only validator is handled as on target
+            StakeReserveStatus::ValidatorOnTarget | StakeReserveStatus::ValidatorDrainOnly => {
+                report.noop_validator_on_target += 1
+            }
+            StakeReserveStatus::BelowMinStake => report.noop_below_min_stake += 1,
+            StakeReserveStatus::AllValidatorsAtTarget => report.noop_all_validators_at_target += 1,
+            StakeReserveStatus::ValidatorCooldown => report.noop_validator_cooldown += 1,
+            StakeReserveStatus::ValidatorDelinquent => report.noop_validator_delinquent += 1,
+            StakeReserveStatus::Throttled => report.noop_throttled += 1,
+            StakeReserveStatus::DeltaBelowActionThreshold => {
+                report.noop_delta_below_action_threshold += 1
+            }
+            // not no-ops
+            StakeReserveStatus::None | StakeReserveStatus::Staked => {}
+        }
+    }
+
+    /// share of the epoch stake delta allowed to be staked at the slot of the stake-delta window
+    pub fn stake_window_allowance(&self, slot: u64, last_slot_in_epoch: u64) -> Fee {
+        let start = self.stake_window_start_allowance.basis_points;
+        if start >= Fee::MAX_BASIS_POINTS || self.stake_system.slots_for_stake_delta == 0 {
+            return Fee::from_basis_points(Fee::MAX_BASIS_POINTS);
+        }
+        let window_start = self.stake_system.stake_window_open_slot(last_slot_in_epoch);
+        let window_passed = slot
+            .saturating_sub(window_start)
+            .min(self.stake_system.slots_for_stake_delta);
+        let ramp = (Fee::MAX_BASIS_POINTS - start) as u64 * window_passed
+            / self.stake_system.slots_for_stake_delta;
+        Fee::from_basis_points(start + ramp as u32)
+    }
+
+    pub fn is_stake_list_sorted(&self) -> bool {
+        self.sorted_stake_count == self.stake_system.stake_count()
+    }
+
+    /// index after the last record of the validator in the sorted part of the list
+    fn sorted_stake_insert_index(&self, stake_list_data: &[u8], validator: &Pubkey) -> Result<u32> {
+        let mut low = 0;
+        let mut high = self.sorted_stake_count;
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.stake_system.get(stake_list_data, middle)?.validator <= *validator {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        Ok(low)
+    }
+
+    /// Moves the first unsorted record into its place in the sorted part of the list
+    pub fn sort_next_stake(
+        &mut self,
+        stake_list_data: &mut [u8],
+        record: StakeRecord,
+    ) -> Result<u32> {
+        require_lt!(
+            self.sorted_stake_count,
+            self.stake_system.stake_count(),
+            MarinadeError::ListIndexOutOfBounds
+        );
+        self.stake_system
+            .stake_list
+            .remove_ordered(stake_list_data, self.sorted_stake_count)
+            .map_err(|e| e.with_account_name("stake_list"))?;
+        let index = self.sorted_stake_insert_index(stake_list_data, &record.validator)?;
+        self.stake_system
+            .stake_list
+            .insert(stake_list_data, index, record)
+            .map_err(|e| e.with_account_name("stake_list"))?;
+        self.sorted_stake_count += 1;
+        Ok(index)
+    }
+
+    /// Returns the index of the new record.
+    /// Warn: in sorted mode the record can be inserted in the middle of the list
+    /// moving forward the records with greater indexes.
+    /// It is inserted after all the records of the same validator,
+    /// so adding a split of a stake never moves the source stake record
+    pub fn add_stake(
+        &mut self,
+        stake_list_data: &mut [u8],
+        stake_account: &Pubkey,
+        validator: &Pubkey,
+        delegated_lamports: u64,
+        clock: &Clock,
+        is_emergency_unstaking: u8,
+    ) -> Result<u32> {
+        self.add_stake_record(
+            stake_list_data,
+            StakeRecord::new(
+                stake_account,
+                validator,
+                delegated_lamports,
+                clock,
+                is_emergency_unstaking,
+            ),
+        )
+    }
+
+    /// Same as add but for the prepared record
+    pub fn add_stake_record(
+        &mut self,
+        stake_list_data: &mut [u8],
+        record: StakeRecord,
+    ) -> Result<u32> {
+        let validator = &record.validator;
+        if self.sort_stakes_by_validator && self.is_stake_list_sorted() {
+            let index = self.sorted_stake_insert_index(stake_list_data, validator)?;
+            self.stake_system
+                .stake_list
+                .insert(stake_list_data, index, record)
+                .map_err(|e| e.with_account_name("stake_list"))?;
+            self.sorted_stake_count += 1;
+            Ok(index)
+        } else {
+            self.stake_system
+                .stake_list
+                .push(stake_list_data, record)
+                .map_err(|e| e.with_account_name("stake_list"))?;
+            Ok(self.stake_system.stake_count() - 1)
+        }
+    }
+    /// Warn: in sorted mode all the records after `index` move one position back,
+    /// otherwise only the last record moves into `index`
+    pub fn remove_stake(&mut self, stake_list_data: &mut [u8], index: u32) -> Result<()> {
+        if self.sort_stakes_by_validator {
+            self.stake_system
+                .stake_list
+                .remove_ordered(stake_list_data, index)
+                .map_err(|e| e.with_account_name("stake_list"))?;
+            if index < self.sorted_stake_count {
+                self.sorted_stake_count -= 1;
+            }
+        } else {
+            // the last record is moved into the removed place
+            self.stake_system
+                .stake_list
+                .remove(stake_list_data, index)
+                .map_err(|e| e.with_account_name("stake_list"))?;
+            self.sorted_stake_count = self.sorted_stake_count.min(index);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::*;
+    use anchor_lang::solana_program::{native_token::LAMPORTS_PER_SOL, stake::state::Lockup};
+
+    use super::{StakeRecord, StakeReserveStatus, StakeSystem};
+    use crate::{
+        error::MarinadeError,
+        state::{
+            list::List,
+            validator_system::{ValidatorRecord, ValidatorSystem},
+            State,
+        },
+    };
+
+    /// min_stake of 1 SOL
+    fn test_state() -> Result<State> {
+        let mut stake_list_data = [0; 8];
+        let mut validator_list_data = [0; 8];
+        Ok(State::for_tests(
+            StakeSystem::new(
+                &Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                &mut stake_list_data,
+                1000,
+                LAMPORTS_PER_SOL,
+                0,
+                0,
+            )?,
+            ValidatorSystem::new(
+                Pubkey::new_unique(),
+                &mut validator_list_data,
+                Pubkey::new_unique(),
+                0,
+            )?,
+        ))
+    }
+
+    #[test]
+    fn test_stake_authorities() {
+        let state = Pubkey::new_unique();
+        let (deposit_authority, deposit_bump) = StakeSystem::find_stake_deposit_authority(&state);
+        assert_eq!(
+            StakeSystem::stake_deposit_authority(&state, deposit_bump),
+            deposit_authority
+        );
+        let (withdraw_authority, withdraw_bump) =
+            StakeSystem::find_stake_withdraw_authority(&state);
+        assert_eq!(
+            StakeSystem::stake_withdraw_authority(&state, withdraw_bump),
+            withdraw_authority
+        );
+        assert_ne!(deposit_authority, withdraw_authority);
+    }
+
+    #[test]
+    fn test_stake_reserve_amount_delta_below_min_stake() -> Result<()> {
+        let state = test_state()?;
+        // the remainder takes all the delta, even when the delta itself is lower than min stake,
+        // so stake_reserve must check the delta against min stake before staking
+        let delta = LAMPORTS_PER_SOL / 2;
+        let amount = state.stake_reserve_amount(10 * LAMPORTS_PER_SOL, 0, delta);
+        assert_eq!(amount, delta);
+        assert!(amount < state.stake_system.min_stake);
+        // the delta above min stake is staked up to the validator target
+        assert_eq!(
+            state.stake_reserve_amount(3 * LAMPORTS_PER_SOL, 0, 10 * LAMPORTS_PER_SOL),
+            3 * LAMPORTS_PER_SOL
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stake_reserve_amount_edges() -> Result<()> {
+        let state = test_state()?;
+        let min_stake = state.stake_system.min_stake;
+        // zero delta
+        assert_eq!(state.stake_reserve_amount(10 * min_stake, 0, 0), 0);
+        // on target or over it: the gap is 0, the delta below min stake goes in full
+        assert_eq!(
+            state.stake_reserve_amount(min_stake, 2 * min_stake, min_stake / 2),
+            min_stake / 2
+        );
+        // ... the delta of min stake and more is left for the other validators
+        assert_eq!(
+            state.stake_reserve_amount(min_stake, 2 * min_stake, min_stake),
+            0
+        );
+        // the gap is clamped by the delta
+        assert_eq!(
+            state.stake_reserve_amount(10 * min_stake, min_stake, 3 * min_stake),
+            3 * min_stake
+        );
+        // the remainder of exactly min stake is left for the next call
+        assert_eq!(
+            state.stake_reserve_amount(3 * min_stake, 0, 4 * min_stake),
+            3 * min_stake
+        );
+        // one lamport less: the remainder is folded
+        assert_eq!(
+            state.stake_reserve_amount(3 * min_stake, 0, 4 * min_stake - 1),
+            4 * min_stake - 1
+        );
+        // the gap itself below min stake is returned as is, the caller skips it
+        assert_eq!(
+            state.stake_reserve_amount(min_stake / 2, 0, 10 * min_stake),
+            min_stake / 2
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stake_reserve_amount_rounding() -> Result<()> {
+        let mut state = test_state()?;
+        let half = LAMPORTS_PER_SOL / 2;
+        // 0 disables rounding
+        assert_eq!(
+            state.stake_reserve_amount(3 * LAMPORTS_PER_SOL + half, 0, 10 * LAMPORTS_PER_SOL),
+            3 * LAMPORTS_PER_SOL + half
+        );
+        state.stake_rounding_lamports = LAMPORTS_PER_SOL;
+        assert_eq!(
+            state.stake_reserve_amount(3 * LAMPORTS_PER_SOL + half, 0, 10 * LAMPORTS_PER_SOL),
+            3 * LAMPORTS_PER_SOL
+        );
+        // the remainder of the rounding is left for the next call
+        assert_eq!(
+            state.stake_reserve_amount(
+                9 * LAMPORTS_PER_SOL + half,
+                0,
+                10 * LAMPORTS_PER_SOL + half
+            ),
+            9 * LAMPORTS_PER_SOL
+        );
+        // the remainder of the rounding below min stake: takes all the delta, not rounded
+        assert_eq!(
+            state.stake_reserve_amount(
+                10 * LAMPORTS_PER_SOL + half / 2,
+                0,
+                10 * LAMPORTS_PER_SOL + half
+            ),
+            10 * LAMPORTS_PER_SOL + half
+        );
+        // never rounded below min stake
+        state.stake_system.min_stake = LAMPORTS_PER_SOL + half;
+        assert_eq!(
+            state.stake_reserve_amount(LAMPORTS_PER_SOL + 3 * half / 2, 0, 10 * LAMPORTS_PER_SOL),
+            LAMPORTS_PER_SOL + 3 * half / 2
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stake_accounts_limit() -> Result<()> {
+        let mut state = test_state()?;
+        let validator = ValidatorRecord {
+            stake_accounts_count: 3,
+            ..Default::default()
+        };
+        // 0 is unlimited
+        assert!(state.check_stake_accounts_limit(&validator, 100).is_ok());
+        state.max_stake_accounts_per_validator = 5;
+        assert!(state.check_stake_accounts_limit(&validator, 2).is_ok());
+        // all the chunks of a split stake count
+        assert_eq!(
+            state.check_stake_accounts_limit(&validator, 3).err(),
+            Some(error!(MarinadeError::TooManyStakeAccounts))
+        );
+        assert!(!state.stake_accounts_limit_reached(&validator));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stake_operations_throttled() -> Result<()> {
+        let mut state = test_state()?;
+        // 0 is unlimited
+        assert!(!state.stake_operations_throttled(10, 100));
+        state.max_stake_operations_per_epoch = 3;
+        state.on_stake_operations(10, 2);
+        assert_eq!(state.stake_operations_in_epoch(10), 2);
+        assert!(!state.stake_operations_throttled(10, 1));
+        // a stake split into 2 stake accounts does not fit
+        assert!(state.stake_operations_throttled(10, 2));
+        state.on_stake_operations(10, 1);
+        assert!(state.stake_operations_throttled(10, 1));
+        // the next epoch resets the counter
+        assert_eq!(state.stake_operations_in_epoch(11), 0);
+        assert!(!state.stake_operations_throttled(11, 3));
+        state.on_stake_operations(11, 1);
+        assert_eq!(state.stake_operations_this_epoch, 1);
+        assert_eq!(state.stake_operations_epoch, 11);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stake_reserve_noop_counters() -> Result<()> {
+        let mut state = test_state()?;
+        let clock = Clock {
+            epoch: 10,
+            slot: 100,
+            ..Clock::default()
+        };
+        state.on_stake_reserve_status(StakeReserveStatus::ValidatorCooldown, &clock);
+        assert_eq!(state.stake_reserve_report.noop_validator_cooldown, 1);
+        state.on_stake_reserve_status(StakeReserveStatus::ValidatorDelinquent, &clock);
+        assert_eq!(state.stake_reserve_report.noop_validator_delinquent, 1);
+        state.on_stake_reserve_status(StakeReserveStatus::Throttled, &clock);
+        assert_eq!(state.stake_reserve_report.noop_throttled, 1);
+        state.on_stake_reserve_status(StakeReserveStatus::DeltaBelowActionThreshold, &clock);
+        assert_eq!(
+            state.stake_reserve_report.noop_delta_below_action_threshold,
+            1
+        );
+        // not no-ops
+        state.on_stake_reserve_status(StakeReserveStatus::Staked, &clock);
+        assert_eq!(state.stake_reserve_report.noop_nothing_to_stake, 0);
+        // a new epoch resets the counters
+        state.on_stake_reserve_status(
+            StakeReserveStatus::ValidatorCooldown,
+            &Clock { epoch: 11, ..clock },
+        );
+        assert_eq!(state.stake_reserve_report.epoch, 11);
+        assert_eq!(state.stake_reserve_report.noop_validator_cooldown, 1);
+        assert_eq!(
+            state.stake_reserve_last_status.status,
+            StakeReserveStatus::ValidatorCooldown
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stake_lockup() -> Result<()> {
+        let mut state = test_state()?;
+        let withdrawer = Pubkey::new_unique();
+        assert_eq!(state.stake_lockup(&withdrawer), Lockup::default());
+        state.stake_lockup_epoch = 20;
+        let lockup = state.stake_lockup(&withdrawer);
+        assert_eq!(lockup.epoch, 20);
+        assert_eq!(lockup.unix_timestamp, 0);
+        assert_eq!(lockup.custodian, withdrawer);
+        let clock = Clock {
+            epoch: 19,
+            ..Clock::default()
+        };
+        assert!(lockup.is_in_force(&clock, None));
+        // the program signs as the custodian
+        assert!(!lockup.is_in_force(&clock, Some(&withdrawer)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_contains_stake_account() -> Result<()> {
+        let mut stake_list_data =
+            vec![0; List::bytes_for(StakeRecord::serialized_len(), 2) as usize];
+        let mut validator_list_data = [0; 8];
+        let mut state = State::for_tests(
+            StakeSystem::new(
+                &Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                &mut stake_list_data,
+                1000,
+                LAMPORTS_PER_SOL,
+                0,
+                0,
+            )?,
+            ValidatorSystem::new(
+                Pubkey::new_unique(),
+                &mut validator_list_data,
+                Pubkey::new_unique(),
+                0,
+            )?,
+        );
+        let stake_accounts = [Pubkey::new_unique(), Pubkey::new_unique()];
+        for stake_account in &stake_accounts {
+            state.add_stake(
+                &mut stake_list_data,
+                stake_account,
+                &Pubkey::new_unique(),
+                LAMPORTS_PER_SOL,
+                &Clock::default(),
+                0,
+            )?;
+        }
+        for stake_account in &stake_accounts {
+            assert!(state
+                .stake_system
+                .contains_stake_account(&stake_list_data, stake_account)?);
+        }
+        assert!(!state
+            .stake_system
+            .contains_stake_account(&stake_list_data, &Pubkey::new_unique())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_stake_list() -> Result<()> {
+        let mut state = test_state()?;
+        let record_size = state.stake_system.stake_record_size() as usize;
+        state.stake_system.check_stake_list(8)?;
+        assert_eq!(
+            state.stake_system.check_stake_list(7).err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+        // a stale count over the account data
+        state.stake_system.stake_list.count = 2;
+        state.stake_system.check_stake_list(8 + 2 * record_size)?;
+        assert_eq!(
+            state
+                .stake_system
+                .check_stake_list(8 + 2 * record_size - 1)
+                .err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+        Ok(())
     }
 }
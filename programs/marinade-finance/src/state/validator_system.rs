@@ -1,9 +1,11 @@
-//use std::convert::TryInto;
+use crate::{calc::proportional, error::MarinadeError, require_lt, ID};
+use anchor_lang::{prelude::*, solana_program::vote, Discriminator};
+use std::convert::TryInto;
 
-use crate::{calc::proportional, error::MarinadeError, ID};
-use anchor_lang::{prelude::*, Discriminator};
+use super::{list::List, State};
 
-use super::list::List;
+pub const MAX_VALIDATOR_POOLS: usize = 4;
+pub const MAX_PREFERRED_VALIDATORS: usize = 8;
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
 pub struct ValidatorRecord {
@@ -15,11 +17,37 @@ pub struct ValidatorRecord {
     pub score: u32,
     pub last_stake_delta_epoch: u64,
     pub duplication_flag_bump_seed: u8,
+
+    /// Minimal delegation of the stake accounts created for this validator.
+    /// 0 means not set, global stake_system.min_stake is used instead
+    pub min_delegation: u64,
+
+    /// Validator pool index (< MAX_VALIDATOR_POOLS). 0 is the default pool
+    pub pool: u8,
+
+    /// Hard cap of active_balance for stake_reserve. 0 means no cap
+    pub max_stake: u64,
+
+    /// Number of the active (not deactivating) stake accounts delegated to the validator.
+    /// Records migrated from the older layout start counting from 0
+    pub stake_accounts_count: u32,
+
+    /// Set by admin: stake_reserve never adds stake, the existing stake stays until unstaked.
+    /// Differently from score 0 the validator keeps its stake target
+    pub drain_only: bool,
+
+    /// epoch of add_validator, stake_reserve ramps the stake target in this epoch.
+    /// 0 for the validators added before the field
+    pub added_epoch: u64,
 }
 
 impl ValidatorRecord {
     pub const DUPLICATE_FLAG_SEED: &'static [u8] = b"unique_validator";
 
+    pub fn serialized_len() -> u32 {
+        Self::default().try_to_vec().unwrap().len() as u32
+    }
+
     pub fn find_duplication_flag(state: &Pubkey, validator_account: &Pubkey) -> (Pubkey, u8) {
         Pubkey::find_program_address(
             &[
@@ -44,6 +72,24 @@ impl ValidatorRecord {
         ])
     }
 
+    pub fn on_stake_account_added(&mut self) {
+        self.stake_accounts_count += 1;
+    }
+
+    pub fn on_stake_account_removed(&mut self) {
+        // saturating for the accounts not counted before the migration
+        self.stake_accounts_count = self.stake_accounts_count.saturating_sub(1);
+    }
+
+    /// how much stake_reserve can delegate until reaching max_stake
+    pub fn max_stake_room(&self) -> u64 {
+        if self.max_stake == 0 {
+            std::u64::MAX
+        } else {
+            self.max_stake.saturating_sub(self.active_balance)
+        }
+    }
+
     pub fn duplication_flag_address(&self, state: &Pubkey) -> Pubkey {
         self.with_duplication_flag_seeds(state, |seeds| Pubkey::create_program_address(seeds, &ID))
             .unwrap()
@@ -54,6 +100,7 @@ impl ValidatorRecord {
         score: u32,
         state: &Pubkey,
         duplication_flag_address: &Pubkey,
+        added_epoch: u64,
     ) -> Result<Self> {
         let (actual_duplication_flag, duplication_flag_bump_seed) =
             Self::find_duplication_flag(state, &validator_account);
@@ -69,6 +116,12 @@ impl ValidatorRecord {
             score,
             last_stake_delta_epoch: std::u64::MAX, // never
             duplication_flag_bump_seed,
+            min_delegation: 0,
+            pool: 0,
+            max_stake: 0,
+            stake_accounts_count: 0,
+            drain_only: false,
+            added_epoch,
         })
     }
 }
@@ -124,7 +177,7 @@ pub struct ValidatorSystem {
 impl ValidatorSystem {
     pub fn bytes_for_list(count: u32, additional_record_space: u32) -> u32 {
         List::bytes_for(
-            ValidatorRecord::default().try_to_vec().unwrap().len() as u32 + additional_record_space,
+            ValidatorRecord::serialized_len() + additional_record_space,
             count,
         )
     }
@@ -138,8 +191,7 @@ impl ValidatorSystem {
         Ok(Self {
             validator_list: List::new(
                 &ValidatorList::DISCRIMINATOR,
-                ValidatorRecord::default().try_to_vec().unwrap().len() as u32
-                    + additional_record_space,
+                ValidatorRecord::serialized_len() + additional_record_space,
                 validator_list_account,
                 validator_list_data,
             )
@@ -159,14 +211,66 @@ impl ValidatorSystem {
         self.validator_list.len()
     }
 
+    /// fast fail for a stale validator index, before any other processing
+    pub fn check_validator_index(&self, index: u32) -> Result<()> {
+        require_lt!(
+            index,
+            self.validator_count(),
+            MarinadeError::InvalidValidatorIndex
+        );
+        Ok(())
+    }
+
     pub fn validator_list_capacity(&self, validator_list_len: usize) -> Result<u32> {
         self.validator_list.capacity(validator_list_len)
     }
 
+    /// Some(capacity) if the last added validator made the list cross the near capacity threshold
+    pub fn validator_list_crossed_near_capacity(
+        &self,
+        validator_list_len: usize,
+    ) -> Result<Option<u32>> {
+        self.validator_list
+            .crossed_near_capacity(validator_list_len, 1)
+    }
+
+    /// InvalidListAccount when the list header does not match the account data
+    /// or the records are not migrated to the current layout (realloc_validator_list)
+    pub fn check_validator_list(&self, validator_list_len: usize) -> Result<()> {
+        self.validator_list
+            .check_account(validator_list_len, ValidatorRecord::serialized_len())
+            .map_err(|e| e.with_account_name("validator_list"))
+    }
+
     pub fn validator_record_size(&self) -> u32 {
         self.validator_list.item_size()
     }
 
+    /// Record size of the list after migration to the current ValidatorRecord layout.
+    /// Lists created with enough additional_record_space keep their record size
+    pub fn upgraded_validator_record_size(&self) -> u32 {
+        self.validator_record_size()
+            .max(ValidatorRecord::serialized_len())
+    }
+
+    /// Moves the records apart when the list was created for an older (shorter) ValidatorRecord layout.
+    /// New fields of the migrated records are zeroed (unset).
+    /// Returns the old record size if migration happened
+    pub fn upgrade_validator_record_size(
+        &mut self,
+        validator_list_data: &mut [u8],
+    ) -> Result<Option<u32>> {
+        let old = self.validator_record_size();
+        let new = self.upgraded_validator_record_size();
+        if new == old {
+            return Ok(None);
+        }
+        self.validator_list
+            .set_item_size(validator_list_data, new)
+            .map_err(|e| e.with_account_name("validator_list"))?;
+        Ok(Some(old))
+    }
+
     pub fn add(
         &mut self,
         validator_list_data: &mut [u8],
@@ -174,17 +278,25 @@ impl ValidatorSystem {
         score: u32,
         state: &Pubkey,
         duplication_flag_address: &Pubkey,
+        added_epoch: u64,
     ) -> Result<()> {
         self.validator_list
             .push(
                 validator_list_data,
-                ValidatorRecord::new(validator_account, score, state, duplication_flag_address)?,
+                ValidatorRecord::new(
+                    validator_account,
+                    score,
+                    state,
+                    duplication_flag_address,
+                    added_epoch,
+                )?,
             )
             .map_err(|e| e.with_account_name("validator_list"))?;
         self.total_validator_score += score;
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_with_balance(
         &mut self,
         validator_list_data: &mut [u8],
@@ -193,9 +305,15 @@ impl ValidatorSystem {
         balance: u64,
         state: &Pubkey,
         duplication_flag_address: &Pubkey,
+        added_epoch: u64,
     ) -> Result<()> {
-        let mut validator =
-            ValidatorRecord::new(validator_account, score, state, duplication_flag_address)?;
+        let mut validator = ValidatorRecord::new(
+            validator_account,
+            score,
+            state,
+            duplication_flag_address,
+            added_epoch,
+        )?;
         validator.active_balance = balance;
         self.validator_list
             .push(validator_list_data, validator)
@@ -259,6 +377,106 @@ impl ValidatorSystem {
             .map_err(|e| e.with_account_name("validator_list"))
     }
 
+    pub fn set_score(&mut self, validator: &mut ValidatorRecord, score: u32) {
+        self.total_validator_score -= validator.score;
+        validator.score = score;
+        self.total_validator_score += score;
+    }
+
+    /// validator identity (node pubkey) of the vote account.
+    /// All the vote state versions start with the node pubkey after the u32 version tag
+    pub fn vote_account_identity(vote: &AccountInfo) -> Result<Pubkey> {
+        require_keys_eq!(
+            *vote.owner,
+            vote::program::ID,
+            MarinadeError::InvalidVoteAccount
+        );
+        let data = vote.data.borrow();
+        if data.len() < 36 {
+            return err!(MarinadeError::InvalidVoteAccount);
+        }
+        Ok(Pubkey::new_from_array(data[4..36].try_into().unwrap()))
+    }
+
+    /// index of the vote account in validator_list[start_index..start_index + max_scan].
+    /// Reads only the vote of every record
+    pub fn find_by_vote(
+        &self,
+        validator_list_data: &[u8],
+        validator_vote: &Pubkey,
+        start_index: u32,
+        max_scan: u32,
+    ) -> Result<Option<u32>> {
+        let end_index = start_index
+            .saturating_add(max_scan)
+            .min(self.validator_count());
+        for index in start_index..end_index {
+            // validator_account is the first field of ValidatorRecord
+            let validator_account: Pubkey = self
+                .validator_list
+                .get(validator_list_data, index)
+                .map_err(|e| e.with_account_name("validator_list"))?;
+            if validator_account == *validator_vote {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    /// maps the validator identity to the validator record using the candidate vote accounts,
+    /// the vote is searched in validator_list[start_index..start_index + max_scan].
+    /// ValidatorNotFound if no candidate vote is of the identity or the scanned range has not the vote
+    pub fn find_by_identity(
+        &self,
+        validator_list_data: &[u8],
+        identity: &Pubkey,
+        vote_accounts: &[AccountInfo],
+        start_index: u32,
+        max_scan: u32,
+    ) -> Result<(u32, ValidatorRecord)> {
+        let vote = vote_accounts
+            .iter()
+            .find(|vote| {
+                Self::vote_account_identity(vote)
+                    .map(|vote_identity| vote_identity == *identity)
+                    .unwrap_or(false)
+            })
+            .ok_or_else(|| {
+                msg!("No vote account of identity {}", identity);
+                error!(MarinadeError::ValidatorNotFound)
+            })?;
+        let index = self
+            .find_by_vote(validator_list_data, vote.key, start_index, max_scan)?
+            .ok_or_else(|| {
+                msg!(
+                    "Vote {} not found in {} validators from index {}",
+                    vote.key,
+                    max_scan,
+                    start_index
+                );
+                error!(MarinadeError::ValidatorNotFound)
+            })?;
+        Ok((index, self.get(validator_list_data, index)?))
+    }
+
+    /// share of the stake surplus for staking over the target, proportional to the score
+    /// (equal for all the validators if there are no scores)
+    pub fn validator_surplus_share(
+        &self,
+        validator: &ValidatorRecord,
+        surplus: u64,
+    ) -> Result<u64> {
+        if self.total_validator_score == 0 {
+            Ok(surplus / self.validator_count().max(1) as u64)
+        } else {
+            proportional(
+                surplus,
+                validator.score as u64,
+                self.total_validator_score as u64,
+            )
+        }
+    }
+
     pub fn validator_stake_target(
         &self,
         validator: &ValidatorRecord,
@@ -274,3 +492,466 @@ impl ValidatorSystem {
         )
     }
 }
+
+// validator pools, appended to the State layout
+impl State {
+    pub fn pools_enabled(&self) -> bool {
+        self.pool_allocations
+            .iter()
+            .any(|allocation| allocation.basis_points > 0)
+    }
+
+    /// the validator joined the pool (added to the validator list with the score, or moved to the pool)
+    pub fn add_pool_score(&mut self, pool: u8, score: u32) {
+        let pool_score = &mut self.pool_scores[pool as usize];
+        *pool_score = pool_score.checked_add(score).unwrap_or_else(|| {
+            msg!("Pool {} score overflow, recompute_pool_scores", pool);
+            self.pool_scores_valid = false;
+            std::u32::MAX
+        });
+    }
+
+    /// the validator left the pool (removed from the validator list, or moved to another pool).
+    /// The pool score can be lower than the score of the validator only when pool_scores
+    /// are out of sync with the records (e.g. migrated records outside the default pool)
+    pub fn remove_pool_score(&mut self, pool: u8, score: u32) {
+        let pool_score = &mut self.pool_scores[pool as usize];
+        *pool_score = pool_score.checked_sub(score).unwrap_or_else(|| {
+            msg!("Pool {} score underflow, recompute_pool_scores", pool);
+            self.pool_scores_valid = false;
+            0
+        });
+    }
+
+    /// Sums the active balances of validator_list[start_index..start_index + count]
+    /// (start_index 0 restarts the run), the last page sets total_active_balance.
+    /// Returns true when the whole list is summed
+    pub fn reconcile_total_active_balance(
+        &mut self,
+        validator_list_data: &[u8],
+        start_index: u32,
+        count: u32,
+    ) -> Result<bool> {
+        if start_index == 0 {
+            self.reconcile_next_validator_index = 0;
+            self.reconcile_active_balance_sum = 0;
+        }
+        require_eq!(
+            start_index,
+            self.reconcile_next_validator_index,
+            MarinadeError::ReconcilePageOutOfOrder
+        );
+        let validator_count = self.validator_system.validator_count();
+        let end_index = start_index.saturating_add(count).min(validator_count);
+        let mut sum = self.reconcile_active_balance_sum;
+        for index in start_index..end_index {
+            sum = sum
+                .checked_add(
+                    self.validator_system
+                        .get(validator_list_data, index)?
+                        .active_balance,
+                )
+                .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+        }
+
+        if end_index < validator_count {
+            self.reconcile_next_validator_index = end_index;
+            self.reconcile_active_balance_sum = sum;
+            return Ok(false);
+        }
+        self.validator_system.total_active_balance = sum;
+        self.reconcile_next_validator_index = 0;
+        self.reconcile_active_balance_sum = 0;
+        Ok(true)
+    }
+
+    /// Sums the scores of validator_list[start_index..start_index + count] by pool
+    /// (start_index 0 restarts the run), the last page sets pool_scores and pool_scores_valid.
+    /// Returns true when the whole list is summed
+    pub fn recompute_pool_scores(
+        &mut self,
+        validator_list_data: &[u8],
+        start_index: u32,
+        count: u32,
+    ) -> Result<bool> {
+        if start_index == 0 {
+            self.recompute_pool_scores_next_index = 0;
+            self.recompute_pool_scores_sums = [0; MAX_VALIDATOR_POOLS];
+        }
+        require_eq!(
+            start_index,
+            self.recompute_pool_scores_next_index,
+            MarinadeError::ReconcilePageOutOfOrder
+        );
+        let validator_count = self.validator_system.validator_count();
+        let end_index = start_index.saturating_add(count).min(validator_count);
+        let mut sums = self.recompute_pool_scores_sums;
+        for index in start_index..end_index {
+            let validator = self.validator_system.get(validator_list_data, index)?;
+            let sum = sums
+                .get_mut(validator.pool as usize)
+                .ok_or_else(|| error!(MarinadeError::InvalidValidatorPool))?;
+            *sum = sum
+                .checked_add(validator.score)
+                .ok_or_else(|| error!(MarinadeError::CalculationFailure))?;
+        }
+
+        if end_index < validator_count {
+            self.recompute_pool_scores_next_index = end_index;
+            self.recompute_pool_scores_sums = sums;
+            return Ok(false);
+        }
+        self.pool_scores = sums;
+        self.pool_scores_valid = true;
+        self.recompute_pool_scores_next_index = 0;
+        self.recompute_pool_scores_sums = [0; MAX_VALIDATOR_POOLS];
+        Ok(true)
+    }
+
+    /// ValidatorSystem::set_score keeping the score of the validator pool
+    pub fn set_validator_score(&mut self, validator: &mut ValidatorRecord, score: u32) {
+        self.remove_pool_score(validator.pool, validator.score);
+        self.validator_system.set_score(validator, score);
+        self.add_pool_score(validator.pool, score);
+    }
+
+    pub fn set_validator_pool(&mut self, validator: &mut ValidatorRecord, pool: u8) -> Result<()> {
+        require_gt!(
+            MAX_VALIDATOR_POOLS,
+            pool as usize,
+            MarinadeError::InvalidValidatorPool
+        );
+        self.remove_pool_score(validator.pool, validator.score);
+        validator.pool = pool;
+        self.add_pool_score(pool, validator.score);
+        Ok(())
+    }
+
+    /// stake target of the validator: share of total_stake_target by its score,
+    /// within the allocation of its pool when the pools are enabled
+    pub fn validator_stake_target(
+        &self,
+        validator: &ValidatorRecord,
+        total_stake_target: u64,
+    ) -> Result<u64> {
+        if !self.pools_enabled() {
+            return self
+                .validator_system
+                .validator_stake_target(validator, total_stake_target);
+        }
+        // the validator shares the target of its pool with the other validators of the pool
+        let pool_score = self.pool_scores[validator.pool as usize];
+        if pool_score == 0 {
+            return Ok(0);
+        }
+        proportional(
+            self.pool_allocations[validator.pool as usize].apply(total_stake_target),
+            validator.score as u64,
+            pool_score as u64,
+        )
+    }
+
+    /// validators stake_reserve scans to detect all the validators at target
+    pub const ALL_AT_TARGET_MAX_SCAN: u32 = 64;
+
+    /// stake delta can remain when the targets of the validators don't cover it
+    /// (e.g. an allocation to a pool without validators).
+    /// Scans validator_list[start_index..start_index + max_scan]:
+    /// None when all the scanned validators are at target but the range does not cover the list
+    pub fn all_validators_at_target(
+        &self,
+        validator_list_data: &[u8],
+        total_stake_target: u64,
+        start_index: u32,
+        max_scan: u32,
+    ) -> Result<Option<bool>> {
+        let validator_count = self.validator_system.validator_count();
+        let end_index = start_index.saturating_add(max_scan).min(validator_count);
+        for index in start_index..end_index {
+            let validator = self.validator_system.get(validator_list_data, index)?;
+            if validator.active_balance
+                < self.validator_stake_target(&validator, total_stake_target)?
+            {
+                return Ok(Some(false));
+            }
+        }
+        if start_index == 0 && end_index == validator_count {
+            Ok(Some(true))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ValidatorRecord, ValidatorSystem, MAX_VALIDATOR_POOLS};
+    use crate::{
+        error::MarinadeError,
+        state::{list::List, stake_system::StakeSystem},
+        State,
+    };
+    use anchor_lang::prelude::*;
+    use anchor_lang::solana_program::{native_token::LAMPORTS_PER_SOL, vote};
+
+    /// validators of score 100 in the given pools, pool_scores in sync
+    fn state_with_pools(pools: &[u8]) -> (State, Vec<u8>) {
+        let state_address = Pubkey::new_unique();
+        let mut stake_list_data = [0; 8];
+        let mut validator_list_data = vec![
+            0;
+            List::bytes_for(ValidatorRecord::serialized_len(), pools.len() as u32)
+                as usize
+        ];
+        let validator_system = ValidatorSystem::new(
+            Pubkey::new_unique(),
+            &mut validator_list_data,
+            Pubkey::new_unique(),
+            0,
+        )
+        .unwrap();
+        let mut state = State::for_tests(
+            StakeSystem::new(
+                &state_address,
+                Pubkey::new_unique(),
+                &mut stake_list_data,
+                StakeSystem::MIN_UPDATE_WINDOW,
+                LAMPORTS_PER_SOL,
+                0,
+                0,
+            )
+            .unwrap(),
+            validator_system,
+        );
+        for (index, pool) in pools.iter().enumerate() {
+            let vote = Pubkey::new_unique();
+            let (duplication_flag, _) =
+                ValidatorRecord::find_duplication_flag(&state_address, &vote);
+            state
+                .validator_system
+                .add(
+                    &mut validator_list_data,
+                    vote,
+                    100,
+                    &state_address,
+                    &duplication_flag,
+                    0,
+                )
+                .unwrap();
+            state.add_pool_score(0, 100);
+            let mut validator = state
+                .validator_system
+                .get(&validator_list_data, index as u32)
+                .unwrap();
+            state.set_validator_pool(&mut validator, *pool).unwrap();
+            state
+                .validator_system
+                .set(&mut validator_list_data, index as u32, validator)
+                .unwrap();
+        }
+        (state, validator_list_data)
+    }
+
+    #[test]
+    fn test_pool_scores_out_of_sync() {
+        let (mut state, validator_list_data) = state_with_pools(&[0, 1]);
+        state.pool_scores_valid = true;
+        assert_eq!(state.pool_scores, [100, 100, 0, 0]);
+        // the migrated records: all the score is counted in the default pool
+        state.pool_scores = [200, 0, 0, 0];
+        let mut validator = state.validator_system.get(&validator_list_data, 1).unwrap();
+        // the score update of set_validator_score and stake_reserve_with_scores:
+        // the score of pool 1 would underflow
+        state.set_validator_score(&mut validator, 50);
+        assert_eq!(state.pool_scores, [200, 50, 0, 0]);
+        assert!(!state.pool_scores_valid);
+
+        state.pool_scores_valid = true;
+        state.set_validator_pool(&mut validator, 2).unwrap();
+        assert_eq!(state.pool_scores, [200, 0, 50, 0]);
+        state.set_validator_score(&mut validator, 70);
+        assert_eq!(state.pool_scores, [200, 0, 70, 0]);
+        assert!(state.pool_scores_valid);
+        state.pool_scores[2] = 0;
+        state.set_validator_pool(&mut validator, 1).unwrap();
+        assert_eq!(state.pool_scores, [200, 70, 0, 0]);
+        assert!(!state.pool_scores_valid);
+
+        state.pool_scores_valid = true;
+        state.pool_scores[3] = std::u32::MAX;
+        state.add_pool_score(3, 1);
+        assert_eq!(state.pool_scores[3], std::u32::MAX);
+        assert!(!state.pool_scores_valid);
+    }
+
+    #[test]
+    fn test_recompute_pool_scores() {
+        let (mut state, mut validator_list_data) = state_with_pools(&[0, 1, 1]);
+        state.pool_scores = [300, 0, 0, 0];
+        let mut validator = state.validator_system.get(&validator_list_data, 2).unwrap();
+        state.validator_system.set_score(&mut validator, 50);
+        state
+            .validator_system
+            .set(&mut validator_list_data, 2, validator)
+            .unwrap();
+
+        assert_eq!(
+            state.recompute_pool_scores(&validator_list_data, 0, 2),
+            Ok(false)
+        );
+        assert_eq!(state.pool_scores, [300, 0, 0, 0]);
+        assert!(!state.pool_scores_valid);
+        assert_eq!(
+            state.recompute_pool_scores(&validator_list_data, 1, 2),
+            Err(error!(MarinadeError::ReconcilePageOutOfOrder))
+        );
+        assert_eq!(
+            state.recompute_pool_scores(&validator_list_data, 2, 2),
+            Ok(true)
+        );
+        assert_eq!(state.pool_scores, [100, 150, 0, 0]);
+        assert!(state.pool_scores_valid);
+        assert_eq!(state.recompute_pool_scores_next_index, 0);
+        assert_eq!(state.recompute_pool_scores_sums, [0; MAX_VALIDATOR_POOLS]);
+
+        // start_index 0 restarts the run
+        assert_eq!(
+            state.recompute_pool_scores(&validator_list_data, 0, 1),
+            Ok(false)
+        );
+        assert_eq!(
+            state.recompute_pool_scores(&validator_list_data, 0, 10),
+            Ok(true)
+        );
+        assert_eq!(state.pool_scores, [100, 150, 0, 0]);
+    }
+
+    #[test]
+    fn test_reconcile_total_active_balance() {
+        let (mut state, mut validator_list_data) = state_with_pools(&[0, 0, 0]);
+        for (index, active_balance) in [10, 20, 30].into_iter().enumerate() {
+            let mut validator = state
+                .validator_system
+                .get(&validator_list_data, index as u32)
+                .unwrap();
+            validator.active_balance = active_balance * LAMPORTS_PER_SOL;
+            state
+                .validator_system
+                .set(&mut validator_list_data, index as u32, validator)
+                .unwrap();
+        }
+        // drifted
+        state.validator_system.total_active_balance = 1;
+
+        assert_eq!(
+            state.reconcile_total_active_balance(&validator_list_data, 0, 2),
+            Ok(false)
+        );
+        assert_eq!(state.validator_system.total_active_balance, 1);
+        assert_eq!(state.reconcile_next_validator_index, 2);
+        assert_eq!(state.reconcile_active_balance_sum, 30 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            state.reconcile_total_active_balance(&validator_list_data, 1, 2),
+            Err(error!(MarinadeError::ReconcilePageOutOfOrder))
+        );
+        assert_eq!(
+            state.reconcile_total_active_balance(&validator_list_data, 2, 2),
+            Ok(true)
+        );
+        assert_eq!(
+            state.validator_system.total_active_balance,
+            60 * LAMPORTS_PER_SOL
+        );
+        assert_eq!(state.reconcile_next_validator_index, 0);
+        assert_eq!(state.reconcile_active_balance_sum, 0);
+
+        // start_index 0 restarts the run
+        state.validator_system.total_active_balance = 1;
+        assert_eq!(
+            state.reconcile_total_active_balance(&validator_list_data, 0, 1),
+            Ok(false)
+        );
+        assert_eq!(
+            state.reconcile_total_active_balance(&validator_list_data, 0, 10),
+            Ok(true)
+        );
+        assert_eq!(
+            state.validator_system.total_active_balance,
+            60 * LAMPORTS_PER_SOL
+        );
+    }
+
+    #[test]
+    fn test_find_by_identity() {
+        let (state, validator_list_data) = state_with_pools(&[0, 0, 0]);
+        let vote = state
+            .validator_system
+            .get(&validator_list_data, 2)
+            .unwrap()
+            .validator_account;
+        let identity = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut vote_data = [0; 36];
+        vote_data[4..36].copy_from_slice(identity.as_ref());
+        let vote_account = AccountInfo::new(
+            &vote,
+            false,
+            false,
+            &mut lamports,
+            &mut vote_data,
+            &vote::program::ID,
+            false,
+            0,
+        );
+        let vote_accounts = [vote_account];
+        let find = |identity: &Pubkey, start_index, max_scan| {
+            state.validator_system.find_by_identity(
+                &validator_list_data,
+                identity,
+                &vote_accounts,
+                start_index,
+                max_scan,
+            )
+        };
+        let (index, validator) = find(&identity, 0, u32::MAX).unwrap();
+        assert_eq!(index, 2);
+        assert_eq!(validator.validator_account, vote);
+        assert_eq!(find(&identity, 2, 1).unwrap().0, 2);
+        // out of the scanned range
+        assert_eq!(
+            find(&identity, 0, 2).err(),
+            Some(error!(MarinadeError::ValidatorNotFound))
+        );
+        // no vote account of the identity
+        assert_eq!(
+            find(&Pubkey::new_unique(), 0, u32::MAX).err(),
+            Some(error!(MarinadeError::ValidatorNotFound))
+        );
+    }
+
+    #[test]
+    fn test_check_validator_list() {
+        let (mut state, validator_list_data) = state_with_pools(&[0, 0]);
+        assert!(state
+            .validator_system
+            .check_validator_list(validator_list_data.len())
+            .is_ok());
+        // truncated inside the last record
+        assert_eq!(
+            state
+                .validator_system
+                .check_validator_list(validator_list_data.len() - 1)
+                .err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+        // records of an older layout, not reallocated
+        state.validator_system.validator_list.item_size = ValidatorRecord::serialized_len() - 1;
+        assert_eq!(
+            state
+                .validator_system
+                .check_validator_list(validator_list_data.len())
+                .err(),
+            Some(error!(MarinadeError::InvalidListAccount))
+        );
+    }
+}